@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE "session" DROP COLUMN IF EXISTS hysteresis;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE "session" ADD COLUMN hysteresis NUMERIC(3,1) NOT NULL DEFAULT 0;
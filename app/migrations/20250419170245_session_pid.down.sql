@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE "session"
+    DROP COLUMN IF EXISTS control_mode,
+    DROP COLUMN IF EXISTS pid_integral,
+    DROP COLUMN IF EXISTS pid_last_error,
+    DROP COLUMN IF EXISTS pid_window_start,
+    DROP COLUMN IF EXISTS pid_updated_at;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE "session"
+    ADD COLUMN control_mode VARCHAR(250) NOT NULL DEFAULT 'Hysteresis' CHECK (control_mode IN ('Hysteresis', 'Pid')),
+    ADD COLUMN pid_integral REAL NOT NULL DEFAULT 0,
+    ADD COLUMN pid_last_error REAL,
+    ADD COLUMN pid_window_start TIMESTAMP(6),
+    ADD COLUMN pid_updated_at TIMESTAMP(6);
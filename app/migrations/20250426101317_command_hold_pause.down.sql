@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE "command" DROP COLUMN IF EXISTS hold_paused_at;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE "command" ADD COLUMN hold_paused_at TIMESTAMP(6);
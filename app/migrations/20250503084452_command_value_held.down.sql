@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE "command"
+    DROP COLUMN IF EXISTS value_held_duration,
+    DROP COLUMN IF EXISTS value_held_at;
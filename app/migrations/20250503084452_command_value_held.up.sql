@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE "command"
+    ADD COLUMN value_held_duration INTEGER NOT NULL DEFAULT 0, -- in seconds, time accumulated within the band
+    ADD COLUMN value_held_at TIMESTAMP(6);
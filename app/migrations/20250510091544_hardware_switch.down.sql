@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS hardware_switch_hardware_id_switched_at_idx;
+DROP TABLE IF EXISTS "hardware_switch";
@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "hardware_switch" (
+    id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    hardware_id VARCHAR(250) NOT NULL,
+    state VARCHAR(250) NOT NULL CHECK (state IN ('On', 'Off')),
+    switched_at TIMESTAMP(6) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS hardware_switch_hardware_id_switched_at_idx ON "hardware_switch" (hardware_id, switched_at DESC);
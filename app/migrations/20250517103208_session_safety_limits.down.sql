@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE "session"
+    DROP COLUMN IF EXISTS min_temperature,
+    DROP COLUMN IF EXISTS max_temperature,
+    DROP COLUMN IF EXISTS heating_locked_out,
+    DROP COLUMN IF EXISTS cooling_locked_out,
+    DROP COLUMN IF EXISTS faulted_at;
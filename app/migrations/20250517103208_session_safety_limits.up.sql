@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE "session"
+    ADD COLUMN min_temperature NUMERIC(3,1),
+    ADD COLUMN max_temperature NUMERIC(3,1),
+    ADD COLUMN heating_locked_out BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN cooling_locked_out BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN faulted_at TIMESTAMP(6);
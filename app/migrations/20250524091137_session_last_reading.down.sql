@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE "session"
+    DROP COLUMN IF EXISTS last_reading_at;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE "session"
+    ADD COLUMN last_reading_at TIMESTAMP(6);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE "session"
+    DROP COLUMN IF EXISTS reading_window,
+    DROP COLUMN IF EXISTS filtered_temperature,
+    DROP COLUMN IF EXISTS outlier_count;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE "session"
+    ADD COLUMN reading_window REAL[] NOT NULL DEFAULT '{}',
+    ADD COLUMN filtered_temperature REAL,
+    ADD COLUMN outlier_count INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE "command" DROP CONSTRAINT IF EXISTS command_status_check;
+ALTER TABLE "command"
+    ADD CONSTRAINT command_status_check CHECK (status IN ('Planned', 'Running', 'Executed', 'Cancelled'));
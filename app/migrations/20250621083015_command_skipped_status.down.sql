@@ -0,0 +1,5 @@
+-- Add down migration script here
+UPDATE "command" SET status = 'Executed' WHERE status = 'Skipped';
+ALTER TABLE "command" DROP CONSTRAINT IF EXISTS command_status_check;
+ALTER TABLE "command"
+    ADD CONSTRAINT command_status_check CHECK (status IN ('Planned', 'Running', 'Executed', 'Cancelled'));
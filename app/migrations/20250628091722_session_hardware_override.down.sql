@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE "session"
+    DROP COLUMN IF EXISTS override_hardware_type,
+    DROP COLUMN IF EXISTS override_state,
+    DROP COLUMN IF EXISTS override_until;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE "session"
+    ADD COLUMN override_hardware_type VARCHAR(250),
+    ADD COLUMN override_state VARCHAR(250),
+    ADD COLUMN override_until TIMESTAMP(6);
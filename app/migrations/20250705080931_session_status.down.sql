@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE "session"
+    DROP COLUMN IF EXISTS status,
+    DROP COLUMN IF EXISTS completed_at;
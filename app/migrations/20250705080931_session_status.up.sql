@@ -0,0 +1,29 @@
+-- Add up migration script here
+ALTER TABLE "session"
+    ADD COLUMN status VARCHAR(250) NOT NULL DEFAULT 'Active' CHECK (status IN ('Active', 'Completed', 'Cancelled', 'Faulted')),
+    ADD COLUMN completed_at TIMESTAMP(6);
+
+UPDATE "session" SET status = 'Faulted' WHERE faulted_at IS NOT NULL;
+UPDATE "session"
+SET
+    status = 'Cancelled',
+    completed_at = (SELECT MAX(status_date) FROM "command" WHERE "command".session_id = "session".id)
+-- a rescheduled session also has a Cancelled command, it is only cancelled once nothing is left to run after it
+WHERE NOT EXISTS (
+    SELECT 1 FROM "command" WHERE "command".session_id = "session".id AND "command".status IN ('Planned', 'Running')
+)
+AND (
+    SELECT "command".status FROM "command"
+    WHERE "command".session_id = "session".id
+    ORDER BY "command".status_date DESC, "command".execution_order DESC
+    LIMIT 1
+) = 'Cancelled';
+UPDATE "session"
+SET
+    status = 'Completed',
+    completed_at = (SELECT MAX(status_date) FROM "command" WHERE "command".session_id = "session".id)
+WHERE status IN ('Active', 'Faulted')
+AND EXISTS (SELECT 1 FROM "command" WHERE "command".session_id = "session".id)
+AND NOT EXISTS (
+    SELECT 1 FROM "command" WHERE "command".session_id = "session".id AND "command".status IN ('Planned', 'Running')
+);
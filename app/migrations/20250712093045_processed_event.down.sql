@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS processed_event_processed_at_idx;
+DROP TABLE IF EXISTS "processed_event";
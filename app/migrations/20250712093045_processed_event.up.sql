@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "processed_event" (
+    event_id UUID PRIMARY KEY,
+    processed_at TIMESTAMP(6) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS processed_event_processed_at_idx ON "processed_event" (processed_at);
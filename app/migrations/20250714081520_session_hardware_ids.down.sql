@@ -0,0 +1,2 @@
+-- Add down migration script here
+UPDATE "session" SET cooling_id = heating_id, heating_id = cooling_id;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- sessions were stored with their heating and cooling hardware ids reversed, the right hand side uses the old values
+UPDATE "session" SET cooling_id = heating_id, heating_id = cooling_id;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS command_event_session_id_idx;
+DROP TABLE IF EXISTS "command_event";
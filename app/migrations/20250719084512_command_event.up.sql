@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "command_event" (
+    id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    session_id INTEGER NOT NULL,
+    -- no foreign key, the events of the planned commands replaced by a reschedule are kept
+    command_uuid UUID,
+    kind VARCHAR(250) NOT NULL CHECK (kind IN ('StatusChanged', 'ValueReached', 'ValueReset', 'HoldPaused', 'HardwareSwitched')),
+    status VARCHAR(250),
+    hardware_id VARCHAR(250),
+    hardware_state VARCHAR(250) CHECK (hardware_state IN ('On', 'Off')),
+    is_forced BOOLEAN,
+    occurred_at TIMESTAMP(6) NOT NULL,
+  CONSTRAINT fk_session
+      FOREIGN KEY (session_id)
+      REFERENCES "session" (id)
+      ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS command_event_session_id_idx ON "command_event" (session_id, id);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS temperature_reading_measured_at_idx;
+DROP INDEX IF EXISTS temperature_reading_session_id_measured_at_idx;
+DROP TABLE IF EXISTS "temperature_reading";
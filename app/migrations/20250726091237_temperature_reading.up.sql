@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "temperature_reading" (
+    id INTEGER PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    session_id INTEGER NOT NULL REFERENCES "session" (id) ON DELETE CASCADE,
+    measured_at TIMESTAMP(6) NOT NULL,
+    temperature REAL NOT NULL, -- raw reading, before the session filter
+    source VARCHAR(255)
+);
+
+CREATE INDEX IF NOT EXISTS temperature_reading_session_id_measured_at_idx ON "temperature_reading" (session_id, measured_at);
+CREATE INDEX IF NOT EXISTS temperature_reading_measured_at_idx ON "temperature_reading" (measured_at);
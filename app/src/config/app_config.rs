@@ -1,4 +1,4 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use serde::Deserialize;
 use std::{
@@ -8,12 +8,30 @@ use std::{
 
 use crate::utils::{file::FileUtils, pem::PemUtils};
 
-use super::{nats_config::NatsConfig, postgres_config::PostgresConfig};
+use super::{
+    controller_config::ControllerConfig, ha_config::HaConfig, inbox_config::InboxConfig,
+    migration_config::MigrationConfig, nats_config::NatsConfig, postgres_config::PostgresConfig,
+    reading_config::ReadingConfig, tick_config::TickConfig, watchdog_config::WatchdogConfig,
+};
 
 #[derive(Deserialize)]
 pub struct AppConfig {
     pub nats: NatsConfig,
     pub postgres: PostgresConfig,
+    #[serde(default)]
+    pub controller: ControllerConfig,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    #[serde(default)]
+    pub tick: TickConfig,
+    #[serde(default)]
+    pub inbox: InboxConfig,
+    #[serde(default)]
+    pub readings: ReadingConfig,
+    #[serde(default)]
+    pub ha: HaConfig,
+    #[serde(default)]
+    pub migration: MigrationConfig,
 }
 
 impl AppConfig {
@@ -21,7 +39,25 @@ impl AppConfig {
         let project_root = env!("CARGO_MANIFEST_DIR");
         let file_path = Path::new(project_root).join(file_name);
         let content = fs::read_to_string(file_path).map_err(|err| anyhow!("Could not read config file: {:?}", err))?;
-        toml::from_str(&content).map_err(|err| anyhow!("Could not parse TOML config: {:?}", err))
+        let config: AppConfig =
+            toml::from_str(&content).map_err(|err| anyhow!("Could not parse TOML config: {:?}", err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // a zero interval makes the periodic tasks panic once they start
+    fn validate(&self) -> anyhow::Result<()> {
+        for (name, interval) in [
+            ("tick.interval", self.tick.interval),
+            ("watchdog.check_interval", self.watchdog.check_interval),
+            ("inbox.purge_interval", self.inbox.purge_interval),
+            ("readings.purge_interval", self.readings.purge_interval),
+        ] {
+            if interval == 0 {
+                bail!("{name} must be a positive number of seconds");
+            }
+        }
+        Ok(())
     }
 }
 
@@ -83,6 +119,26 @@ mod tests {
         AppConfig::load("config.toml").unwrap();
     }
 
+    #[test]
+    fn should_load_config_template() {
+        AppConfig::load("config.template.toml").unwrap();
+    }
+
+    #[test]
+    fn should_reject_a_zero_interval() {
+        let zeroes: [fn(&mut AppConfig); 4] = [
+            |config| config.tick.interval = 0,
+            |config| config.watchdog.check_interval = 0,
+            |config| config.inbox.purge_interval = 0,
+            |config| config.readings.purge_interval = 0,
+        ];
+        for zero in zeroes {
+            let mut config = AppConfig::load("config.template.toml").unwrap();
+            zero(&mut config);
+            assert!(config.validate().is_err());
+        }
+    }
+
     #[test]
     fn should_return_correct_cert_file_path() {
         let cert_conf = CertConfig {
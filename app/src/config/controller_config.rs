@@ -0,0 +1,158 @@
+use internal::domain::{
+    controller::{ControllerSettings, HoldMode},
+    filter::{FilterKind, FilterSettings},
+    pid::PidSettings,
+    protection::HardwareProtection,
+    session::TemperatureLimits,
+};
+use serde::Deserialize;
+use time::Duration;
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ControllerConfig {
+    pub hysteresis: f32,
+    pub pid: PidConfig,
+    pub hold_mode: HoldModeConfig,
+    pub cooling: ProtectionConfig,
+    pub heating: ProtectionConfig,
+    // °C, can be overridden per session in the schedule event
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+    pub filter: FilterConfig,
+}
+
+// durations are in seconds, zero disables the rule
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ProtectionConfig {
+    pub min_run: i64,
+    pub min_off: i64,
+    pub max_toggles_per_hour: Option<u32>,
+}
+
+impl From<&ProtectionConfig> for HardwareProtection {
+    fn from(value: &ProtectionConfig) -> Self {
+        HardwareProtection {
+            min_run: Duration::seconds(value.min_run),
+            min_off: Duration::seconds(value.min_off),
+            max_toggles_per_hour: value.max_toggles_per_hour,
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Clone)]
+pub enum HoldModeConfig {
+    #[default]
+    Reset,
+    Pause,
+    Accumulate,
+}
+
+#[derive(Deserialize, Default, Clone)]
+pub enum FilterKindConfig {
+    #[default]
+    None,
+    MovingAverage,
+    Median,
+    Exponential,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct FilterConfig {
+    pub kind: FilterKindConfig,
+    pub window: usize,
+    // only used by the Exponential filter
+    pub alpha: f32,
+    // °C
+    pub max_jump: Option<f32>,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            kind: FilterKindConfig::None,
+            window: FilterSettings::default().window,
+            alpha: 0.3,
+            max_jump: None,
+        }
+    }
+}
+
+impl From<&FilterConfig> for FilterSettings {
+    fn from(value: &FilterConfig) -> Self {
+        FilterSettings {
+            kind: match value.kind {
+                FilterKindConfig::None => FilterKind::None,
+                FilterKindConfig::MovingAverage => FilterKind::MovingAverage,
+                FilterKindConfig::Median => FilterKind::Median,
+                FilterKindConfig::Exponential => FilterKind::Exponential { alpha: value.alpha },
+            },
+            window: value.window,
+            max_jump: value.max_jump,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct PidConfig {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    // in seconds
+    pub window: i64,
+}
+
+impl Default for PidConfig {
+    fn default() -> Self {
+        let settings = PidSettings::default();
+        PidConfig {
+            kp: settings.kp,
+            ki: settings.ki,
+            kd: settings.kd,
+            window: settings.window.whole_seconds(),
+        }
+    }
+}
+
+impl From<&ControllerConfig> for ControllerSettings {
+    fn from(value: &ControllerConfig) -> Self {
+        ControllerSettings {
+            hysteresis: value.hysteresis,
+            pid: PidSettings {
+                kp: value.pid.kp,
+                ki: value.pid.ki,
+                kd: value.pid.kd,
+                window: Duration::seconds(value.pid.window),
+            },
+            hold_mode: match value.hold_mode {
+                HoldModeConfig::Reset => HoldMode::Reset,
+                HoldModeConfig::Pause => HoldMode::Pause,
+                HoldModeConfig::Accumulate => HoldMode::Accumulate,
+            },
+            cooling_protection: (&value.cooling).into(),
+            heating_protection: (&value.heating).into(),
+            temperature_limits: TemperatureLimits {
+                min: value.min_temperature,
+                max: value.max_temperature,
+            },
+            filter: (&value.filter).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_default_to_domain_pid_settings() {
+        let settings = ControllerSettings::from(&ControllerConfig::default());
+        assert_eq!(settings.pid, PidSettings::default());
+        assert!(!settings.cooling_protection.is_enabled());
+        assert!(!settings.heating_protection.is_enabled());
+        assert!(!settings.filter.is_enabled());
+    }
+}
@@ -0,0 +1,22 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct HaConfig {
+    // when disabled, the instance drives the hardware on its own
+    pub enabled: bool,
+    // key of the Postgres advisory lock the instances compete for
+    pub lock_id: i64,
+    // in seconds, how long the standby may take to lead once the connection of the leader dropped
+    pub takeover_timeout: u64,
+}
+
+impl Default for HaConfig {
+    fn default() -> Self {
+        HaConfig {
+            enabled: false,
+            lock_id: 7_598_537_620_513_865_573,
+            takeover_timeout: 30,
+        }
+    }
+}
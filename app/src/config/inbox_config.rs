@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct InboxConfig {
+    // in seconds, how long a processed event id is kept to detect its redeliveries
+    pub retention: i64,
+    // in seconds
+    pub purge_interval: u64,
+}
+
+impl Default for InboxConfig {
+    fn default() -> Self {
+        InboxConfig {
+            retention: 604800,
+            purge_interval: 3600,
+        }
+    }
+}
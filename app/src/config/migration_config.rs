@@ -0,0 +1,15 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MigrationConfig {
+    // applies the pending migrations embedded in the binary before starting,
+    // the app refuses to start on a pending or unknown migration otherwise
+    pub on_boot: bool,
+}
+
+impl Default for MigrationConfig {
+    fn default() -> Self {
+        MigrationConfig { on_boot: true }
+    }
+}
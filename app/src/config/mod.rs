@@ -1,3 +1,10 @@
 pub mod app_config;
+pub mod controller_config;
+pub mod ha_config;
+pub mod inbox_config;
+pub mod migration_config;
 pub mod nats_config;
 pub mod postgres_config;
+pub mod reading_config;
+pub mod tick_config;
+pub mod watchdog_config;
@@ -12,6 +12,7 @@ pub struct NatsConfig {
 #[derive(Deserialize, Default, Clone)]
 pub struct ConsumerConfig {
     pub subjects: Vec<String>,
+    #[allow(dead_code)]
     pub delivery_subject: String,
     pub name: String,
 }
@@ -20,6 +21,8 @@ pub struct ConsumerConfig {
 //https://shelly-api-docs.shelly.cloud/gen1/#shelly-plug-plugs-mqtt
 pub struct PublisherConfig {
     pub command_topic_template: String,
+    pub alert_subject: String,
+    pub session_event_subject: String,
 }
 
 #[derive(Deserialize, Default, Clone)]
@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ReadingConfig {
+    // in seconds, how long the raw readings of a session are kept
+    pub retention: i64,
+    // in seconds
+    pub purge_interval: u64,
+}
+
+impl Default for ReadingConfig {
+    fn default() -> Self {
+        ReadingConfig {
+            retention: 2592000,
+            purge_interval: 3600,
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct TickConfig {
+    // in seconds, how often running commands are checked for an elapsed holding duration
+    pub interval: u64,
+}
+
+impl Default for TickConfig {
+    fn default() -> Self {
+        TickConfig { interval: 60 }
+    }
+}
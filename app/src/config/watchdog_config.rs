@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    // in seconds, time without tracking message after which the hardware of a session is stopped
+    pub silence: i64,
+    // in seconds
+    pub check_interval: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            silence: 900,
+            check_interval: 60,
+        }
+    }
+}
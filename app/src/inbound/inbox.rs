@@ -0,0 +1,34 @@
+use internal::port::command::CommandExecutorDriverPort;
+use log::{debug, error};
+use time::{Duration, OffsetDateTime};
+
+use crate::config::inbox_config::InboxConfig;
+
+// Forgets the processed events once JetStream can't redeliver them anymore, the inbox table would grow forever otherwise
+pub struct InboxPurger {
+    retention: Duration,
+    purge_interval: std::time::Duration,
+}
+
+impl InboxPurger {
+    pub fn new(config: &InboxConfig) -> Self {
+        InboxPurger {
+            retention: Duration::seconds(config.retention),
+            purge_interval: std::time::Duration::from_secs(config.purge_interval),
+        }
+    }
+
+    pub async fn run(&self, executor: &impl CommandExecutorDriverPort) {
+        let mut interval = tokio::time::interval(self.purge_interval);
+        loop {
+            interval.tick().await;
+            match executor
+                .purge_processed_events(OffsetDateTime::now_utc() - self.retention)
+                .await
+            {
+                Ok(purged) => debug!("{purged} processed event(s) purged"),
+                Err(e) => error!("Unable to purge processed events: {e}"),
+            }
+        }
+    }
+}
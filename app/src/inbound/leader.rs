@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use sqlx::{Connection, PgConnection, postgres::PgConnectOptions, query, query_scalar};
+
+use crate::config::ha_config::HaConfig;
+
+// Only the instance holding the advisory lock drives the hardware, the other one stands by.
+// Postgres releases the lock once the connection of its holder drops.
+pub struct LeaderElection {
+    enabled: bool,
+    options: PgConnectOptions,
+    lock_id: i64,
+    takeover_timeout: Duration,
+}
+
+impl LeaderElection {
+    pub fn new(config: &HaConfig, options: PgConnectOptions) -> Self {
+        LeaderElection {
+            enabled: config.enabled,
+            options,
+            lock_id: config.lock_id,
+            takeover_timeout: Duration::from_secs(config.takeover_timeout),
+        }
+    }
+
+    // runs the work while this instance leads, it is started again once the leadership is regained
+    pub async fn lead(&self, work: impl AsyncFn()) {
+        if !self.enabled {
+            return work().await;
+        }
+        loop {
+            let conn = self.acquire().await;
+            info!("Leadership acquired");
+            tokio::select! {
+                _ = self.hold(conn) => warn!("Leadership lost, standing by"),
+                _ = work() => return,
+            }
+        }
+    }
+
+    // the standby competes for the lock this often, the leader checks its connection as often
+    fn check_interval(&self) -> Duration {
+        (self.takeover_timeout / 4).max(Duration::from_secs(1))
+    }
+
+    async fn acquire(&self) -> PgConnection {
+        let mut interval = tokio::time::interval(self.check_interval());
+        loop {
+            interval.tick().await;
+            match self.try_acquire().await {
+                Ok(Some(conn)) => return conn,
+                Ok(None) => debug!("Another instance leads, standing by"),
+                Err(e) => error!("Unable to compete for the leadership: {e}"),
+            }
+        }
+    }
+
+    // returns the connection holding the lock, None when another instance holds it
+    async fn try_acquire(&self) -> anyhow::Result<Option<PgConnection>> {
+        let mut conn = PgConnection::connect_with(&self.options).await?;
+        // the server drops the connection of a leader that vanished, and so releases its lock, within the takeover timeout
+        let keepalive = self.check_interval().as_secs();
+        query(&format!("SET tcp_keepalives_idle = {keepalive}"))
+            .execute(&mut conn)
+            .await?;
+        query(&format!("SET tcp_keepalives_interval = {keepalive}"))
+            .execute(&mut conn)
+            .await?;
+        query("SET tcp_keepalives_count = 3").execute(&mut conn).await?;
+        let is_acquired: bool = query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(self.lock_id)
+            .fetch_one(&mut conn)
+            .await?;
+        if is_acquired {
+            Ok(Some(conn))
+        } else {
+            conn.close().await?;
+            Ok(None)
+        }
+    }
+
+    // returns once the connection holding the lock is lost, the lock may be held by the standby then
+    async fn hold(&self, mut conn: PgConnection) {
+        let mut interval = tokio::time::interval(self.check_interval());
+        loop {
+            interval.tick().await;
+            match tokio::time::timeout(self.check_interval(), conn.ping()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    error!("Leadership connection lost: {e}");
+                    return;
+                }
+                Err(_) => {
+                    error!("Leadership connection unresponsive");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Connection, PgPool};
+
+    use crate::config::ha_config::HaConfig;
+
+    use super::LeaderElection;
+
+    fn election(pool: &PgPool) -> LeaderElection {
+        LeaderElection::new(
+            &HaConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            pool.connect_options().as_ref().clone(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn should_let_a_single_instance_lead_until_its_connection_drops(pool: PgPool) -> anyhow::Result<()> {
+        let (leader, standby) = (election(&pool), election(&pool));
+        let conn = leader.try_acquire().await?.expect("the first instance should lead");
+        assert!(standby.try_acquire().await?.is_none());
+        conn.close().await?;
+        // the backend of the closed connection releases the lock as it exits
+        let mut standby_conn = None;
+        for _ in 0..50 {
+            standby_conn = standby.try_acquire().await?;
+            if standby_conn.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(standby_conn.is_some());
+        assert!(leader.try_acquire().await?.is_none());
+        Ok(())
+    }
+}
@@ -1,2 +1,8 @@
+pub mod inbox;
+pub mod leader;
 pub mod model;
 pub mod nats;
+pub mod reading;
+pub mod serial_executor;
+pub mod tick;
+pub mod watchdog;
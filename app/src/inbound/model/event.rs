@@ -1,6 +1,12 @@
 use anyhow::{Result, bail};
-use internal::domain::message::{
-    FermentationStep, Hardware, HardwareType, Message, MessageType, Rate, ScheduleMessageData, TrackingMessageData,
+use internal::domain::{
+    message::{
+        CancelMessageData, FermentationStep, Hardware, HardwareType, Message, MessageType, OverrideMessageData,
+        PauseMessageData, Rate, RescheduleMessageData, ResetMessageData, ResumeMessageData, ScheduleMessageData,
+        SkipMessageData, TrackingMessageData,
+    },
+    protection::HardwareState,
+    session::ControlMode,
 };
 use serde::Deserialize;
 use serde_json;
@@ -23,10 +29,59 @@ pub enum EventData {
         session_id: Uuid,
         hardwares: Vec<HardwareData>,
         steps: Vec<FermentationStepData>,
+        #[serde(default)]
+        hysteresis: Option<f32>,
+        #[serde(default)]
+        control_mode: Option<String>,
+        #[serde(default)]
+        min_temperature: Option<f32>,
+        #[serde(default)]
+        max_temperature: Option<f32>,
     },
     Tracking {
         session_id: Uuid,
         temperature: f32,
+        // the event sent_at is used when the hydrometer doesn't provide it
+        #[serde(default, with = "time::serde::rfc3339::option")]
+        measured_at: Option<OffsetDateTime>,
+        // the hydrometer that took the reading
+        #[serde(default)]
+        source: Option<String>,
+    },
+    // lifts the hardware lockout of a session once a temperature limit has been crossed
+    Reset {
+        session_id: Uuid,
+    },
+    // aborts the session, its hardware is stopped and its remaining commands are cancelled
+    Cancel {
+        session_id: Uuid,
+    },
+    // suspends the control of a session, e.g. while the fermenter is moved, without losing its progress
+    Pause {
+        session_id: Uuid,
+    },
+    Resume {
+        session_id: Uuid,
+    },
+    // replaces the profile of a session that has not been executed yet
+    Reschedule {
+        session_id: Uuid,
+        steps: Vec<FermentationStepData>,
+        #[serde(default)]
+        replace_running: bool,
+    },
+    // advances the profile without waiting for the holding duration, to the next command or to the given step
+    Skip {
+        session_id: Uuid,
+        #[serde(default)]
+        to_step: Option<i32>,
+    },
+    // forces a hardware on or off, the profile takes over again once the duration, in minutes, is over
+    Override {
+        session_id: Uuid,
+        hardware_type: String,
+        state: String,
+        duration: i64,
     },
 }
 
@@ -76,18 +131,39 @@ impl TryFrom<HardwareData> for Hardware {
     type Error = anyhow::Error;
 
     fn try_from(value: HardwareData) -> anyhow::Result<Self, Self::Error> {
-        match value.hardware_type.to_lowercase().as_str() {
-            "heating" => Ok(Hardware {
-                id: value.id.to_string(),
-                hardware_type: HardwareType::Heating,
-            }),
-            "cooling" => Ok(Hardware {
-                id: value.id.to_string(),
-                hardware_type: HardwareType::Cooling,
-            }),
-            _ => bail!("Unknown hardware type: {}", value.hardware_type),
-        }
+        Ok(Hardware {
+            hardware_type: parse_hardware_type(&value.hardware_type)?,
+            id: value.id,
+        })
+    }
+}
+fn parse_hardware_type(value: &str) -> anyhow::Result<HardwareType> {
+    match value.to_lowercase().as_str() {
+        "heating" => Ok(HardwareType::Heating),
+        "cooling" => Ok(HardwareType::Cooling),
+        _ => bail!("Unknown hardware type: {}", value),
+    }
+}
+fn parse_control_mode(value: &str) -> anyhow::Result<ControlMode> {
+    match value.to_lowercase().as_str() {
+        "hysteresis" => Ok(ControlMode::Hysteresis),
+        "pid" => Ok(ControlMode::Pid),
+        _ => bail!("Unknown control mode: {}", value),
+    }
+}
+fn parse_hardware_state(value: &str) -> anyhow::Result<HardwareState> {
+    match value.to_lowercase().as_str() {
+        "on" => Ok(HardwareState::On),
+        "off" => Ok(HardwareState::Off),
+        _ => bail!("Unknown hardware state: {}", value),
+    }
+}
+// an override that is already over would be taken back by the profile on the next reading
+fn parse_override_duration(minutes: i64) -> anyhow::Result<Duration> {
+    if minutes <= 0 {
+        bail!("Invalid override duration: {minutes}, it must be a positive number of minutes");
     }
+    Ok(Duration::minutes(minutes))
 }
 impl TryFrom<Event> for Message {
     type Error = anyhow::Error;
@@ -98,69 +174,191 @@ impl TryFrom<Event> for Message {
                 id: value.id,
                 sent_at: value.sent_at,
                 version: value.version,
-                message_type: MessageType::Schedule(ScheduleMessageData::try_from(value.data)?),
+                message_type: MessageType::Schedule(ScheduleMessageData::try_from(value)?),
             },
             EventData::Tracking { .. } => Message {
                 id: value.id,
                 sent_at: value.sent_at,
                 version: value.version,
-                message_type: MessageType::Tracking(TrackingMessageData::try_from(value.data)?),
+                message_type: MessageType::Tracking(TrackingMessageData::try_from(value)?),
+            },
+            EventData::Reset { session_id } => Message {
+                id: value.id,
+                sent_at: value.sent_at,
+                version: value.version,
+                message_type: MessageType::Reset(ResetMessageData {
+                    event_id: value.id,
+                    session_id: *session_id,
+                }),
+            },
+            EventData::Cancel { session_id } => Message {
+                id: value.id,
+                sent_at: value.sent_at,
+                version: value.version,
+                message_type: MessageType::Cancel(CancelMessageData {
+                    event_id: value.id,
+                    session_id: *session_id,
+                }),
+            },
+            EventData::Pause { session_id } => Message {
+                id: value.id,
+                sent_at: value.sent_at,
+                version: value.version,
+                message_type: MessageType::Pause(PauseMessageData {
+                    event_id: value.id,
+                    session_id: *session_id,
+                }),
+            },
+            EventData::Resume { session_id } => Message {
+                id: value.id,
+                sent_at: value.sent_at,
+                version: value.version,
+                message_type: MessageType::Resume(ResumeMessageData {
+                    event_id: value.id,
+                    session_id: *session_id,
+                }),
+            },
+            EventData::Reschedule {
+                session_id,
+                steps,
+                replace_running,
+            } => Message {
+                id: value.id,
+                sent_at: value.sent_at,
+                version: value.version,
+                message_type: MessageType::Reschedule(RescheduleMessageData {
+                    event_id: value.id,
+                    session_id: *session_id,
+                    steps: steps.iter().map(FermentationStep::from).collect(),
+                    replace_running: *replace_running,
+                }),
+            },
+            EventData::Skip { session_id, to_step } => Message {
+                id: value.id,
+                sent_at: value.sent_at,
+                version: value.version,
+                message_type: MessageType::Skip(SkipMessageData {
+                    event_id: value.id,
+                    session_id: *session_id,
+                    to_step: *to_step,
+                }),
+            },
+            EventData::Override {
+                session_id,
+                hardware_type,
+                state,
+                duration,
+            } => Message {
+                id: value.id,
+                sent_at: value.sent_at,
+                version: value.version,
+                message_type: MessageType::Override(OverrideMessageData {
+                    event_id: value.id,
+                    session_id: *session_id,
+                    hardware_type: parse_hardware_type(hardware_type)?,
+                    state: parse_hardware_state(state)?,
+                    duration: parse_override_duration(*duration)?,
+                }),
             },
         })
     }
 }
 
-impl TryFrom<EventData> for TrackingMessageData {
+impl TryFrom<Event> for TrackingMessageData {
     type Error = anyhow::Error;
 
-    fn try_from(value: EventData) -> std::result::Result<Self, Self::Error> {
-        Ok(match value {
+    fn try_from(value: Event) -> std::result::Result<Self, Self::Error> {
+        Ok(match value.data {
             EventData::Schedule { .. } => {
                 bail!("Cannot convert schedule event data to tracking message data")
             }
+            EventData::Reset { .. } => {
+                bail!("Cannot convert reset event data to tracking message data")
+            }
+            EventData::Cancel { .. }
+            | EventData::Pause { .. }
+            | EventData::Resume { .. }
+            | EventData::Skip { .. }
+            | EventData::Override { .. } => {
+                bail!("Cannot convert session event data to tracking message data")
+            }
+            EventData::Reschedule { .. } => {
+                bail!("Cannot convert reschedule event data to tracking message data")
+            }
             EventData::Tracking {
                 session_id,
                 temperature,
+                measured_at,
+                source,
             } => TrackingMessageData {
                 session_id,
                 temperature,
+                measured_at: measured_at.unwrap_or(value.sent_at),
+                event_id: Some(value.id),
+                source,
             },
         })
     }
 }
-impl TryFrom<EventData> for ScheduleMessageData {
+impl TryFrom<Event> for ScheduleMessageData {
     type Error = anyhow::Error;
 
-    fn try_from(value: EventData) -> std::result::Result<Self, Self::Error> {
-        Ok(match value {
+    fn try_from(value: Event) -> std::result::Result<Self, Self::Error> {
+        Ok(match value.data {
             EventData::Schedule {
                 session_id,
                 hardwares,
                 steps,
+                hysteresis,
+                control_mode,
+                min_temperature,
+                max_temperature,
             } => ScheduleMessageData {
+                event_id: value.id,
                 session_id,
                 hardwares: hardwares
                     .into_iter()
                     .map(Hardware::try_from)
                     .collect::<Result<Vec<Hardware>, _>>()?,
                 steps: steps.iter().map(FermentationStep::from).collect(),
+                hysteresis,
+                control_mode: control_mode.as_deref().map(parse_control_mode).transpose()?,
+                min_temperature,
+                max_temperature,
             },
             EventData::Tracking { .. } => {
                 bail!("Cannot convert tracking event data to schedule message data")
             }
+            EventData::Reset { .. } => {
+                bail!("Cannot convert reset event data to schedule message data")
+            }
+            EventData::Cancel { .. }
+            | EventData::Pause { .. }
+            | EventData::Resume { .. }
+            | EventData::Skip { .. }
+            | EventData::Override { .. } => {
+                bail!("Cannot convert session event data to schedule message data")
+            }
+            EventData::Reschedule { .. } => {
+                bail!("Cannot convert reschedule event data to schedule message data")
+            }
         })
     }
 }
 #[cfg(test)]
 mod tests {
 
-    use internal::domain::message::{FermentationStep, Hardware, HardwareType, Message, MessageType};
-    use time::{Duration, OffsetDateTime};
+    use internal::domain::{
+        message::{FermentationStep, Hardware, HardwareType, Message, MessageType},
+        protection::HardwareState,
+        session::ControlMode,
+    };
+    use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
     use uuid::Uuid;
 
     use crate::inbound::model::event::{FermentationStepData, HardwareData, RateData};
 
-    use super::{Event, EventData};
+    use super::{Event, EventData, parse_control_mode};
 
     #[test]
     fn should_map_schedule_event_to_message() {
@@ -176,6 +374,10 @@ mod tests {
                 duration: 1,
                 rate: None,
             }],
+            hysteresis: Some(0.5),
+            control_mode: Some("pid".to_string()),
+            min_temperature: None,
+            max_temperature: Some(30.0),
         };
         let event = Event {
             id: Uuid::new_v4(),
@@ -189,6 +391,7 @@ mod tests {
         assert_eq!(msg.id, event.id);
         match msg.message_type {
             MessageType::Schedule(schedule_message_data) => {
+                assert_eq!(schedule_message_data.event_id, event.id);
                 assert_eq!(schedule_message_data.hardwares.len(), 1);
                 let hw = schedule_message_data.hardwares.first().unwrap();
                 assert_eq!(hw.hardware_type, HardwareType::Cooling);
@@ -199,8 +402,12 @@ mod tests {
                 assert_eq!(step.duration, Duration::hours(1));
                 assert_eq!(step.target_temperature, 21.0);
                 assert_eq!(step.position, 0);
+                assert_eq!(schedule_message_data.hysteresis, Some(0.5));
+                assert_eq!(schedule_message_data.control_mode, Some(ControlMode::Pid));
+                assert_eq!(schedule_message_data.min_temperature, None);
+                assert_eq!(schedule_message_data.max_temperature, Some(30.0));
             }
-            MessageType::Tracking(_) => panic!("should be an schedule message"),
+            _ => panic!("should be an schedule message"),
         }
     }
 
@@ -243,4 +450,172 @@ mod tests {
             }
         });
     }
+    #[test]
+    fn should_parse_control_mode() {
+        assert_eq!(parse_control_mode("Hysteresis").unwrap(), ControlMode::Hysteresis);
+        assert_eq!(parse_control_mode("PID").unwrap(), ControlMode::Pid);
+        parse_control_mode("fuzzy").unwrap_err();
+    }
+
+    #[test]
+    fn should_map_reset_event_to_message() {
+        let session_id = Uuid::new_v4();
+        let event: Event = serde_json::from_str(&format!(
+            r#"{{"id":"{}","sent_at":"2025-05-17T10:00:00Z","version":1,"type":"Reset","data":{{"session_id":"{session_id}"}}}}"#,
+            Uuid::new_v4()
+        ))
+        .unwrap();
+        match Message::try_from(event).unwrap().message_type {
+            MessageType::Reset(reset_message_data) => assert_eq!(reset_message_data.session_id, session_id),
+            _ => panic!("should be a reset message"),
+        }
+    }
+
+    #[test]
+    fn should_map_tracking_event_measured_at_or_fallback_to_sent_at() {
+        let tracking_event = |data: &str| -> Event {
+            serde_json::from_str(&format!(
+                r#"{{"id":"{}","sent_at":"2025-05-24T10:00:00Z","version":1,"type":"Tracking","data":{data}}}"#,
+                Uuid::new_v4()
+            ))
+            .unwrap()
+        };
+        let session_id = Uuid::new_v4();
+        let measured_at_of = |event: Event| match Message::try_from(event).unwrap().message_type {
+            MessageType::Tracking(tracking_message_data) => tracking_message_data.measured_at,
+            _ => panic!("should be a tracking message"),
+        };
+        let measured_at = measured_at_of(tracking_event(&format!(
+            r#"{{"session_id":"{session_id}","temperature":18.5,"measured_at":"2025-05-24T09:58:00Z"}}"#
+        )));
+        assert_eq!(
+            measured_at,
+            OffsetDateTime::parse("2025-05-24T09:58:00Z", &Rfc3339).unwrap()
+        );
+        let measured_at = measured_at_of(tracking_event(&format!(
+            r#"{{"session_id":"{session_id}","temperature":18.5}}"#
+        )));
+        assert_eq!(
+            measured_at,
+            OffsetDateTime::parse("2025-05-24T10:00:00Z", &Rfc3339).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_map_tracking_event_source() {
+        let session_id = Uuid::new_v4();
+        let event: Event = serde_json::from_str(&format!(
+            r#"{{"id":"{}","sent_at":"2025-07-26T10:00:00Z","version":1,"type":"Tracking","data":{{"session_id":"{session_id}","temperature":18.5,"source":"ispindel-1"}}}}"#,
+            Uuid::new_v4()
+        ))
+        .unwrap();
+        match Message::try_from(event).unwrap().message_type {
+            MessageType::Tracking(tracking_message_data) => {
+                assert_eq!(tracking_message_data.source, Some("ispindel-1".to_string()))
+            }
+            _ => panic!("should be a tracking message"),
+        }
+    }
+
+    #[test]
+    fn should_map_cancel_event_to_message() {
+        let session_id = Uuid::new_v4();
+        let event: Event = serde_json::from_str(&format!(
+            r#"{{"id":"{}","sent_at":"2025-06-07T10:00:00Z","version":1,"type":"Cancel","data":{{"session_id":"{session_id}"}}}}"#,
+            Uuid::new_v4()
+        ))
+        .unwrap();
+        match Message::try_from(event).unwrap().message_type {
+            MessageType::Cancel(cancel_message_data) => assert_eq!(cancel_message_data.session_id, session_id),
+            _ => panic!("should be a cancel message"),
+        }
+    }
+
+    #[test]
+    fn should_map_pause_and_resume_events_to_messages() {
+        let session_id = Uuid::new_v4();
+        let event = |event_type: &str| -> Event {
+            serde_json::from_str(&format!(
+                r#"{{"id":"{}","sent_at":"2025-06-14T10:00:00Z","version":1,"type":"{event_type}","data":{{"session_id":"{session_id}"}}}}"#,
+                Uuid::new_v4()
+            ))
+            .unwrap()
+        };
+        match Message::try_from(event("Pause")).unwrap().message_type {
+            MessageType::Pause(pause_message_data) => assert_eq!(pause_message_data.session_id, session_id),
+            _ => panic!("should be a pause message"),
+        }
+        match Message::try_from(event("Resume")).unwrap().message_type {
+            MessageType::Resume(resume_message_data) => assert_eq!(resume_message_data.session_id, session_id),
+            _ => panic!("should be a resume message"),
+        }
+    }
+
+    #[test]
+    fn should_map_reschedule_event_to_message() {
+        let session_id = Uuid::new_v4();
+        let event: Event = serde_json::from_str(&format!(
+            r#"{{"id":"{}","sent_at":"2025-06-21T10:00:00Z","version":1,"type":"Reschedule","data":{{"session_id":"{session_id}","steps":[{{"position":0,"target_temperature":2.0,"duration":48,"rate":null}}]}}}}"#,
+            Uuid::new_v4()
+        ))
+        .unwrap();
+        match Message::try_from(event).unwrap().message_type {
+            MessageType::Reschedule(reschedule_message_data) => {
+                assert_eq!(reschedule_message_data.session_id, session_id);
+                assert_eq!(reschedule_message_data.steps.len(), 1);
+                assert_eq!(reschedule_message_data.steps[0].duration, Duration::hours(48));
+                assert!(!reschedule_message_data.replace_running);
+            }
+            _ => panic!("should be a reschedule message"),
+        }
+    }
+
+    #[test]
+    fn should_map_skip_event_to_message() {
+        let (session_id, event_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let event: Event = serde_json::from_str(&format!(
+            r#"{{"id":"{event_id}","sent_at":"2025-06-21T10:00:00Z","version":1,"type":"Skip","data":{{"session_id":"{session_id}","to_step":3}}}}"#
+        ))
+        .unwrap();
+        match Message::try_from(event).unwrap().message_type {
+            MessageType::Skip(skip_message_data) => {
+                assert_eq!(skip_message_data.event_id, event_id);
+                assert_eq!(skip_message_data.session_id, session_id);
+                assert_eq!(skip_message_data.to_step, Some(3));
+            }
+            _ => panic!("should be a skip message"),
+        }
+    }
+
+    #[test]
+    fn should_map_override_event_to_message() {
+        let session_id = Uuid::new_v4();
+        let event: Event = serde_json::from_str(&format!(
+            r#"{{"id":"{}","sent_at":"2025-06-28T10:00:00Z","version":1,"type":"Override","data":{{"session_id":"{session_id}","hardware_type":"cooling","state":"on","duration":30}}}}"#,
+            Uuid::new_v4()
+        ))
+        .unwrap();
+        match Message::try_from(event).unwrap().message_type {
+            MessageType::Override(override_message_data) => {
+                assert_eq!(override_message_data.session_id, session_id);
+                assert_eq!(override_message_data.hardware_type, HardwareType::Cooling);
+                assert_eq!(override_message_data.state, HardwareState::On);
+                assert_eq!(override_message_data.duration, Duration::minutes(30));
+            }
+            _ => panic!("should be an override message"),
+        }
+    }
+
+    #[test]
+    fn should_reject_override_event_without_a_positive_duration() {
+        for duration in [0, -30] {
+            let event: Event = serde_json::from_str(&format!(
+                r#"{{"id":"{}","sent_at":"2025-06-28T10:00:00Z","version":1,"type":"Override","data":{{"session_id":"{}","hardware_type":"cooling","state":"on","duration":{duration}}}}}"#,
+                Uuid::new_v4(),
+                Uuid::new_v4()
+            ))
+            .unwrap();
+            assert!(Message::try_from(event).is_err());
+        }
+    }
 }
@@ -45,6 +45,7 @@ impl NatsConsumer {
             ..Default::default()
         }
     }
+    #[allow(dead_code)]
     pub async fn subscribe(
         &self, client: &async_nats::Client,
     ) -> BoxFuture<'static, Result<Subscriber, SubscribeError>> {
@@ -0,0 +1,34 @@
+use internal::port::command::CommandExecutorDriverPort;
+use log::{debug, error};
+use time::{Duration, OffsetDateTime};
+
+use crate::config::reading_config::ReadingConfig;
+
+// Applies the retention policy of the raw readings
+pub struct ReadingPurger {
+    retention: Duration,
+    purge_interval: std::time::Duration,
+}
+
+impl ReadingPurger {
+    pub fn new(config: &ReadingConfig) -> Self {
+        ReadingPurger {
+            retention: Duration::seconds(config.retention),
+            purge_interval: std::time::Duration::from_secs(config.purge_interval),
+        }
+    }
+
+    pub async fn run(&self, executor: &impl CommandExecutorDriverPort) {
+        let mut interval = tokio::time::interval(self.purge_interval);
+        loop {
+            interval.tick().await;
+            match executor
+                .purge_temperature_readings(OffsetDateTime::now_utc() - self.retention)
+                .await
+            {
+                Ok(purged) => debug!("{purged} reading(s) purged"),
+                Err(e) => error!("Unable to purge readings: {e}"),
+            }
+        }
+    }
+}
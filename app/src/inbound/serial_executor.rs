@@ -0,0 +1,93 @@
+use internal::{
+    domain::{
+        error::CommandExecutorServiceError,
+        message::{OverrideMessageData, TrackingMessageData},
+    },
+    port::command::CommandExecutorDriverPort,
+};
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+// Tracking messages, ticks and the watchdog all drive the executor, running them one at a time
+// prevents two of them from completing the same command and firing the next one twice.
+pub struct SerialExecutor<E: CommandExecutorDriverPort> {
+    executor: E,
+    lock: Mutex<()>,
+}
+
+impl<E: CommandExecutorDriverPort> SerialExecutor<E> {
+    pub fn new(executor: E) -> Self {
+        SerialExecutor {
+            executor,
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl<E: CommandExecutorDriverPort> CommandExecutorDriverPort for SerialExecutor<E> {
+    async fn process(&self, tracking_message_data: TrackingMessageData) -> Result<(), CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.process(tracking_message_data).await
+    }
+
+    async fn reevaluate(&self, tracking_message_data: TrackingMessageData) -> Result<(), CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.reevaluate(tracking_message_data).await
+    }
+
+    async fn process_sensor_loss(
+        &self, session_id: Uuid, last_tracked_at: OffsetDateTime,
+    ) -> Result<bool, CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.process_sensor_loss(session_id, last_tracked_at).await
+    }
+
+    async fn fetch_due_sessions(&self) -> Result<Vec<Uuid>, CommandExecutorServiceError> {
+        self.executor.fetch_due_sessions().await
+    }
+
+    async fn fetch_watched_sessions(&self) -> Result<Vec<(Uuid, OffsetDateTime)>, CommandExecutorServiceError> {
+        self.executor.fetch_watched_sessions().await
+    }
+
+    async fn reset_lockout(&self, session_id: Uuid, event_id: Uuid) -> Result<(), CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.reset_lockout(session_id, event_id).await
+    }
+
+    async fn cancel(&self, session_id: Uuid, event_id: Uuid) -> Result<u64, CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.cancel(session_id, event_id).await
+    }
+
+    async fn pause(&self, session_id: Uuid, event_id: Uuid) -> Result<(), CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.pause(session_id, event_id).await
+    }
+
+    async fn resume(&self, session_id: Uuid, event_id: Uuid) -> Result<(), CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.resume(session_id, event_id).await
+    }
+
+    async fn skip(
+        &self, session_id: Uuid, to_step: Option<i32>, last_reading: Option<TrackingMessageData>, event_id: Uuid,
+    ) -> Result<u64, CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.skip(session_id, to_step, last_reading, event_id).await
+    }
+
+    async fn override_hardware(&self, data: OverrideMessageData) -> Result<(), CommandExecutorServiceError> {
+        let _guard = self.lock.lock().await;
+        self.executor.override_hardware(data).await
+    }
+
+    async fn purge_processed_events(&self, before: OffsetDateTime) -> Result<u64, CommandExecutorServiceError> {
+        self.executor.purge_processed_events(before).await
+    }
+
+    async fn purge_temperature_readings(&self, before: OffsetDateTime) -> Result<u64, CommandExecutorServiceError> {
+        self.executor.purge_temperature_readings(before).await
+    }
+}
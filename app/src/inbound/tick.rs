@@ -0,0 +1,102 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use internal::{domain::message::TrackingMessageData, port::command::CommandExecutorDriverPort};
+use log::{debug, error};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::config::tick_config::TickConfig;
+
+// Advances the sessions whose holding duration is over without waiting for their next tracking message, and follows
+// the duty cycle of the sessions driven by the pid, the executor is fed with the last known temperature of the session.
+pub struct Ticker {
+    interval: std::time::Duration,
+    last_readings: Mutex<HashMap<Uuid, (f32, OffsetDateTime)>>,
+}
+
+impl Ticker {
+    pub fn new(config: &TickConfig) -> Self {
+        Ticker {
+            interval: std::time::Duration::from_secs(config.interval),
+            last_readings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, tracking_message_data: &TrackingMessageData) {
+        self.last_readings.lock().unwrap().insert(
+            tracking_message_data.session_id,
+            (tracking_message_data.temperature, tracking_message_data.measured_at),
+        );
+    }
+
+    pub async fn run(&self, executor: &impl CommandExecutorDriverPort) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            let due_sessions = match executor.fetch_due_sessions().await {
+                Ok(due_sessions) => due_sessions,
+                Err(e) => {
+                    error!("Unable to fetch due sessions: {e}");
+                    continue;
+                }
+            };
+            for tracking_message_data in self.last_readings_of(due_sessions) {
+                let session_id = tracking_message_data.session_id;
+                match executor.reevaluate(tracking_message_data).await {
+                    Ok(_) => debug!("Session {session_id} re-evaluated on tick"),
+                    Err(e) => error!("Unable to re-evaluate session {session_id} on tick: {e}"),
+                }
+            }
+        }
+    }
+
+    pub fn last_reading_of(&self, session_id: Uuid) -> Option<TrackingMessageData> {
+        self.last_readings_of(vec![session_id]).pop()
+    }
+
+    // sessions without any reading since startup are left to the next tracking message
+    fn last_readings_of(&self, session_ids: Vec<Uuid>) -> Vec<TrackingMessageData> {
+        let last_readings = self.last_readings.lock().unwrap();
+        session_ids
+            .into_iter()
+            .filter_map(|session_id| {
+                last_readings
+                    .get(&session_id)
+                    .map(|(temperature, measured_at)| TrackingMessageData {
+                        session_id,
+                        temperature: *temperature,
+                        measured_at: *measured_at,
+                        event_id: None,
+                        source: None,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use internal::domain::message::TrackingMessageData;
+    use uuid::Uuid;
+
+    use crate::config::tick_config::TickConfig;
+
+    use super::Ticker;
+
+    #[test]
+    fn should_reuse_last_known_temperature() {
+        let ticker = Ticker::new(&TickConfig::default());
+        let (tracked, untracked) = (Uuid::new_v4(), Uuid::new_v4());
+        for temperature in [18.0, 18.5] {
+            ticker.record(&TrackingMessageData {
+                session_id: tracked,
+                temperature,
+                ..Default::default()
+            });
+        }
+        let result = ticker.last_readings_of(vec![tracked, untracked]);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].session_id, tracked);
+        assert_eq!(result[0].temperature, 18.5);
+    }
+}
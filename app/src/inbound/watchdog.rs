@@ -0,0 +1,138 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use internal::port::command::CommandExecutorDriverPort;
+use log::{debug, error, warn};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::config::watchdog_config::WatchdogConfig;
+
+// Puts the hardware of a session in a safe state once its hydrometer stops sending tracking messages
+pub struct SensorWatchdog {
+    silence: Duration,
+    check_interval: std::time::Duration,
+    last_tracked: Mutex<HashMap<Uuid, OffsetDateTime>>,
+}
+
+impl SensorWatchdog {
+    pub fn new(config: &WatchdogConfig) -> Self {
+        SensorWatchdog {
+            silence: Duration::seconds(config.silence),
+            check_interval: std::time::Duration::from_secs(config.check_interval),
+            last_tracked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn track(&self, session_id: Uuid) {
+        self.last_tracked
+            .lock()
+            .unwrap()
+            .insert(session_id, OffsetDateTime::now_utc());
+    }
+
+    // the sessions tracked before a restart, or by the previous leader, are watched from their last processed reading
+    fn seed(&self, sessions: Vec<(Uuid, OffsetDateTime)>) {
+        let mut last_tracked = self.last_tracked.lock().unwrap();
+        for (session_id, last_reading_at) in sessions {
+            last_tracked.entry(session_id).or_insert(last_reading_at);
+        }
+    }
+
+    pub async fn watch(&self, executor: &impl CommandExecutorDriverPort) {
+        match executor.fetch_watched_sessions().await {
+            Ok(sessions) => self.seed(sessions),
+            Err(e) => error!("Unable to fetch the sessions to watch: {e}"),
+        }
+        let mut interval = tokio::time::interval(self.check_interval);
+        loop {
+            interval.tick().await;
+            for (session_id, last_tracked_at) in self.take_silent_sessions(OffsetDateTime::now_utc()) {
+                match executor.process_sensor_loss(session_id, last_tracked_at).await {
+                    Ok(true) => warn!(
+                        "No tracking message received for session {session_id} since {last_tracked_at}, hardware has been stopped"
+                    ),
+                    Ok(false) => debug!("Session {session_id} is silent but has no running command"),
+                    Err(e) => {
+                        error!("Unable to put the hardware of session {session_id} in a safe state: {e}");
+                        // retried on the next check unless a tracking message arrived in between
+                        self.last_tracked
+                            .lock()
+                            .unwrap()
+                            .entry(session_id)
+                            .or_insert(last_tracked_at);
+                    }
+                }
+            }
+        }
+    }
+
+    // silent sessions are forgotten until their next tracking message, so the safe state is applied once
+    fn take_silent_sessions(&self, now: OffsetDateTime) -> Vec<(Uuid, OffsetDateTime)> {
+        let mut last_tracked = self.last_tracked.lock().unwrap();
+        let silent: Vec<(Uuid, OffsetDateTime)> = last_tracked
+            .iter()
+            .filter(|(_, at)| now - **at >= self.silence)
+            .map(|(session_id, at)| (*session_id, *at))
+            .collect();
+        silent.iter().for_each(|(session_id, _)| {
+            last_tracked.remove(session_id);
+        });
+        silent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Duration, OffsetDateTime};
+    use uuid::Uuid;
+
+    use crate::config::watchdog_config::WatchdogConfig;
+
+    use super::SensorWatchdog;
+
+    #[test]
+    fn should_take_silent_sessions_once() {
+        let watchdog = SensorWatchdog::new(&WatchdogConfig {
+            silence: 600,
+            check_interval: 60,
+        });
+        let (silent, alive) = (Uuid::new_v4(), Uuid::new_v4());
+        watchdog.track(silent);
+        watchdog.track(alive);
+        let now = OffsetDateTime::now_utc();
+        watchdog
+            .last_tracked
+            .lock()
+            .unwrap()
+            .insert(silent, now - Duration::minutes(11));
+
+        let result = watchdog.take_silent_sessions(now);
+        assert_eq!(result, vec![(silent, now - Duration::minutes(11))]);
+        assert!(watchdog.take_silent_sessions(now).is_empty());
+
+        // readings are back, the session is watched again
+        watchdog.track(silent);
+        assert!(watchdog.take_silent_sessions(now).is_empty());
+        assert_eq!(watchdog.take_silent_sessions(now + Duration::minutes(11)).len(), 2);
+    }
+
+    #[test]
+    fn should_watch_seeded_sessions_from_their_last_reading() {
+        let watchdog = SensorWatchdog::new(&WatchdogConfig {
+            silence: 600,
+            check_interval: 60,
+        });
+        let (silent, tracked) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = OffsetDateTime::now_utc();
+        watchdog.track(tracked);
+        // a tracking message received meanwhile is fresher than the persisted reading
+        watchdog.seed(vec![
+            (silent, now - Duration::minutes(11)),
+            (tracked, now - Duration::minutes(11)),
+        ]);
+        assert_eq!(
+            watchdog.take_silent_sessions(now),
+            vec![(silent, now - Duration::minutes(11))]
+        );
+    }
+}
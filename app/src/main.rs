@@ -5,33 +5,61 @@ mod outbound;
 mod utils;
 //TODO move the mod into lib.rs so they can be used for IT tests.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use anyhow::Result;
-use async_nats::jetstream;
+use anyhow::{Result, bail};
+use async_nats::jetstream::{self, AckKind};
 use config::app_config::AppConfig;
 use futures::TryStreamExt;
+use inbound::inbox::InboxPurger;
+use inbound::leader::LeaderElection;
 use inbound::model::event::Event;
 use inbound::nats::NatsConsumer;
+use inbound::reading::ReadingPurger;
+use inbound::serial_executor::SerialExecutor;
+use inbound::tick::Ticker;
+use inbound::watchdog::SensorWatchdog;
 use internal::{
-    domain::message::{Message, MessageType},
+    domain::{
+        error::{CommandExecutorServiceError, CommandSchedulerServiceError},
+        message::{Message, MessageType},
+    },
     port::command::CommandExecutorDriverPort,
     port::command::CommandSchedulerDriverPort,
     service::{command_executor_service::CommandExecutorService, command_scheduler_service::CommandSchedulerService},
 };
 use log::{debug, error, warn};
 use nats_client::NatsClient;
-use outbound::{nats_publisher::NatsPublisher, postgres::CommandRepository};
+use outbound::{
+    migration::SchemaMigrator, nats_publisher::NatsPublisher, postgres::CommandRepository, system_clock::SystemClock,
+};
 use sqlx::postgres::PgPoolOptions;
 use tokio::sync::OnceCell;
 use utils::pem::PemUtils;
 static CMD_REPOSITORY: OnceCell<Arc<CommandRepository>> = OnceCell::const_new();
+// leaves the database some time to come back before a failed event is processed again
+const REDELIVERY_DELAY: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     env_logger::init();
     PemUtils::init_provider();
     let conf = AppConfig::load("config.toml").unwrap();
+    let pool = PgPoolOptions::new().connect_with(conf.postgres.options()).await?;
+    let migrator = SchemaMigrator::new(pool.clone());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => {}
+        ["migrate", command] => return migrate(&migrator, command).await,
+        _ => bail!("Usage: rtgb-controller [migrate up|down|status]"),
+    }
+    // both refuse a schema migrated by a newer binary
+    if conf.migration.on_boot {
+        migrator.up().await?;
+    } else {
+        migrator.ensure_up_to_date().await?;
+    }
+
     let nats = NatsClient {
         client_config: conf.nats.client,
     };
@@ -41,63 +69,176 @@ async fn main() -> Result<(), anyhow::Error> {
     let consumer = consumer.create_consumer(&context).await?;
 
     let cmd_repository = CMD_REPOSITORY
-        .get_or_init(async || {
-            let pool = PgPoolOptions::new()
-                .connect_with(conf.postgres.options())
-                .await
-                .unwrap();
-            Arc::new(CommandRepository::new(pool))
-        })
+        .get_or_init(async || Arc::new(CommandRepository::new(pool)))
         .await;
-    let nats_publisher = NatsPublisher::new(client, conf.nats.publisher);
-    let scheduler_service = CommandSchedulerService::new(cmd_repository.clone());
-    let executor_service = CommandExecutorService::new(cmd_repository.clone(), nats_publisher);
+    let nats_publisher = NatsPublisher::new(client.clone(), conf.nats.publisher.clone());
+    let session_event_publisher = NatsPublisher::new(client, conf.nats.publisher);
+    let scheduler_service =
+        CommandSchedulerService::new(cmd_repository.clone(), SystemClock, (&conf.controller).into());
+    let executor_service = SerialExecutor::new(CommandExecutorService::new(
+        cmd_repository.clone(),
+        nats_publisher,
+        session_event_publisher,
+        SystemClock,
+        (&conf.controller).into(),
+    ));
 
-    loop {
-        let messages = consumer.messages().await;
-        match messages {
-            Err(e) => {
-                error!("Unable to consume stream {e}");
-                continue;
-            }
-            Ok(mut stream) => {
-                while let Ok(maybe_msg) = stream.try_next().await {
-                    match maybe_msg {
-                        None => warn!("No message to process"),
-                        Some(nats_msg) => {
-                            let msg = Event::try_from(&nats_msg)
-                                .and_then(Message::try_from)
-                                .inspect_err(|e| error!("{e}"));
-                            if let Ok(msg) = msg {
-                                let processing_result = match msg.message_type {
-                                    MessageType::Schedule(schedule_message_data) => scheduler_service
-                                        .schedule(schedule_message_data)
-                                        .await
-                                        .inspect(|it| debug!("Command Processed, {:?} commmand(s) created", it))
-                                        .inspect_err(|e| error!("{e}"))
-                                        .map_err(|e| anyhow::anyhow!(e))
-                                        .map(|_| ()),
-                                    MessageType::Tracking(tracking_message_data) => executor_service
-                                        .process(tracking_message_data)
-                                        .await
-                                        .inspect(|_| debug!("Message Processed, commmand(s) executed/updated"))
-                                        .inspect_err(|e| error!("{e}"))
-                                        .map_err(|e| anyhow::anyhow!(e))
-                                        .map(|_| ()),
-                                };
-                                if let Err(e) = processing_result {
-                                    error!("Unable to process incoming events: {e}")
+    let watchdog = SensorWatchdog::new(&conf.watchdog);
+    let ticker = Ticker::new(&conf.tick);
+    let inbox_purger = InboxPurger::new(&conf.inbox);
+    let reading_purger = ReadingPurger::new(&conf.readings);
+    let leader_election = LeaderElection::new(&conf.ha, conf.postgres.options());
+
+    let consume = async || {
+        loop {
+            let messages = consumer.messages().await;
+            match messages {
+                Err(e) => {
+                    error!("Unable to consume stream {e}");
+                    continue;
+                }
+                Ok(mut stream) => {
+                    while let Ok(maybe_msg) = stream.try_next().await {
+                        match maybe_msg {
+                            None => warn!("No message to process"),
+                            Some(nats_msg) => {
+                                let msg = Event::try_from(&nats_msg)
+                                    .and_then(Message::try_from)
+                                    .inspect_err(|e| error!("{e}"));
+                                // an unreadable event or a rejected one would fail the same way on its redelivery
+                                let mut ack_kind = AckKind::Ack;
+                                if let Ok(msg) = msg {
+                                    let processing_result = match msg.message_type {
+                                        MessageType::Schedule(schedule_message_data) => scheduler_service
+                                            .schedule(schedule_message_data)
+                                            .await
+                                            .inspect(|it| debug!("Command Processed, {:?} commmand(s) created", it))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e))
+                                            .map(|_| ()),
+                                        MessageType::Reschedule(reschedule_message_data) => scheduler_service
+                                            .reschedule(reschedule_message_data)
+                                            .await
+                                            .inspect(|it| debug!("Session rescheduled, {it:?} commmand(s) created"))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e))
+                                            .map(|_| ()),
+                                        MessageType::Tracking(tracking_message_data) => {
+                                            watchdog.track(tracking_message_data.session_id);
+                                            ticker.record(&tracking_message_data);
+                                            executor_service
+                                                .process(tracking_message_data)
+                                                .await
+                                                .inspect(|_| debug!("Message Processed, commmand(s) executed/updated"))
+                                                .inspect_err(|e| error!("{e}"))
+                                                .map_err(|e| anyhow::anyhow!(e))
+                                                .map(|_| ())
+                                        }
+                                        MessageType::Reset(reset_message_data) => executor_service
+                                            .reset_lockout(reset_message_data.session_id, reset_message_data.event_id)
+                                            .await
+                                            .inspect(|_| debug!("Lockout reset"))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e)),
+                                        MessageType::Cancel(cancel_message_data) => executor_service
+                                            .cancel(cancel_message_data.session_id, cancel_message_data.event_id)
+                                            .await
+                                            .inspect(|it| debug!("Session cancelled, {it:?} command(s) cancelled"))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e))
+                                            .map(|_| ()),
+                                        MessageType::Pause(pause_message_data) => executor_service
+                                            .pause(pause_message_data.session_id, pause_message_data.event_id)
+                                            .await
+                                            .inspect(|_| debug!("Session paused"))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e)),
+                                        MessageType::Resume(resume_message_data) => executor_service
+                                            .resume(resume_message_data.session_id, resume_message_data.event_id)
+                                            .await
+                                            .inspect(|_| debug!("Session resumed"))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e)),
+                                        MessageType::Skip(skip_message_data) => executor_service
+                                            .skip(
+                                                skip_message_data.session_id,
+                                                skip_message_data.to_step,
+                                                ticker.last_reading_of(skip_message_data.session_id),
+                                                skip_message_data.event_id,
+                                            )
+                                            .await
+                                            .inspect(|it| debug!("Profile advanced, {it:?} command(s) skipped"))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e))
+                                            .map(|_| ()),
+                                        MessageType::Override(override_message_data) => executor_service
+                                            .override_hardware(override_message_data)
+                                            .await
+                                            .inspect(|_| debug!("Hardware overridden"))
+                                            .inspect_err(|e| error!("{e}"))
+                                            .map_err(|e| anyhow::anyhow!(e)),
+                                    };
+                                    if let Err(e) = processing_result {
+                                        error!("Unable to process incoming events: {e}");
+                                        if is_technical(&e) {
+                                            ack_kind = AckKind::Nak(Some(REDELIVERY_DELAY));
+                                        }
+                                    };
+                                }
+
+                                match nats_msg.ack_with(ack_kind).await {
+                                    Ok(_) => debug!("Nats message acknowledged with {ack_kind:?}"),
+                                    Err(e) => error!("Unable to ack message: {e}"),
                                 };
                             }
-
-                            match nats_msg.ack().await {
-                                Ok(_) => debug!("Nats message acknowledged"),
-                                Err(e) => error!("Unable to ack message: {e}"),
-                            };
                         }
                     }
                 }
             }
         }
+    };
+    // the standby instance neither consumes the events nor looks after the sessions
+    leader_election
+        .lead(async || {
+            tokio::select! {
+                _ = watchdog.watch(&executor_service) => {}
+                _ = ticker.run(&executor_service) => {}
+                _ = inbox_purger.run(&executor_service) => {}
+                _ = reading_purger.run(&executor_service) => {}
+                _ = consume() => {}
+            }
+        })
+        .await;
+    Ok(())
+}
+
+// the event failed on the way, e.g. the database was unreachable, its redelivery may succeed
+fn is_technical(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<CommandExecutorServiceError>(),
+        Some(CommandExecutorServiceError::TechnicalError(_))
+    ) || matches!(
+        e.downcast_ref::<CommandSchedulerServiceError>(),
+        Some(CommandSchedulerServiceError::TechnicalError(_))
+    )
+}
+
+async fn migrate(migrator: &SchemaMigrator, command: &str) -> Result<()> {
+    match command {
+        "up" => migrator.up().await,
+        "down" => {
+            match migrator.down().await? {
+                Some(version) => println!("Migration {version} reverted"),
+                None => println!("No migration to revert"),
+            }
+            Ok(())
+        }
+        "status" => {
+            for status in migrator.status().await? {
+                println!("{} {:?} {}", status.version, status.state, status.description);
+            }
+            Ok(())
+        }
+        other => bail!("Unknown migrate command {other}, expected up, down or status"),
     }
 }
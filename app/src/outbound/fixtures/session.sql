@@ -2,11 +2,15 @@ INSERT INTO session (
     uuid,
     cooling_id,
     heating_id,
-    active_hardware_type
+    active_hardware_type,
+    hysteresis,
+    control_mode
 )
 VALUES (
     '871b888e-2185-4bb8-b8b0-f87d4be4c133',
     'cooling_id',
     'heating_id',
-    'Cooling'
+    'Cooling',
+    0.5,
+    'Pid'
 );
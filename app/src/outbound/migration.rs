@@ -0,0 +1,180 @@
+use anyhow::bail;
+use sqlx::{
+    PgPool,
+    migrate::{Migrate, Migrator},
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, PartialEq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    // applied by a newer binary
+    Unknown,
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+// Applies the migrations of ./migrations, embedded in the binary
+pub struct SchemaMigrator {
+    pool: PgPool,
+}
+
+impl SchemaMigrator {
+    pub fn new(pool: PgPool) -> Self {
+        SchemaMigrator { pool }
+    }
+
+    async fn applied_versions(&self) -> anyhow::Result<Vec<i64>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        let mut versions: Vec<i64> = conn
+            .list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
+        versions.sort();
+        Ok(versions)
+    }
+
+    fn is_known(version: i64) -> bool {
+        MIGRATOR.iter().any(|migration| migration.version == version)
+    }
+
+    // a schema migrated by a newer binary may not be understood by this one
+    pub async fn ensure_known_schema(&self) -> anyhow::Result<()> {
+        let unknown: Vec<i64> = self
+            .applied_versions()
+            .await?
+            .into_iter()
+            .filter(|version| !Self::is_known(*version))
+            .collect();
+        if !unknown.is_empty() {
+            bail!("The schema is newer than this binary, unknown migration(s) {unknown:?} have been applied");
+        }
+        Ok(())
+    }
+
+    // the schema must be the one of this binary when it isn't migrated on boot
+    pub async fn ensure_up_to_date(&self) -> anyhow::Result<()> {
+        self.ensure_known_schema().await?;
+        let pending: Vec<i64> = self
+            .status()
+            .await?
+            .into_iter()
+            .filter(|status| status.state == MigrationState::Pending)
+            .map(|status| status.version)
+            .collect();
+        if !pending.is_empty() {
+            bail!("The schema is older than this binary, migration(s) {pending:?} are pending");
+        }
+        Ok(())
+    }
+
+    pub async fn up(&self) -> anyhow::Result<()> {
+        self.ensure_known_schema().await?;
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    // reverts the last applied migration, returns its version if any
+    pub async fn down(&self) -> anyhow::Result<Option<i64>> {
+        self.ensure_known_schema().await?;
+        let mut applied = self.applied_versions().await?;
+        let Some(last) = applied.pop() else {
+            return Ok(None);
+        };
+        MIGRATOR.undo(&self.pool, applied.last().copied().unwrap_or(0)).await?;
+        Ok(Some(last))
+    }
+
+    pub async fn status(&self) -> anyhow::Result<Vec<MigrationStatus>> {
+        let applied = self.applied_versions().await?;
+        let mut statuses: Vec<MigrationStatus> = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state: if applied.contains(&migration.version) {
+                    MigrationState::Applied
+                } else {
+                    MigrationState::Pending
+                },
+            })
+            .collect();
+        statuses.extend(
+            applied
+                .into_iter()
+                .filter(|version| !Self::is_known(*version))
+                .map(|version| MigrationStatus {
+                    version,
+                    description: String::new(),
+                    state: MigrationState::Unknown,
+                }),
+        );
+        Ok(statuses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{PgPool, query};
+
+    use super::{MigrationState, SchemaMigrator};
+
+    #[sqlx::test(migrations = false)]
+    async fn should_apply_and_revert_the_embedded_migrations(pool: PgPool) -> anyhow::Result<()> {
+        let migrator = SchemaMigrator::new(pool);
+        assert!(
+            migrator
+                .status()
+                .await?
+                .iter()
+                .all(|status| status.state == MigrationState::Pending)
+        );
+        assert!(migrator.ensure_up_to_date().await.is_err());
+        migrator.up().await?;
+        let statuses = migrator.status().await?;
+        assert!(statuses.iter().all(|status| status.state == MigrationState::Applied));
+        migrator.ensure_up_to_date().await?;
+
+        let last = statuses.last().map(|status| status.version);
+        assert_eq!(migrator.down().await?, last);
+        let statuses = migrator.status().await?;
+        assert_eq!(statuses.last().unwrap().state, MigrationState::Pending);
+        assert!(
+            statuses[..statuses.len() - 1]
+                .iter()
+                .all(|status| status.state == MigrationState::Applied)
+        );
+        migrator.up().await?;
+        assert_eq!(migrator.status().await?.last().unwrap().state, MigrationState::Applied);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn should_refuse_a_schema_newer_than_the_binary(pool: PgPool) -> anyhow::Result<()> {
+        let migrator = SchemaMigrator::new(pool.clone());
+        migrator.up().await?;
+        query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES ($1, 'from a newer binary', TRUE, '\\x00', 0)",
+        )
+        .bind(99991231235959_i64)
+        .execute(&pool)
+        .await?;
+        assert!(migrator.ensure_known_schema().await.is_err());
+        assert!(migrator.ensure_up_to_date().await.is_err());
+        assert!(migrator.up().await.is_err());
+        assert!(migrator.down().await.is_err());
+        assert_eq!(migrator.status().await?.last().unwrap().state, MigrationState::Unknown);
+        Ok(())
+    }
+}
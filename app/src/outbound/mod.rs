@@ -1,2 +1,5 @@
+pub mod migration;
+pub mod model;
 pub mod nats_publisher;
 pub mod postgres;
+pub mod system_clock;
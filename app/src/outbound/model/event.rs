@@ -0,0 +1,166 @@
+use internal::domain::{
+    alert::{Alert, AlertKind},
+    session_event::{SessionEvent, SessionEventKind},
+};
+use serde::Serialize;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+// Same envelope as the inbound events
+#[derive(Serialize, Debug)]
+pub struct OutgoingEvent {
+    pub id: Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    pub sent_at: OffsetDateTime,
+    pub version: u32,
+    #[serde(flatten)]
+    pub data: OutgoingEventData,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", content = "data")]
+pub enum OutgoingEventData {
+    SensorLost {
+        session_id: Uuid,
+        #[serde(with = "time::serde::rfc3339")]
+        raised_at: OffsetDateTime,
+        #[serde(with = "time::serde::rfc3339")]
+        last_tracked_at: OffsetDateTime,
+    },
+    TemperatureLimitCrossed {
+        session_id: Uuid,
+        #[serde(with = "time::serde::rfc3339")]
+        raised_at: OffsetDateTime,
+        temperature: f32,
+        limit: f32,
+        hardware_type: &'static str,
+    },
+    StepStarted(SessionEventData),
+    StepTargetReached(SessionEventData),
+    StepCompleted(SessionEventData),
+    SessionCompleted(SessionEventData),
+}
+
+#[derive(Serialize, Debug)]
+pub struct SessionEventData {
+    pub session_id: Uuid,
+    pub step_position: Option<i32>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub occurred_at: OffsetDateTime,
+    pub temperature: f32,
+}
+
+impl From<&Alert> for OutgoingEvent {
+    fn from(alert: &Alert) -> Self {
+        OutgoingEvent {
+            id: Uuid::new_v4(),
+            sent_at: OffsetDateTime::now_utc(),
+            version: 1,
+            data: match &alert.kind {
+                AlertKind::SensorLost { last_tracked_at } => OutgoingEventData::SensorLost {
+                    session_id: alert.session_id,
+                    raised_at: alert.raised_at,
+                    last_tracked_at: *last_tracked_at,
+                },
+                AlertKind::TemperatureLimitCrossed {
+                    temperature,
+                    limit,
+                    hardware_type,
+                } => OutgoingEventData::TemperatureLimitCrossed {
+                    session_id: alert.session_id,
+                    raised_at: alert.raised_at,
+                    temperature: *temperature,
+                    limit: *limit,
+                    hardware_type: hardware_type.name(),
+                },
+            },
+        }
+    }
+}
+
+impl From<&SessionEvent> for OutgoingEvent {
+    fn from(event: &SessionEvent) -> Self {
+        let data = SessionEventData {
+            session_id: event.session_id,
+            step_position: event.step_position,
+            occurred_at: event.occurred_at,
+            temperature: event.temperature,
+        };
+        OutgoingEvent {
+            id: Uuid::new_v4(),
+            sent_at: OffsetDateTime::now_utc(),
+            version: 1,
+            data: match event.kind {
+                SessionEventKind::StepStarted => OutgoingEventData::StepStarted(data),
+                SessionEventKind::StepTargetReached => OutgoingEventData::StepTargetReached(data),
+                SessionEventKind::StepCompleted => OutgoingEventData::StepCompleted(data),
+                SessionEventKind::SessionCompleted => OutgoingEventData::SessionCompleted(data),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use internal::domain::{
+        alert::{Alert, AlertKind},
+        message::HardwareType,
+        session_event::{SessionEvent, SessionEventKind},
+    };
+    use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+    use uuid::Uuid;
+
+    use super::OutgoingEvent;
+
+    #[test]
+    fn should_serialize_sensor_lost_alert() {
+        let last_tracked_at = OffsetDateTime::parse("2025-05-01T10:00:00Z", &Rfc3339).unwrap();
+        let alert = Alert {
+            session_id: Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap(),
+            raised_at: OffsetDateTime::parse("2025-05-01T10:15:00Z", &Rfc3339).unwrap(),
+            kind: AlertKind::SensorLost { last_tracked_at },
+        };
+        let json = serde_json::to_value(OutgoingEvent::from(&alert)).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["type"], "SensorLost");
+        assert_eq!(json["data"]["session_id"], "871b888e-2185-4bb8-b8b0-f87d4be4c133");
+        assert_eq!(json["data"]["raised_at"], "2025-05-01T10:15:00Z");
+        assert_eq!(json["data"]["last_tracked_at"], "2025-05-01T10:00:00Z");
+    }
+
+    #[test]
+    fn should_serialize_temperature_limit_crossed_alert() {
+        let alert = Alert {
+            session_id: Uuid::new_v4(),
+            raised_at: OffsetDateTime::now_utc(),
+            kind: AlertKind::TemperatureLimitCrossed {
+                temperature: 36.5,
+                limit: 35.0,
+                hardware_type: HardwareType::Heating,
+            },
+        };
+        let json = serde_json::to_value(OutgoingEvent::from(&alert)).unwrap();
+        assert_eq!(json["type"], "TemperatureLimitCrossed");
+        assert_eq!(json["data"]["temperature"], 36.5);
+        assert_eq!(json["data"]["limit"], 35.0);
+        assert_eq!(json["data"]["hardware_type"], "Heating");
+    }
+
+    #[test]
+    fn should_serialize_step_started_event() {
+        let event = SessionEvent {
+            session_id: Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap(),
+            step_position: Some(2),
+            occurred_at: OffsetDateTime::parse("2025-05-01T10:15:00Z", &Rfc3339).unwrap(),
+            temperature: 18.5,
+            kind: SessionEventKind::StepStarted,
+        };
+        let json = serde_json::to_value(OutgoingEvent::from(&event)).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["type"], "StepStarted");
+        assert_eq!(json["data"]["session_id"], "871b888e-2185-4bb8-b8b0-f87d4be4c133");
+        assert_eq!(json["data"]["step_position"], 2);
+        assert_eq!(json["data"]["occurred_at"], "2025-05-01T10:15:00Z");
+        assert_eq!(json["data"]["temperature"], 18.5);
+    }
+}
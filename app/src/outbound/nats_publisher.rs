@@ -1,8 +1,16 @@
 use async_nats::Client;
-use internal::port::publisher::{HardwareAction, PublisherDrivenPort};
+use internal::{
+    domain::{alert::Alert, session_event::SessionEvent},
+    port::{
+        publisher::{HardwareAction, PublisherDrivenPort},
+        session_event::SessionEventDrivenPort,
+    },
+};
 
 use crate::config::nats_config::PublisherConfig;
 
+use super::model::event::OutgoingEvent;
+
 pub struct NatsPublisher {
     client: Client,
     publisher_config: PublisherConfig,
@@ -34,6 +42,24 @@ impl PublisherDrivenPort for NatsPublisher {
             .await
             .map_err(|e| anyhow::anyhow!(e))
     }
+
+    async fn alert(&self, alert: Alert) -> anyhow::Result<()> {
+        let payload = serde_json::to_vec(&OutgoingEvent::from(&alert))?;
+        self.client
+            .publish(self.publisher_config.alert_subject.clone(), payload.into())
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+impl SessionEventDrivenPort for NatsPublisher {
+    async fn publish(&self, event: SessionEvent) -> anyhow::Result<()> {
+        let payload = serde_json::to_vec(&OutgoingEvent::from(&event))?;
+        self.client
+            .publish(self.publisher_config.session_event_subject.clone(), payload.into())
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+    }
 }
 
 impl NatsPublisher {
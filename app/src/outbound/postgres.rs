@@ -5,16 +5,22 @@ use bigdecimal::ToPrimitive;
 use futures::FutureExt;
 use log::debug;
 use sqlx::Row;
-use sqlx::{PgPool, query, query_as, query_scalar, types::BigDecimal};
+use sqlx::{PgConnection, PgPool, query, query_as, query_scalar, types::BigDecimal};
 use time::{Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
 use uuid::Uuid;
 
 use internal::{
     domain::{
         command::{Command, CommandStatus, CommandTemperatureData, NewCommand},
+        command_event::{CommandEvent, CommandEventKind},
         error::CommandSchedulerServiceError,
+        filter::ReadingWindow,
         message::{Hardware, HardwareType},
+        pid::PidState,
+        protection::{HardwareState, HardwareSwitch, HardwareSwitchHistory},
+        session::{ControlMode, HardwareLockout, HardwareOverride, SessionSettings, SessionStatus, TemperatureLimits},
         sorting::QueryOptions,
+        temperature_reading::TemperatureReading,
     },
     port::command::CommandDrivenPort,
 };
@@ -23,6 +29,10 @@ pub struct CommandRepository {
     pub pool: PgPool,
     command_table: &'static str,
     session_table: &'static str,
+    hardware_switch_table: &'static str,
+    processed_event_table: &'static str,
+    command_event_table: &'static str,
+    temperature_reading_table: &'static str,
 }
 
 impl CommandRepository {
@@ -31,58 +41,368 @@ impl CommandRepository {
             pool,
             command_table: "command",
             session_table: "session",
+            hardware_switch_table: "hardware_switch",
+            processed_event_table: "processed_event",
+            command_event_table: "command_event",
+            temperature_reading_table: "temperature_reading",
         }
     }
+
+    async fn insert_command_event(
+        &self, conn: &mut PgConnection, record: &CommandRecord, kind: CommandEventKind, occurred_at: OffsetDateTime,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            "INSERT INTO {:?} (session_id, command_uuid, kind, status, occurred_at) VALUES ($1,$2,$3,$4,$5)",
+            self.command_event_table
+        );
+        let status = match &kind {
+            CommandEventKind::StatusChanged(status) => Some(status.name()),
+            _ => None,
+        };
+        query(&sql_query)
+            .bind(record.session_id)
+            .bind(record.uuid)
+            .bind(kind.name())
+            .bind(status)
+            .bind(PrimitiveDateTime::new(occurred_at.date(), occurred_at.time()))
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    // returns false when the event has already been processed, the caller is expected to roll back then
+    async fn record_processed_event(&self, conn: &mut PgConnection, event_id: Uuid) -> anyhow::Result<bool> {
+        let sql_query = format!(
+            "INSERT INTO {:?} (event_id, processed_at) VALUES ($1,$2) ON CONFLICT (event_id) DO NOTHING",
+            self.processed_event_table
+        );
+        let now = OffsetDateTime::now_utc();
+        let result = query(&sql_query)
+            .bind(event_id)
+            .bind(PrimitiveDateTime::new(now.date(), now.time()))
+            .execute(conn)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    // a single statement whatever the number of commands, a ramp can be made of hundreds of them
+    async fn insert_commands(
+        &self, conn: &mut PgConnection, records: &[NewCommandRecord], first_step: i32, first_order: i32,
+    ) -> anyhow::Result<u64> {
+        let sql_query = format!(
+            r#"
+            WITH inserted AS (
+                INSERT INTO {command_table} (uuid, fermentation_step_id, status, status_date, value, value_holding_duration, session_id, execution_order)
+                SELECT * FROM UNNEST($1::UUID[], $2::INTEGER[], $3::VARCHAR[], $4::TIMESTAMP[], $5::NUMERIC[], $6::INTEGER[], $7::INTEGER[], $8::INTEGER[])
+                RETURNING {command_table}.session_id, {command_table}.uuid, {command_table}.status, {command_table}.created_at
+            )
+            INSERT INTO {command_event_table} (session_id, command_uuid, kind, status, occurred_at)
+            SELECT inserted.session_id, inserted.uuid, $9, inserted.status, inserted.created_at FROM inserted
+            "#,
+            command_table = self.command_table,
+            command_event_table = self.command_event_table,
+        );
+        let result = query(&sql_query)
+            .bind(records.iter().map(|rec| rec.command_id).collect::<Vec<_>>())
+            .bind(
+                records
+                    .iter()
+                    .map(|rec| first_step + rec.fermentation_step_id)
+                    .collect::<Vec<_>>(),
+            )
+            .bind(records.iter().map(|rec| rec.status.clone()).collect::<Vec<_>>())
+            .bind(records.iter().map(|rec| rec.status_date).collect::<Vec<_>>())
+            .bind(records.iter().map(|rec| rec.value.clone()).collect::<Vec<_>>())
+            .bind(records.iter().map(|rec| rec.value_holding_duration).collect::<Vec<_>>())
+            .bind(records.iter().map(|rec| rec.session_id).collect::<Vec<_>>())
+            .bind(
+                (0..records.len() as i32)
+                    .map(|order| first_order + order)
+                    .collect::<Vec<_>>(),
+            )
+            .bind(CommandEventKind::StatusChanged(CommandStatus::Planned).name())
+            .execute(conn)
+            .await
+            .map_err(|e| anyhow::anyhow!("Can't execute command insert {}", e))?;
+        Ok(result.rows_affected())
+    }
+
+    // returns None when the command already left the statuses it may leave for the new one
+    async fn update_command_status(
+        &self, conn: &mut PgConnection, command_uuid: Uuid, status: &CommandStatus,
+    ) -> anyhow::Result<Option<CommandRecord>> {
+        // the statuses the command may leave for the new one
+        let (date, from_statuses): (_, &[&str]) = match status {
+            CommandStatus::Planned => bail!("Command can't be updated to Planned"),
+            CommandStatus::Running { since } => (since, &["Planned"]),
+            CommandStatus::Executed { at } => (at, &["Running"]),
+            CommandStatus::Cancelled { at } | CommandStatus::Skipped { at } => (at, &["Planned", "Running"]),
+        };
+        let date = PrimitiveDateTime::new(date.date(), date.time());
+        let sql_query = format!(
+            "SELECT {command_table}.status FROM {command_table} WHERE {command_table}.uuid = $1 FOR UPDATE",
+            command_table = self.command_table,
+        );
+        let current_status: String = query_scalar(&sql_query)
+            .bind(command_uuid)
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or(anyhow::anyhow!("No command {command_uuid} to update"))?;
+        // another instance changed the command first, the row lock has been held until its commit
+        if !from_statuses.contains(&current_status.as_str()) {
+            debug!(
+                "Command {command_uuid} is {current_status}, it can't become {}",
+                status.name()
+            );
+            return Ok(None);
+        }
+        let sql_query = format!(
+            r#"UPDATE {command_table}
+        SET
+            status = $1,
+            status_date = $2
+        WHERE {command_table}.uuid = $3
+        AND {command_table}.status = ANY($4)
+        RETURNING {command_table}.*"#,
+            command_table = self.command_table,
+        );
+        let updated_command_record: CommandRecord = query_as(&sql_query)
+            .bind(status.name())
+            .bind(date)
+            .bind(command_uuid)
+            .bind(from_statuses)
+            .fetch_one(&mut *conn)
+            .await?;
+        self.insert_command_event(
+            conn,
+            &updated_command_record,
+            CommandEventKind::StatusChanged(status.clone()),
+            date.assume_offset(UtcOffset::UTC),
+        )
+        .await?;
+        Ok(Some(updated_command_record))
+    }
+
+    async fn update_reading_window(
+        &self, conn: &mut PgConnection, session_uuid: Uuid, window: ReadingWindow,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET
+                reading_window = $1,
+                filtered_temperature = $2,
+                outlier_count = $3
+            WHERE {session_table}.uuid = $4
+            "#,
+            session_table = self.session_table,
+        );
+        query(&sql_query)
+            .bind(window.readings)
+            .bind(window.filtered)
+            .bind(window.outliers as i32)
+            .bind(session_uuid)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_temperature_reading(
+        &self, conn: &mut PgConnection, reading: TemperatureReading,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r#"
+            INSERT INTO {temperature_reading_table} (session_id, measured_at, temperature, source)
+            SELECT {session_table}.id, $2, $3, $4 FROM {session_table} WHERE {session_table}.uuid = $1
+            "#,
+            temperature_reading_table = self.temperature_reading_table,
+            session_table = self.session_table,
+        );
+        query(&sql_query)
+            .bind(reading.session_id)
+            .bind(PrimitiveDateTime::new(
+                reading.measured_at.date(),
+                reading.measured_at.time(),
+            ))
+            .bind(reading.temperature)
+            .bind(reading.source)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
 }
 
 impl CommandDrivenPort for CommandRepository {
-    async fn insert(&self, commands: Vec<NewCommand>, heating_h: Hardware, cooling_h: Hardware) -> anyhow::Result<u64> {
+    async fn insert(
+        &self, commands: Vec<NewCommand>, heating_h: Hardware, cooling_h: Hardware, settings: SessionSettings,
+        event_id: Uuid,
+    ) -> anyhow::Result<u64> {
         let c = commands.first().ok_or(anyhow::anyhow!("No command to insert"))?;
+        let mut tx = self.pool.begin().await?;
+        if !self.record_processed_event(&mut tx, event_id).await? {
+            return Ok(0);
+        }
         let sql_query = format!(
-            "INSERT INTO {:?} (uuid, cooling_id, heating_id) VALUES ($1,$2,$3) RETURNING id",
+            "INSERT INTO {:?} (uuid, cooling_id, heating_id, hysteresis, control_mode, min_temperature, max_temperature) VALUES ($1,$2,$3,$4,$5,$6,$7) RETURNING id",
             self.session_table
         );
         let session_record_id = query_scalar(sql_query.as_str())
             .bind(c.session_data.id)
-            .bind(heating_h.id)
             .bind(cooling_h.id)
-            .fetch_one(&self.pool)
+            .bind(heating_h.id)
+            .bind(BigDecimal::from_str(&format!("{:.1}", settings.hysteresis))?.with_scale(1))
+            .bind(settings.control_mode.name())
+            .bind(to_temperature_record(settings.temperature_limits.min)?)
+            .bind(to_temperature_record(settings.temperature_limits.max)?)
+            .fetch_one(&mut *tx)
             .await?;
         debug!("Inserted session with id {session_record_id}");
         let records = commands
             .iter()
             .map(|c| NewCommandRecord::from_command(c, session_record_id))
             .collect::<anyhow::Result<Vec<NewCommandRecord>>>()?;
+        let inserted = self.insert_commands(&mut tx, &records, 0, 0).await?;
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn purge_processed_events(&self, before: OffsetDateTime) -> anyhow::Result<u64> {
+        let sql_query = format!("DELETE FROM {:?} WHERE processed_at < $1", self.processed_event_table);
+        let result = query(&sql_query)
+            .bind(PrimitiveDateTime::new(before.date(), before.time()))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn replace_commands(
+        &self, session_uuid: Uuid, commands: Vec<NewCommand>, replace_running: bool, replaced_at: OffsetDateTime,
+        event_id: Uuid,
+    ) -> anyhow::Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        if !self.record_processed_event(&mut tx, event_id).await? {
+            return Ok(0);
+        }
+        let sql_query = format!(
+            "SELECT id FROM {session_table} WHERE {session_table}.uuid = $1 FOR UPDATE",
+            session_table = self.session_table,
+        );
+        let session_record_id: i32 = query_scalar(&sql_query)
+            .bind(session_uuid)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(anyhow::anyhow!("No session {session_uuid} to reschedule"))?;
+        let sql_query = format!(
+            "DELETE FROM {command_table} WHERE {command_table}.session_id = $1 AND {command_table}.status = $2",
+            command_table = self.command_table,
+        );
+        query(&sql_query)
+            .bind(session_record_id)
+            .bind(CommandStatus::Planned.name())
+            .execute(&mut *tx)
+            .await?;
+        if replace_running {
+            let sql_query = format!(
+                r#"
+                WITH cancelled AS (
+                    UPDATE {command_table}
+                    SET
+                        status = $1,
+                        status_date = $2
+                    WHERE {command_table}.session_id = $3
+                    AND {command_table}.status = $4
+                    RETURNING {command_table}.session_id, {command_table}.uuid
+                )
+                INSERT INTO {command_event_table} (session_id, command_uuid, kind, status, occurred_at)
+                SELECT cancelled.session_id, cancelled.uuid, $5, $1, $2 FROM cancelled
+                "#,
+                command_table = self.command_table,
+                command_event_table = self.command_event_table,
+            );
+            let status = CommandStatus::Cancelled { at: replaced_at };
+            query(&sql_query)
+                .bind(status.name())
+                .bind(PrimitiveDateTime::new(replaced_at.date(), replaced_at.time()))
+                .bind(session_record_id)
+                .bind(CommandStatus::Running { since: replaced_at }.name())
+                .bind(CommandEventKind::StatusChanged(status.clone()).name())
+                .execute(&mut *tx)
+                .await?;
+        }
+        // the new profile follows the commands that have been kept, its steps are numbered after theirs
         let sql_query = format!(
-            "INSERT INTO {:?} (uuid, fermentation_step_id, status, status_date, value, value_reached_at,value_holding_duration, session_id, execution_order) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)",
-            self.command_table
+            r#"
+            SELECT
+                COALESCE(MAX({command_table}.execution_order) + 1, 0),
+                COALESCE(MAX({command_table}.fermentation_step_id) + 1, 0)
+            FROM {command_table}
+            WHERE {command_table}.session_id = $1
+            "#,
+            command_table = self.command_table,
         );
-        let futures: Vec<_> = records
+        let (next_order, next_step): (i32, i32) =
+            query_as(&sql_query).bind(session_record_id).fetch_one(&mut *tx).await?;
+        let records = commands
             .iter()
-            .enumerate()
-            .map(|(order, rec)| {
-                query(sql_query.as_str())
-                    .bind(rec.command_id)
-                    .bind(rec.fermentation_step_id)
-                    .bind(rec.status.clone())
-                    .bind(rec.status_date)
-                    .bind(rec.value.clone())
-                    .bind(None as Option<PrimitiveDateTime>)
-                    .bind(rec.value_holding_duration)
-                    .bind(rec.session_id)
-                    .bind(order as i32)
-                    .execute(&self.pool)
-            })
-            .collect();
+            .map(|c| NewCommandRecord::from_command(c, session_record_id))
+            .collect::<anyhow::Result<Vec<NewCommandRecord>>>()?;
+        let inserted = self.insert_commands(&mut tx, &records, next_step, next_order).await?;
+        tx.commit().await?;
+        Ok(inserted)
+    }
 
-        futures::future::join_all(futures)
-            .await
+    async fn fetch_running_commands(&self) -> anyhow::Result<Vec<(Uuid, Command)>> {
+        let sql_query = format!(
+            r#"SELECT
+                {command_table}.uuid,
+                {command_table}.fermentation_step_id,
+                {command_table}.status,
+                {command_table}.status_date,
+                {command_table}.value,
+                {command_table}.value_reached_at,
+                {command_table}.value_holding_duration,
+                {command_table}.hold_paused_at,
+                {command_table}.value_held_duration,
+                {command_table}.value_held_at,
+                {command_table}.session_id,
+                {session_table}.uuid AS session_uuid
+             FROM {command_table}
+                INNER JOIN {session_table} ON {command_table}.session_id = {session_table}.id
+                WHERE {command_table}.status = $1
+            "#,
+            command_table = self.command_table,
+            session_table = self.session_table,
+        );
+        let status = CommandStatus::Running {
+            since: OffsetDateTime::now_utc(),
+        };
+        let res: Vec<SessionCommandRecord> = query_as(&sql_query).bind(status.name()).fetch_all(&self.pool).await?;
+        res.iter()
+            .map(|rec| Command::try_from(&rec.command).map(|cmd| (rec.session_uuid, cmd)))
+            .collect()
+    }
+
+    async fn fetch_last_reading_dates(&self) -> anyhow::Result<Vec<(Uuid, OffsetDateTime)>> {
+        let sql_query = format!(
+            r#"SELECT
+                {session_table}.uuid,
+                COALESCE({session_table}.last_reading_at, {command_table}.status_date)
+             FROM {command_table}
+                INNER JOIN {session_table} ON {command_table}.session_id = {session_table}.id
+                WHERE {command_table}.status = $1
+            "#,
+            command_table = self.command_table,
+            session_table = self.session_table,
+        );
+        let status = CommandStatus::Running {
+            since: OffsetDateTime::now_utc(),
+        };
+        let res: Vec<(Uuid, PrimitiveDateTime)> =
+            query_as(&sql_query).bind(status.name()).fetch_all(&self.pool).await?;
+        Ok(res
             .into_iter()
-            .try_fold(0, |acc, result| {
-                result
-                    .map_err(|e| anyhow::anyhow!("Can't execute command insert {}", e))
-                    .map(|query_result| acc + query_result.rows_affected())
-            })
+            .map(|(session_uuid, date)| (session_uuid, date.assume_offset(UtcOffset::UTC)))
+            .collect())
     }
 
     async fn fetch_commands_by_order(
@@ -98,13 +418,16 @@ impl CommandDrivenPort for CommandRepository {
                 {command_table}.value,
                 {command_table}.value_reached_at,
                 {command_table}.value_holding_duration,
+                {command_table}.hold_paused_at,
+                {command_table}.value_held_duration,
+                {command_table}.value_held_at,
                 {command_table}.session_id
              FROM {command_table}
                 INNER JOIN {session_table} ON {command_table}.session_id = {session_table}.id
                 WHERE {command_table}.status = $1 AND {session_table}.uuid = $2
-                {limit_clause}
                ORDER BY 
                 {command_table}.execution_order {order_clause}
+                {limit_clause}
                "#,
             command_table = self.command_table,
             session_table = self.session_table,
@@ -119,50 +442,129 @@ impl CommandDrivenPort for CommandRepository {
         res.iter().map(Command::try_from).collect()
     }
 
-    async fn update_status(&self, command_uuid: Uuid, status: &CommandStatus) -> anyhow::Result<Command> {
-        let date = match status {
-            CommandStatus::Planned => bail!("Command can't be updated to Planned"),
-            CommandStatus::Running { since } => since,
-            CommandStatus::Executed { at } => at,
+    async fn update_status(&self, command_uuid: Uuid, status: &CommandStatus) -> anyhow::Result<Option<Command>> {
+        let mut tx = self.pool.begin().await?;
+        let Some(updated_command_record) = self.update_command_status(&mut tx, command_uuid, status).await? else {
+            return Ok(None);
         };
-        let date = PrimitiveDateTime::new(date.date(), date.time());
+        tx.commit().await?;
+        Command::try_from(&updated_command_record).map(Some)
+    }
+
+    async fn update_value_reached_at(
+        &self, command_uuid: Uuid, value_reached_at: OffsetDateTime,
+    ) -> anyhow::Result<Option<Command>> {
         let sql_query = format!(
             r#"UPDATE {command_table}
         SET
-            status = $1,
-            status_date = $2
-        WHERE {command_table}.uuid = $3
+            value_reached_at = $1,
+            hold_paused_at = NULL
+        WHERE {command_table}.uuid = $2
+        AND {command_table}.status = 'Running'
         RETURNING {command_table}.*"#,
             command_table = self.command_table,
         );
 
-        let updated_command_record: CommandRecord = query_as(&sql_query)
-            .bind(status.name())
-            .bind(date)
+        let mut tx = self.pool.begin().await?;
+        let Some(updated_command_record): Option<CommandRecord> = query_as(&sql_query)
+            .bind(value_reached_at)
             .bind(command_uuid)
-            .fetch_one(&self.pool)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+        self.insert_command_event(
+            &mut tx,
+            &updated_command_record,
+            CommandEventKind::ValueReached,
+            value_reached_at,
+        )
+        .await?;
+        tx.commit().await?;
+        Command::try_from(&updated_command_record).map(Some)
+    }
+
+    async fn reset_value_reached_at(
+        &self, command_uuid: Uuid, reset_at: OffsetDateTime,
+    ) -> anyhow::Result<Option<Command>> {
+        let sql_query = format!(
+            r#"UPDATE {command_table}
+        SET
+            value_reached_at = NULL,
+            hold_paused_at = NULL
+        WHERE {command_table}.uuid = $1
+        AND {command_table}.status = 'Running'
+        RETURNING {command_table}.*"#,
+            command_table = self.command_table,
+        );
+
+        let mut tx = self.pool.begin().await?;
+        let Some(updated_command_record): Option<CommandRecord> =
+            query_as(&sql_query).bind(command_uuid).fetch_optional(&mut *tx).await?
+        else {
+            return Ok(None);
+        };
+        self.insert_command_event(&mut tx, &updated_command_record, CommandEventKind::ValueReset, reset_at)
             .await?;
-        Command::try_from(&updated_command_record)
+        tx.commit().await?;
+        Command::try_from(&updated_command_record).map(Some)
     }
 
-    async fn update_value_reached_at(
-        &self, command_uuid: Uuid, value_reached_at: OffsetDateTime,
-    ) -> anyhow::Result<Command> {
+    async fn pause_value_reached_at(
+        &self, command_uuid: Uuid, paused_at: OffsetDateTime,
+    ) -> anyhow::Result<Option<Command>> {
         let sql_query = format!(
             r#"UPDATE {command_table}
         SET
-            value_reached_at = $1
+            hold_paused_at = $1
         WHERE {command_table}.uuid = $2
+        AND {command_table}.status = 'Running'
         RETURNING {command_table}.*"#,
             command_table = self.command_table,
         );
 
-        let updated_command_record: CommandRecord = query_as(&sql_query)
-            .bind(value_reached_at)
+        let mut tx = self.pool.begin().await?;
+        let Some(updated_command_record): Option<CommandRecord> = query_as(&sql_query)
+            .bind(PrimitiveDateTime::new(paused_at.date(), paused_at.time()))
             .bind(command_uuid)
-            .fetch_one(&self.pool)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+        self.insert_command_event(
+            &mut tx,
+            &updated_command_record,
+            CommandEventKind::HoldPaused,
+            paused_at,
+        )
+        .await?;
+        tx.commit().await?;
+        Command::try_from(&updated_command_record).map(Some)
+    }
+
+    async fn update_value_held(
+        &self, command_uuid: Uuid, value_held_duration: Duration, value_held_at: Option<OffsetDateTime>,
+    ) -> anyhow::Result<Option<Command>> {
+        let sql_query = format!(
+            r#"UPDATE {command_table}
+        SET
+            value_held_duration = $1,
+            value_held_at = $2
+        WHERE {command_table}.uuid = $3
+        AND {command_table}.status = 'Running'
+        RETURNING {command_table}.*"#,
+            command_table = self.command_table,
+        );
+
+        let updated_command_record: Option<CommandRecord> = query_as(&sql_query)
+            .bind(value_held_duration.whole_seconds() as i32)
+            .bind(value_held_at.map(|d| PrimitiveDateTime::new(d.date(), d.time())))
+            .bind(command_uuid)
+            .fetch_optional(&self.pool)
             .await?;
-        Command::try_from(&updated_command_record)
+        updated_command_record.as_ref().map(Command::try_from).transpose()
     }
 
     async fn fetch_hardware_id(&self, session_uuid: Uuid, hardware_type: &HardwareType) -> anyhow::Result<String> {
@@ -210,236 +612,2126 @@ impl CommandDrivenPort for CommandRepository {
         })
     }
 
-    async fn update_active_hardware_type(
-        &self, session_uuid: Uuid, active_hardware_type: Option<HardwareType>,
-    ) -> anyhow::Result<()> {
+    async fn fetch_session_settings(&self, session_uuid: Uuid) -> anyhow::Result<SessionSettings> {
+        let sql_query = format!(
+            r#"SELECT
+                {session_table}.hysteresis,
+                {session_table}.control_mode,
+                {session_table}.min_temperature,
+                {session_table}.max_temperature,
+                {session_table}.heating_locked_out,
+                {session_table}.cooling_locked_out,
+                {session_table}.paused_at,
+                {session_table}.override_hardware_type,
+                {session_table}.override_state,
+                {session_table}.override_until,
+                {session_table}.status,
+                {session_table}.completed_at,
+                {session_table}.faulted_at
+              FROM {session_table}
+                WHERE {session_table}.uuid = $1
+            "#,
+            session_table = self.session_table,
+        );
+        let record: SessionSettingsRecord = query_as(&sql_query).bind(session_uuid).fetch_one(&self.pool).await?;
+        SessionSettings::try_from(&record)
+    }
+
+    async fn fetch_pid_state(&self, session_uuid: Uuid) -> anyhow::Result<PidState> {
+        let sql_query = format!(
+            r#"SELECT
+                {session_table}.pid_integral,
+                {session_table}.pid_last_error,
+                {session_table}.pid_window_start,
+                {session_table}.pid_updated_at
+              FROM {session_table}
+                WHERE {session_table}.uuid = $1
+            "#,
+            session_table = self.session_table,
+        );
+        let record: PidStateRecord = query_as(&sql_query).bind(session_uuid).fetch_one(&self.pool).await?;
+        Ok(PidState::from(&record))
+    }
+
+    async fn fetch_reading_window(&self, session_uuid: Uuid) -> anyhow::Result<ReadingWindow> {
+        let sql_query = format!(
+            r#"SELECT
+                {session_table}.reading_window,
+                {session_table}.filtered_temperature,
+                {session_table}.outlier_count
+              FROM {session_table}
+                WHERE {session_table}.uuid = $1
+            "#,
+            session_table = self.session_table,
+        );
+        let record: ReadingWindowRecord = query_as(&sql_query).bind(session_uuid).fetch_one(&self.pool).await?;
+        Ok(ReadingWindow::from(record))
+    }
+
+    async fn update_pid_state(&self, session_uuid: Uuid, state: PidState) -> anyhow::Result<()> {
         let sql_query = format!(
             r#"
             UPDATE {session_table}
             SET
-                active_hardware_type = $1
-            WHERE {session_table}.uuid = $2
+                pid_integral = $1,
+                pid_last_error = $2,
+                pid_window_start = $3,
+                pid_updated_at = $4
+            WHERE {session_table}.uuid = $5
             "#,
             session_table = self.session_table,
         );
-
         query(&sql_query)
-            .bind(active_hardware_type.map(|it| it.name()))
+            .bind(state.integral)
+            .bind(state.last_error)
+            .bind(state.window_start.map(|d| PrimitiveDateTime::new(d.date(), d.time())))
+            .bind(state.updated_at.map(|d| PrimitiveDateTime::new(d.date(), d.time())))
             .bind(session_uuid)
             .execute(&self.pool)
-            .map(|_| Ok(()))
-            .await
+            .await?;
+        Ok(())
     }
-}
 
-#[derive(sqlx::FromRow)]
-struct CommandRecord {
-    pub uuid: Uuid,
-    pub fermentation_step_id: i32,
-    pub status: String,
-    pub status_date: Option<PrimitiveDateTime>,
-    pub value: BigDecimal,
-    pub value_reached_at: Option<PrimitiveDateTime>,
-    pub value_holding_duration: i32,
-    pub session_id: i32,
-}
-impl CommandRecord {
-    fn status_to_command_status(&self, date: Option<PrimitiveDateTime>) -> anyhow::Result<CommandStatus> {
-        Ok(match self.status.as_str() {
-            "Planned" => CommandStatus::Planned,
-            "Running" => {
-                CommandStatus::Running {
-                    since: date.map(|d| d.assume_offset(UtcOffset::UTC)).ok_or(
-                        CommandSchedulerServiceError::NotFound("date for running command status".to_string()),
-                    )?,
-                }
-            }
-            "Executed" => CommandStatus::Executed {
-                at: date
-                    .map(|d| d.assume_offset(UtcOffset::UTC))
-                    .ok_or(CommandSchedulerServiceError::NotFound(
-                        "date for executed command status".to_string(),
-                    ))?,
-            },
-            _ => bail!("{} is not a valid status", self.status.as_str()),
+    async fn fetch_hardware_switch_history(
+        &self, hardware_id: &str, since: OffsetDateTime,
+    ) -> anyhow::Result<HardwareSwitchHistory> {
+        let sql_query = format!(
+            r#"SELECT
+                {hardware_switch_table}.state,
+                {hardware_switch_table}.switched_at
+              FROM {hardware_switch_table}
+                WHERE {hardware_switch_table}.hardware_id = $1
+                ORDER BY {hardware_switch_table}.switched_at DESC
+                LIMIT 1
+            "#,
+            hardware_switch_table = self.hardware_switch_table,
+        );
+        let record: Option<HardwareSwitchRecord> = query_as(&sql_query)
+            .bind(hardware_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        let sql_query = format!(
+            r#"SELECT
+                COUNT(*)
+              FROM {hardware_switch_table}
+                WHERE {hardware_switch_table}.hardware_id = $1 AND {hardware_switch_table}.switched_at >= $2
+            "#,
+            hardware_switch_table = self.hardware_switch_table,
+        );
+        let toggles: i64 = query_scalar(&sql_query)
+            .bind(hardware_id)
+            .bind(PrimitiveDateTime::new(since.date(), since.time()))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(HardwareSwitchHistory {
+            last_switch: record.as_ref().map(HardwareSwitch::try_from).transpose()?,
+            toggles_last_hour: toggles.try_into()?,
         })
     }
-}
-impl TryFrom<&CommandRecord> for Command {
-    type Error = anyhow::Error;
 
-    fn try_from(record: &CommandRecord) -> Result<Self, Self::Error> {
-        Ok(Command {
-            uuid: record.uuid,
-            fermentation_step_id: record.fermentation_step_id,
-            status: record.status_to_command_status(record.status_date)?,
-            temperature_data: CommandTemperatureData {
-                value: record
-                    .value
-                    .to_f32()
-                    .ok_or(CommandSchedulerServiceError::ConversionError("record value", "f32"))?,
-                value_reached_at: record
-                    .value_reached_at
-                    .map(|p_date| p_date.assume_offset(UtcOffset::UTC)),
-                value_holding_duration: Duration::hours(record.value_holding_duration as i64),
-            },
-            session_id: record.session_id,
-        })
+    async fn record_hardware_switch(
+        &self, session_uuid: Uuid, hardware_id: &str, switch: HardwareSwitch, is_forced: bool, is_toggle: bool,
+    ) -> anyhow::Result<()> {
+        let switched_at = PrimitiveDateTime::new(switch.at.date(), switch.at.time());
+        let mut tx = self.pool.begin().await?;
+        if is_toggle {
+            let sql_query = format!(
+                "INSERT INTO {:?} (hardware_id, state, switched_at) VALUES ($1,$2,$3)",
+                self.hardware_switch_table
+            );
+            query(&sql_query)
+                .bind(hardware_id)
+                .bind(switch.state.name())
+                .bind(switched_at)
+                .execute(&mut *tx)
+                .await?;
+        }
+        // the switch is tied to the command running at that time, if any
+        let sql_query = format!(
+            r#"
+            INSERT INTO {command_event_table} (session_id, command_uuid, kind, hardware_id, hardware_state, is_forced, occurred_at)
+            SELECT
+                {session_table}.id,
+                (
+                    SELECT {command_table}.uuid FROM {command_table}
+                    WHERE {command_table}.session_id = {session_table}.id AND {command_table}.status = $2
+                    LIMIT 1
+                ),
+                $3, $4, $5, $6, $7
+            FROM {session_table}
+            WHERE {session_table}.uuid = $1
+            "#,
+            command_event_table = self.command_event_table,
+            command_table = self.command_table,
+            session_table = self.session_table,
+        );
+        let kind = CommandEventKind::HardwareSwitched {
+            hardware_id: hardware_id.to_string(),
+            state: switch.state.clone(),
+            is_forced,
+        };
+        query(&sql_query)
+            .bind(session_uuid)
+            .bind(CommandStatus::Running { since: switch.at }.name())
+            .bind(kind.name())
+            .bind(hardware_id)
+            .bind(switch.state.name())
+            .bind(is_forced)
+            .bind(switched_at)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn fetch_session_timeline(&self, session_uuid: Uuid) -> anyhow::Result<Vec<CommandEvent>> {
+        let sql_query = format!(
+            r#"SELECT
+                {command_event_table}.command_uuid,
+                {command_event_table}.kind,
+                {command_event_table}.status,
+                {command_event_table}.hardware_id,
+                {command_event_table}.hardware_state,
+                {command_event_table}.is_forced,
+                {command_event_table}.occurred_at
+              FROM {command_event_table}
+                INNER JOIN {session_table} ON {command_event_table}.session_id = {session_table}.id
+                WHERE {session_table}.uuid = $1
+                ORDER BY {command_event_table}.id ASC
+            "#,
+            command_event_table = self.command_event_table,
+            session_table = self.session_table,
+        );
+        let records: Vec<CommandEventRecord> = query_as(&sql_query).bind(session_uuid).fetch_all(&self.pool).await?;
+        records.iter().map(CommandEvent::try_from).collect()
+    }
+
+    async fn lock_out_hardware(
+        &self, session_uuid: Uuid, hardware_type: &HardwareType, faulted_at: OffsetDateTime,
+    ) -> anyhow::Result<()> {
+        let locked_out_field = match hardware_type {
+            HardwareType::Cooling => "cooling_locked_out",
+            HardwareType::Heating => "heating_locked_out",
+        };
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET
+                {locked_out_field} = TRUE,
+                faulted_at = COALESCE(faulted_at, $1),
+                status = CASE WHEN status = 'Active' THEN 'Faulted' ELSE status END
+            WHERE {session_table}.uuid = $2
+            "#,
+            session_table = self.session_table,
+        );
+        query(&sql_query)
+            .bind(PrimitiveDateTime::new(faulted_at.date(), faulted_at.time()))
+            .bind(session_uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_session_status(&self, session_uuid: Uuid, status: &SessionStatus) -> anyhow::Result<()> {
+        let completed_at = match status {
+            SessionStatus::Completed { at } | SessionStatus::Cancelled { at } => at,
+            _ => bail!("Session can't be updated to {}", status.name()),
+        };
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET
+                status = $1,
+                completed_at = $2
+            WHERE {session_table}.uuid = $3
+            "#,
+            session_table = self.session_table,
+        );
+        query(&sql_query)
+            .bind(status.name())
+            .bind(PrimitiveDateTime::new(completed_at.date(), completed_at.time()))
+            .bind(session_uuid)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn is_new_event(&self, event_id: Uuid) -> anyhow::Result<bool> {
+        let sql_query = format!(
+            "SELECT NOT EXISTS (SELECT 1 FROM {processed_event_table} WHERE {processed_event_table}.event_id = $1)",
+            processed_event_table = self.processed_event_table,
+        );
+        let is_new: bool = query_scalar(&sql_query).bind(event_id).fetch_one(&self.pool).await?;
+        Ok(is_new)
+    }
+
+    async fn reset_lockout(&self, session_uuid: Uuid, event_id: Uuid) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        if !self.record_processed_event(&mut tx, event_id).await? {
+            return Ok(false);
+        }
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET
+                heating_locked_out = FALSE,
+                cooling_locked_out = FALSE,
+                faulted_at = NULL,
+                status = CASE WHEN status = 'Faulted' THEN 'Active' ELSE status END
+            WHERE {session_table}.uuid = $1
+            "#,
+            session_table = self.session_table,
+        );
+        query(&sql_query).bind(session_uuid).execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn update_session_paused_at(
+        &self, session_uuid: Uuid, paused_at: Option<OffsetDateTime>, event_id: Uuid,
+    ) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        if !self.record_processed_event(&mut tx, event_id).await? {
+            return Ok(false);
+        }
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET paused_at = $1
+            WHERE {session_table}.uuid = $2
+            "#,
+            session_table = self.session_table,
+        );
+        query(&sql_query)
+            .bind(paused_at.map(|d| PrimitiveDateTime::new(d.date(), d.time())))
+            .bind(session_uuid)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn update_hardware_override(
+        &self, session_uuid: Uuid, hardware_override: Option<HardwareOverride>, event_id: Option<Uuid>,
+    ) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(event_id) = event_id
+            && !self.record_processed_event(&mut tx, event_id).await?
+        {
+            return Ok(false);
+        }
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET
+                override_hardware_type = $1,
+                override_state = $2,
+                override_until = $3
+            WHERE {session_table}.uuid = $4
+            "#,
+            session_table = self.session_table,
+        );
+        query(&sql_query)
+            .bind(hardware_override.as_ref().map(|o| o.hardware_type.name()))
+            .bind(hardware_override.as_ref().map(|o| o.state.name()))
+            .bind(
+                hardware_override
+                    .as_ref()
+                    .map(|o| PrimitiveDateTime::new(o.until.date(), o.until.time())),
+            )
+            .bind(session_uuid)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn cancel_commands(
+        &self, session_uuid: Uuid, cancelled_at: OffsetDateTime, event_id: Uuid,
+    ) -> anyhow::Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        if !self.record_processed_event(&mut tx, event_id).await? {
+            return Ok(0);
+        }
+        let sql_query = format!(
+            r#"
+            WITH cancelled AS (
+                UPDATE {command_table}
+                SET
+                    status = $1,
+                    status_date = $2
+                FROM {session_table}
+                WHERE {command_table}.session_id = {session_table}.id
+                AND {session_table}.uuid = $3
+                AND {command_table}.status IN ($4, $5)
+                RETURNING {command_table}.session_id, {command_table}.uuid
+            )
+            INSERT INTO {command_event_table} (session_id, command_uuid, kind, status, occurred_at)
+            SELECT cancelled.session_id, cancelled.uuid, $6, $1, $2 FROM cancelled
+            "#,
+            command_table = self.command_table,
+            session_table = self.session_table,
+            command_event_table = self.command_event_table,
+        );
+        let status = CommandStatus::Cancelled { at: cancelled_at };
+        let result = query(&sql_query)
+            .bind(status.name())
+            .bind(PrimitiveDateTime::new(cancelled_at.date(), cancelled_at.time()))
+            .bind(session_uuid)
+            .bind(CommandStatus::Planned.name())
+            .bind(CommandStatus::Running { since: cancelled_at }.name())
+            .bind(CommandEventKind::StatusChanged(status.clone()).name())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn skip_commands(
+        &self, running: Option<Uuid>, skipped: Vec<Uuid>, skipped_at: OffsetDateTime, event_id: Uuid,
+    ) -> anyhow::Result<Option<u64>> {
+        let mut tx = self.pool.begin().await?;
+        if !self.record_processed_event(&mut tx, event_id).await? {
+            return Ok(None);
+        }
+        if let Some(running) = running
+            && self
+                .update_command_status(&mut tx, running, &CommandStatus::Executed { at: skipped_at })
+                .await?
+                .is_none()
+        {
+            return Ok(None);
+        }
+        let status = CommandStatus::Skipped { at: skipped_at };
+        let mut count = 0;
+        for uuid in skipped {
+            match self.update_command_status(&mut tx, uuid, &status).await? {
+                Some(_) => count += 1,
+                None => debug!("Command {uuid} isn't planned anymore, it isn't skipped"),
+            }
+        }
+        tx.commit().await?;
+        Ok(Some(count))
+    }
+
+    async fn is_new_reading(
+        &self, session_uuid: Uuid, measured_at: OffsetDateTime, event_id: Option<Uuid>,
+    ) -> anyhow::Result<bool> {
+        let sql_query = format!(
+            r#"
+            SELECT
+                NOT EXISTS (SELECT 1 FROM {processed_event_table} WHERE {processed_event_table}.event_id = $3)
+                AND EXISTS (
+                    SELECT 1 FROM {session_table}
+                    WHERE {session_table}.uuid = $1
+                    AND ({session_table}.last_reading_at IS NULL OR {session_table}.last_reading_at < $2)
+                )
+            "#,
+            processed_event_table = self.processed_event_table,
+            session_table = self.session_table,
+        );
+        let is_new: bool = query_scalar(&sql_query)
+            .bind(session_uuid)
+            .bind(PrimitiveDateTime::new(measured_at.date(), measured_at.time()))
+            .bind(event_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(is_new)
+    }
+
+    async fn record_reading(
+        &self, session_uuid: Uuid, measured_at: OffsetDateTime, accepted: Option<TemperatureReading>,
+        window: Option<ReadingWindow>, event_id: Option<Uuid>,
+    ) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(event_id) = event_id
+            && !self.record_processed_event(&mut tx, event_id).await?
+        {
+            return Ok(false);
+        }
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET last_reading_at = $1
+            WHERE {session_table}.uuid = $2
+            AND ({session_table}.last_reading_at IS NULL OR {session_table}.last_reading_at < $1)
+            "#,
+            session_table = self.session_table,
+        );
+        let result = query(&sql_query)
+            .bind(PrimitiveDateTime::new(measured_at.date(), measured_at.time()))
+            .bind(session_uuid)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() != 1 {
+            return Ok(false);
+        }
+        if let Some(reading) = accepted {
+            self.insert_temperature_reading(&mut tx, reading).await?;
+        }
+        if let Some(window) = window {
+            self.update_reading_window(&mut tx, session_uuid, window).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn update_active_hardware_type(
+        &self, session_uuid: Uuid, active_hardware_type: Option<HardwareType>,
+    ) -> anyhow::Result<()> {
+        let sql_query = format!(
+            r#"
+            UPDATE {session_table}
+            SET
+                active_hardware_type = $1
+            WHERE {session_table}.uuid = $2
+            "#,
+            session_table = self.session_table,
+        );
+
+        query(&sql_query)
+            .bind(active_hardware_type.map(|it| it.name()))
+            .bind(session_uuid)
+            .execute(&self.pool)
+            .map(|_| Ok(()))
+            .await
+    }
+
+    async fn fetch_temperature_readings(
+        &self, session_uuid: Uuid, from: OffsetDateTime, to: OffsetDateTime,
+    ) -> anyhow::Result<Vec<TemperatureReading>> {
+        let sql_query = format!(
+            r#"SELECT
+                {temperature_reading_table}.measured_at,
+                {temperature_reading_table}.temperature,
+                {temperature_reading_table}.source
+              FROM {temperature_reading_table}
+                INNER JOIN {session_table} ON {temperature_reading_table}.session_id = {session_table}.id
+                WHERE {session_table}.uuid = $1
+                  AND {temperature_reading_table}.measured_at >= $2
+                  AND {temperature_reading_table}.measured_at < $3
+                ORDER BY {temperature_reading_table}.measured_at ASC
+            "#,
+            temperature_reading_table = self.temperature_reading_table,
+            session_table = self.session_table,
+        );
+        let records: Vec<TemperatureReadingRecord> = query_as(&sql_query)
+            .bind(session_uuid)
+            .bind(PrimitiveDateTime::new(from.date(), from.time()))
+            .bind(PrimitiveDateTime::new(to.date(), to.time()))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(records
+            .into_iter()
+            .map(|record| TemperatureReading {
+                session_id: session_uuid,
+                measured_at: record.measured_at.assume_offset(UtcOffset::UTC),
+                temperature: record.temperature,
+                source: record.source,
+            })
+            .collect())
+    }
+
+    async fn purge_temperature_readings(&self, before: OffsetDateTime) -> anyhow::Result<u64> {
+        let sql_query = format!(
+            "DELETE FROM {:?} WHERE measured_at < $1",
+            self.temperature_reading_table
+        );
+        let result = query(&sql_query)
+            .bind(PrimitiveDateTime::new(before.date(), before.time()))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
     }
 }
 
-struct NewCommandRecord {
-    pub command_id: Uuid,
+#[derive(sqlx::FromRow)]
+struct CommandRecord {
+    pub uuid: Uuid,
     pub fermentation_step_id: i32,
     pub status: String,
     pub status_date: Option<PrimitiveDateTime>,
     pub value: BigDecimal,
+    pub value_reached_at: Option<PrimitiveDateTime>,
     pub value_holding_duration: i32,
+    pub hold_paused_at: Option<PrimitiveDateTime>,
+    pub value_held_duration: i32,
+    pub value_held_at: Option<PrimitiveDateTime>,
     pub session_id: i32,
 }
+fn to_command_status(status: &str, date: Option<PrimitiveDateTime>) -> anyhow::Result<CommandStatus> {
+    Ok(match status {
+        "Planned" => CommandStatus::Planned,
+        "Running" => CommandStatus::Running {
+            since: date
+                .map(|d| d.assume_offset(UtcOffset::UTC))
+                .ok_or(CommandSchedulerServiceError::NotFound(
+                    "date for running command status".to_string(),
+                ))?,
+        },
+        "Executed" => CommandStatus::Executed {
+            at: date
+                .map(|d| d.assume_offset(UtcOffset::UTC))
+                .ok_or(CommandSchedulerServiceError::NotFound(
+                    "date for executed command status".to_string(),
+                ))?,
+        },
+        "Cancelled" => CommandStatus::Cancelled {
+            at: date
+                .map(|d| d.assume_offset(UtcOffset::UTC))
+                .ok_or(CommandSchedulerServiceError::NotFound(
+                    "date for cancelled command status".to_string(),
+                ))?,
+        },
+        "Skipped" => CommandStatus::Skipped {
+            at: date
+                .map(|d| d.assume_offset(UtcOffset::UTC))
+                .ok_or(CommandSchedulerServiceError::NotFound(
+                    "date for skipped command status".to_string(),
+                ))?,
+        },
+        _ => bail!("{} is not a valid status", status),
+    })
+}
+impl TryFrom<&CommandRecord> for Command {
+    type Error = anyhow::Error;
 
-impl NewCommandRecord {
-    fn from_command(command: &NewCommand, session_record_id: i32) -> anyhow::Result<Self> {
-        Ok(Self {
-            command_id: command.id,
-            fermentation_step_id: command.session_data.step_position as i32,
-            status: command.status.name().into(),
-            status_date: command
-                .status
-                .date()
-                .map(|d| PrimitiveDateTime::new(d.date(), d.time())),
-            value: BigDecimal::from_str(&format!("{:.1}", command.value))?.with_scale(1),
-            value_holding_duration: command.value_holding_duration.whole_hours() as i32,
-            session_id: session_record_id,
+    fn try_from(record: &CommandRecord) -> Result<Self, Self::Error> {
+        Ok(Command {
+            uuid: record.uuid,
+            fermentation_step_id: record.fermentation_step_id,
+            status: to_command_status(&record.status, record.status_date)?,
+            temperature_data: CommandTemperatureData {
+                value: record
+                    .value
+                    .to_f32()
+                    .ok_or(CommandSchedulerServiceError::ConversionError("record value", "f32"))?,
+                value_reached_at: record
+                    .value_reached_at
+                    .map(|p_date| p_date.assume_offset(UtcOffset::UTC)),
+                value_holding_duration: Duration::hours(record.value_holding_duration as i64),
+                hold_paused_at: record.hold_paused_at.map(|d| d.assume_offset(UtcOffset::UTC)),
+                value_held_duration: Duration::seconds(record.value_held_duration as i64),
+                value_held_at: record.value_held_at.map(|d| d.assume_offset(UtcOffset::UTC)),
+            },
+            session_id: record.session_id,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SessionCommandRecord {
+    #[sqlx(flatten)]
+    pub command: CommandRecord,
+    pub session_uuid: Uuid,
+}
+
+#[derive(sqlx::FromRow)]
+struct SessionSettingsRecord {
+    pub hysteresis: BigDecimal,
+    pub control_mode: String,
+    pub min_temperature: Option<BigDecimal>,
+    pub max_temperature: Option<BigDecimal>,
+    pub heating_locked_out: bool,
+    pub cooling_locked_out: bool,
+    pub paused_at: Option<PrimitiveDateTime>,
+    pub override_hardware_type: Option<String>,
+    pub override_state: Option<String>,
+    pub override_until: Option<PrimitiveDateTime>,
+    pub status: String,
+    pub completed_at: Option<PrimitiveDateTime>,
+    pub faulted_at: Option<PrimitiveDateTime>,
+}
+impl TryFrom<&SessionSettingsRecord> for SessionSettings {
+    type Error = anyhow::Error;
+
+    fn try_from(record: &SessionSettingsRecord) -> Result<Self, Self::Error> {
+        Ok(SessionSettings {
+            hysteresis: record
+                .hysteresis
+                .to_f32()
+                .ok_or(CommandSchedulerServiceError::ConversionError(
+                    "session hysteresis",
+                    "f32",
+                ))?,
+            control_mode: match record.control_mode.as_str() {
+                "Hysteresis" => ControlMode::Hysteresis,
+                "Pid" => ControlMode::Pid,
+                other => bail!("Unknown control mode: {}", other),
+            },
+            temperature_limits: TemperatureLimits {
+                min: from_temperature_record(record.min_temperature.as_ref(), "session min temperature")?,
+                max: from_temperature_record(record.max_temperature.as_ref(), "session max temperature")?,
+            },
+            lockout: HardwareLockout {
+                heating: record.heating_locked_out,
+                cooling: record.cooling_locked_out,
+            },
+            paused_at: record.paused_at.map(|d| d.assume_offset(UtcOffset::UTC)),
+            hardware_override: match (
+                record.override_hardware_type.as_deref(),
+                record.override_state.as_deref(),
+                record.override_until,
+            ) {
+                (Some(hardware_type), Some(state), Some(until)) => Some(HardwareOverride {
+                    hardware_type: match hardware_type {
+                        "Heating" => HardwareType::Heating,
+                        "Cooling" => HardwareType::Cooling,
+                        other => bail!("Unknown Hardware type: {}", other),
+                    },
+                    state: match state {
+                        "On" => HardwareState::On,
+                        "Off" => HardwareState::Off,
+                        other => bail!("Unknown hardware state: {}", other),
+                    },
+                    until: until.assume_offset(UtcOffset::UTC),
+                }),
+                _ => None,
+            },
+            status: match record.status.as_str() {
+                "Active" => SessionStatus::Active,
+                "Completed" => SessionStatus::Completed {
+                    at: record.completed_at.map(|d| d.assume_offset(UtcOffset::UTC)).ok_or(
+                        CommandSchedulerServiceError::NotFound("date for completed session status".to_string()),
+                    )?,
+                },
+                "Cancelled" => SessionStatus::Cancelled {
+                    at: record.completed_at.map(|d| d.assume_offset(UtcOffset::UTC)).ok_or(
+                        CommandSchedulerServiceError::NotFound("date for cancelled session status".to_string()),
+                    )?,
+                },
+                "Faulted" => SessionStatus::Faulted {
+                    since: record.faulted_at.map(|d| d.assume_offset(UtcOffset::UTC)).ok_or(
+                        CommandSchedulerServiceError::NotFound("date for faulted session status".to_string()),
+                    )?,
+                },
+                other => bail!("{} is not a valid session status", other),
+            },
+        })
+    }
+}
+
+fn to_temperature_record(temperature: Option<f32>) -> anyhow::Result<Option<BigDecimal>> {
+    temperature
+        .map(|t| BigDecimal::from_str(&format!("{:.1}", t)).map(|d| d.with_scale(1)))
+        .transpose()
+        .map_err(|e| anyhow::anyhow!(e))
+}
+
+fn from_temperature_record(
+    temperature: Option<&BigDecimal>, name: &'static str,
+) -> Result<Option<f32>, CommandSchedulerServiceError> {
+    temperature
+        .map(|t| {
+            t.to_f32()
+                .ok_or(CommandSchedulerServiceError::ConversionError(name, "f32"))
         })
+        .transpose()
+}
+
+#[derive(sqlx::FromRow)]
+struct PidStateRecord {
+    pub pid_integral: f32,
+    pub pid_last_error: Option<f32>,
+    pub pid_window_start: Option<PrimitiveDateTime>,
+    pub pid_updated_at: Option<PrimitiveDateTime>,
+}
+impl From<&PidStateRecord> for PidState {
+    fn from(record: &PidStateRecord) -> Self {
+        PidState {
+            integral: record.pid_integral,
+            last_error: record.pid_last_error,
+            window_start: record.pid_window_start.map(|d| d.assume_offset(UtcOffset::UTC)),
+            updated_at: record.pid_updated_at.map(|d| d.assume_offset(UtcOffset::UTC)),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ReadingWindowRecord {
+    pub reading_window: Vec<f32>,
+    pub filtered_temperature: Option<f32>,
+    pub outlier_count: i32,
+}
+impl From<ReadingWindowRecord> for ReadingWindow {
+    fn from(record: ReadingWindowRecord) -> Self {
+        ReadingWindow {
+            readings: record.reading_window,
+            filtered: record.filtered_temperature,
+            outliers: record.outlier_count as u32,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct HardwareSwitchRecord {
+    pub state: String,
+    pub switched_at: PrimitiveDateTime,
+}
+impl TryFrom<&HardwareSwitchRecord> for HardwareSwitch {
+    type Error = anyhow::Error;
+
+    fn try_from(record: &HardwareSwitchRecord) -> Result<Self, Self::Error> {
+        Ok(HardwareSwitch {
+            state: match record.state.as_str() {
+                "On" => HardwareState::On,
+                "Off" => HardwareState::Off,
+                other => bail!("Unknown hardware state: {}", other),
+            },
+            at: record.switched_at.assume_offset(UtcOffset::UTC),
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct CommandEventRecord {
+    pub command_uuid: Option<Uuid>,
+    pub kind: String,
+    pub status: Option<String>,
+    pub hardware_id: Option<String>,
+    pub hardware_state: Option<String>,
+    pub is_forced: Option<bool>,
+    pub occurred_at: PrimitiveDateTime,
+}
+impl TryFrom<&CommandEventRecord> for CommandEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(record: &CommandEventRecord) -> Result<Self, Self::Error> {
+        let kind = match record.kind.as_str() {
+            "StatusChanged" => CommandEventKind::StatusChanged(to_command_status(
+                record.status.as_deref().unwrap_or_default(),
+                Some(record.occurred_at),
+            )?),
+            "ValueReached" => CommandEventKind::ValueReached,
+            "ValueReset" => CommandEventKind::ValueReset,
+            "HoldPaused" => CommandEventKind::HoldPaused,
+            "HardwareSwitched" => CommandEventKind::HardwareSwitched {
+                hardware_id: record.hardware_id.clone().unwrap_or_default(),
+                state: match record.hardware_state.as_deref() {
+                    Some("On") => HardwareState::On,
+                    Some("Off") => HardwareState::Off,
+                    other => bail!("Unknown hardware state: {:?}", other),
+                },
+                is_forced: record.is_forced.unwrap_or_default(),
+            },
+            other => bail!("{} is not a valid command event", other),
+        };
+        Ok(CommandEvent {
+            command_id: record.command_uuid,
+            occurred_at: record.occurred_at.assume_offset(UtcOffset::UTC),
+            kind,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TemperatureReadingRecord {
+    pub measured_at: PrimitiveDateTime,
+    pub temperature: f32,
+    pub source: Option<String>,
+}
+
+struct NewCommandRecord {
+    pub command_id: Uuid,
+    pub fermentation_step_id: i32,
+    pub status: String,
+    pub status_date: Option<PrimitiveDateTime>,
+    pub value: BigDecimal,
+    pub value_holding_duration: i32,
+    pub session_id: i32,
+}
+
+impl NewCommandRecord {
+    fn from_command(command: &NewCommand, session_record_id: i32) -> anyhow::Result<Self> {
+        Ok(Self {
+            command_id: command.id,
+            fermentation_step_id: command.session_data.step_position as i32,
+            status: command.status.name().into(),
+            status_date: command
+                .status
+                .date()
+                .map(|d| PrimitiveDateTime::new(d.date(), d.time())),
+            value: BigDecimal::from_str(&format!("{:.1}", command.value))?.with_scale(1),
+            value_holding_duration: command.value_holding_duration.whole_hours() as i32,
+            session_id: session_record_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{CommandRepository, NewCommandRecord};
+    use internal::{
+        domain::{
+            command::{CommandStatus, NewCommand, SessionData},
+            command_event::{CommandEvent, CommandEventKind},
+            filter::ReadingWindow,
+            message::{Hardware, HardwareType},
+            pid::PidState,
+            protection::{HardwareState, HardwareSwitch, HardwareSwitchHistory},
+            session::{
+                ControlMode, HardwareLockout, HardwareOverride, SessionSettings, SessionStatus, TemperatureLimits,
+            },
+            sorting::{QueryOptions, Sorting},
+            temperature_reading::TemperatureReading,
+        },
+        port::command::CommandDrivenPort,
+    };
+    use sqlx::{PgPool, query_as, query_scalar, types::BigDecimal};
+    use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
+    use uuid::Uuid;
+
+    #[test]
+    fn should_create_new_command_record() {
+        let session_id = 1;
+        let record = NewCommandRecord::from_command(&NewCommand::default(), 1).unwrap();
+        assert_eq!(record.value_holding_duration, 0);
+        assert_eq!(record.value, BigDecimal::from_str("0.0").unwrap());
+        assert_eq!(record.fermentation_step_id, 0);
+        assert_eq!(record.command_id, Uuid::default());
+        assert_eq!(record.session_id, session_id);
+        assert_eq!(record.status, "Planned");
+        assert_eq!(record.status_date, None);
+    }
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_insert_commands(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let cmds = vec![NewCommand::default()];
+        let heating_h = Hardware::new(String::from("heating_id"), HardwareType::Heating);
+        let cooling_h = Hardware::new(String::from("cooling_id"), HardwareType::Cooling);
+        let result = repo
+            .insert(cmds, heating_h, cooling_h, SessionSettings::default(), Uuid::new_v4())
+            .await;
+        assert_eq!(result.unwrap(), 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_not_insert_an_already_processed_schedule_event(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let event_id = Uuid::new_v4();
+        for expected in [1, 0] {
+            let inserted = repo
+                .insert(
+                    vec![NewCommand::default()],
+                    Hardware::new(String::from("heating_id"), HardwareType::Heating),
+                    Hardware::new(String::from("cooling_id"), HardwareType::Cooling),
+                    SessionSettings::default(),
+                    event_id,
+                )
+                .await?;
+            assert_eq!(inserted, expected);
+        }
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_insert_a_long_profile(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::new_v4();
+        let cmds = (0..500)
+            .map(|_| NewCommand {
+                id: Uuid::new_v4(),
+                session_data: SessionData {
+                    id: session_uuid,
+                    step_position: 0,
+                },
+                ..Default::default()
+            })
+            .collect();
+        let inserted = repo
+            .insert(
+                cmds,
+                Hardware::new(String::from("heating_id"), HardwareType::Heating),
+                Hardware::new(String::from("cooling_id"), HardwareType::Cooling),
+                SessionSettings::default(),
+                Uuid::new_v4(),
+            )
+            .await?;
+        assert_eq!(inserted, 500);
+        let planned = repo
+            .fetch_commands_by_order(
+                session_uuid,
+                &CommandStatus::Planned,
+                QueryOptions::new(None, Sorting::ASC),
+            )
+            .await?;
+        assert_eq!(planned.len(), 500);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_store_each_hardware_id_in_its_own_column(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool.clone());
+        let session_uuid = Uuid::new_v4();
+        repo.insert(
+            vec![NewCommand {
+                session_data: SessionData {
+                    id: session_uuid,
+                    step_position: 0,
+                },
+                ..Default::default()
+            }],
+            Hardware::new(String::from("heating_id"), HardwareType::Heating),
+            Hardware::new(String::from("cooling_id"), HardwareType::Cooling),
+            SessionSettings::default(),
+            Uuid::new_v4(),
+        )
+        .await?;
+        let (cooling_id, heating_id): (String, String) =
+            query_as(r#"SELECT cooling_id, heating_id FROM "session" WHERE uuid = $1"#)
+                .bind(session_uuid)
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!((cooling_id.as_str(), heating_id.as_str()), ("cooling_id", "heating_id"));
+        assert_eq!(
+            repo.fetch_hardware_id(session_uuid, &HardwareType::Cooling).await?,
+            "cooling_id"
+        );
+        assert_eq!(
+            repo.fetch_hardware_id(session_uuid, &HardwareType::Heating).await?,
+            "heating_id"
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_record_the_timeline_of_a_session(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let command_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        let now = {
+            let dt = OffsetDateTime::now_utc();
+            dt.replace_nanosecond(dt.nanosecond() / 1000 * 1000).unwrap()
+        };
+        let running_uuid = Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap();
+        repo.update_status(running_uuid, &CommandStatus::Executed { at: now })
+            .await?;
+        repo.record_hardware_switch(
+            session_uuid,
+            "heating_id",
+            HardwareSwitch {
+                state: HardwareState::Off,
+                at: now,
+            },
+            true,
+            false,
+        )
+        .await?;
+        repo.update_status(command_uuid, &CommandStatus::Running { since: now })
+            .await?;
+        repo.record_hardware_switch(
+            session_uuid,
+            "heating_id",
+            HardwareSwitch {
+                state: HardwareState::On,
+                at: now,
+            },
+            false,
+            true,
+        )
+        .await?;
+        repo.update_value_reached_at(command_uuid, now).await?;
+        repo.update_status(command_uuid, &CommandStatus::Executed { at: now })
+            .await?;
+        let timeline = repo.fetch_session_timeline(session_uuid).await?;
+        assert_eq!(
+            timeline,
+            vec![
+                CommandEvent {
+                    command_id: Some(running_uuid),
+                    occurred_at: now,
+                    kind: CommandEventKind::StatusChanged(CommandStatus::Executed { at: now }),
+                },
+                CommandEvent {
+                    command_id: None,
+                    occurred_at: now,
+                    kind: CommandEventKind::HardwareSwitched {
+                        hardware_id: "heating_id".into(),
+                        state: HardwareState::Off,
+                        is_forced: true,
+                    },
+                },
+                CommandEvent {
+                    command_id: Some(command_uuid),
+                    occurred_at: now,
+                    kind: CommandEventKind::StatusChanged(CommandStatus::Running { since: now }),
+                },
+                CommandEvent {
+                    command_id: Some(command_uuid),
+                    occurred_at: now,
+                    kind: CommandEventKind::HardwareSwitched {
+                        hardware_id: "heating_id".into(),
+                        state: HardwareState::On,
+                        is_forced: false,
+                    },
+                },
+                CommandEvent {
+                    command_id: Some(command_uuid),
+                    occurred_at: now,
+                    kind: CommandEventKind::ValueReached,
+                },
+                CommandEvent {
+                    command_id: Some(command_uuid),
+                    occurred_at: now,
+                    kind: CommandEventKind::StatusChanged(CommandStatus::Executed { at: now }),
+                },
+            ]
+        );
+        // only the toggle is kept in the protection history
+        let history = repo
+            .fetch_hardware_switch_history("heating_id", now - Duration::hours(1))
+            .await?;
+        assert_eq!(history.toggles_last_hour, 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_record_the_planned_status_of_inserted_commands(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::new_v4();
+        let cmds = (0..3)
+            .map(|_| NewCommand {
+                id: Uuid::new_v4(),
+                session_data: SessionData {
+                    id: session_uuid,
+                    step_position: 0,
+                },
+                ..Default::default()
+            })
+            .collect();
+        repo.insert(
+            cmds,
+            Hardware::new(String::from("heating_id"), HardwareType::Heating),
+            Hardware::new(String::from("cooling_id"), HardwareType::Cooling),
+            SessionSettings::default(),
+            Uuid::new_v4(),
+        )
+        .await?;
+        repo.cancel_commands(session_uuid, OffsetDateTime::now_utc(), Uuid::new_v4())
+            .await?;
+        let statuses: Vec<String> = repo
+            .fetch_session_timeline(session_uuid)
+            .await?
+            .iter()
+            .map(|event| match &event.kind {
+                CommandEventKind::StatusChanged(status) => status.name().to_string(),
+                other => other.name().to_string(),
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec!["Planned", "Planned", "Planned", "Cancelled", "Cancelled", "Cancelled"]
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_roll_back_the_session_if_a_command_insert_fails(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool.clone());
+        let (session_uuid, command_uuid, event_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let command = || NewCommand {
+            id: command_uuid,
+            session_data: SessionData {
+                id: session_uuid,
+                step_position: 0,
+            },
+            ..Default::default()
+        };
+        // the duplicated command uuid makes the command insert fail once the session has been inserted
+        let result = repo
+            .insert(
+                vec![command(), command()],
+                Hardware::new(String::from("heating_id"), HardwareType::Heating),
+                Hardware::new(String::from("cooling_id"), HardwareType::Cooling),
+                SessionSettings::default(),
+                event_id,
+            )
+            .await;
+        assert!(result.is_err());
+        let sessions: i64 = query_scalar(r#"SELECT COUNT(*) FROM "session""#)
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(sessions, 0);
+        // the event isn't recorded either, its redelivery schedules the session
+        let inserted = repo
+            .insert(
+                vec![command()],
+                Hardware::new(String::from("heating_id"), HardwareType::Heating),
+                Hardware::new(String::from("cooling_id"), HardwareType::Cooling),
+                SessionSettings::default(),
+                event_id,
+            )
+            .await?;
+        assert_eq!(inserted, 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_fetch_commands(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let result = repo
+            .fetch_commands_by_order(
+                session_uuid,
+                &CommandStatus::Planned,
+                QueryOptions::new(None, Sorting::DESC),
+            )
+            .await;
+        let result = result.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.first().unwrap().status, CommandStatus::Planned);
+        assert_eq!(
+            result.first().unwrap().uuid,
+            Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap()
+        );
+        Ok(())
+    }
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_update_command_status(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let date = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let status = CommandStatus::Running { since: date };
+        let cmd_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+
+        let result = repo.update_status(cmd_uuid, &status).await.unwrap().unwrap();
+        assert_eq!(result.session_id, 1); //this field is not updatable
+        assert_eq!(result.fermentation_step_id, 1); //this field is not updatable
+        assert_eq!(result.temperature_data.value, 20.4); //this field is not updatable
+        assert_eq!(result.temperature_data.value_holding_duration, Duration::hours(1)); //this field is not updatable
+        assert_eq!(result.temperature_data.value_reached_at, None);
+        assert_eq!(result.status, status);
+        assert_eq!(
+            result.uuid,
+            Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap()
+        );
+        Ok(())
+    }
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_update_command_value_reached_at(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let date = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let cmd_uuid = Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap();
+
+        let result = repo.update_value_reached_at(cmd_uuid, date).await.unwrap().unwrap();
+        assert_eq!(result.session_id, 1); //this field is not updatable
+        assert_eq!(result.fermentation_step_id, 1); //this field is not updatable
+        assert_eq!(result.temperature_data.value, 20.4); //this field is not updatable
+        assert_eq!(result.temperature_data.value_holding_duration, Duration::hours(1)); //this field is not updatable
+        assert_eq!(result.temperature_data.value_reached_at, Some(date));
+        assert!(matches!(result.status, CommandStatus::Running { .. }));
+        assert_eq!(
+            result.uuid,
+            Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap()
+        );
+        Ok(())
+    }
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_fetch_active_hardware_type(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+
+        let result = repo.fetch_active_hardware_type(&session_uuid).await.unwrap().unwrap();
+        assert_eq!(result, HardwareType::Cooling);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_update_active_hardware_type(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        repo.update_active_hardware_type(session_uuid, None).await?;
+        let result = repo.fetch_active_hardware_type(&session_uuid).await.unwrap();
+        assert_eq!(result, None);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_fetch_session_settings(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let result = repo.fetch_session_settings(session_uuid).await.unwrap();
+        assert_eq!(result.hysteresis, 0.5);
+        assert_eq!(result.control_mode, ControlMode::Pid);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_update_pid_state(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        assert_eq!(repo.fetch_pid_state(session_uuid).await?, PidState::default());
+        let date = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let state = PidState {
+            integral: 12.5,
+            last_error: Some(-0.5),
+            window_start: Some(date),
+            updated_at: Some(date),
+        };
+        repo.update_pid_state(session_uuid, state.clone()).await?;
+        assert_eq!(repo.fetch_pid_state(session_uuid).await?, state);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_record_the_reading_window(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        assert_eq!(repo.fetch_reading_window(session_uuid).await?, ReadingWindow::default());
+        let window = ReadingWindow {
+            readings: vec![18.0, 18.5, 18.25],
+            filtered: Some(18.25),
+            outliers: 1,
+        };
+        repo.record_reading(
+            session_uuid,
+            OffsetDateTime::now_utc(),
+            None,
+            Some(window.clone()),
+            None,
+        )
+        .await?;
+        assert_eq!(repo.fetch_reading_window(session_uuid).await?, window);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_pause_and_reset_command_value_reached_at(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let date = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let cmd_uuid = Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap();
+        repo.update_value_reached_at(cmd_uuid, date).await?;
+
+        let result = repo.pause_value_reached_at(cmd_uuid, date).await?.unwrap();
+        assert_eq!(result.temperature_data.value_reached_at, Some(date));
+        assert_eq!(result.temperature_data.hold_paused_at, Some(date));
+
+        let result = repo.update_value_reached_at(cmd_uuid, date).await?.unwrap();
+        assert_eq!(result.temperature_data.hold_paused_at, None);
+
+        repo.pause_value_reached_at(cmd_uuid, date).await?;
+        let reset_at = date - Duration::minutes(5);
+        let result = repo.reset_value_reached_at(cmd_uuid, reset_at).await?.unwrap();
+        assert_eq!(result.temperature_data.value_reached_at, None);
+        assert_eq!(result.temperature_data.hold_paused_at, None);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let reset = repo
+            .fetch_session_timeline(session_uuid)
+            .await?
+            .into_iter()
+            .find(|event| event.kind == CommandEventKind::ValueReset)
+            .unwrap();
+        assert_eq!(reset.occurred_at, reset_at);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_update_command_value_held(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let date = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let cmd_uuid = Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap();
+
+        let result = repo
+            .update_value_held(cmd_uuid, Duration::minutes(90), Some(date))
+            .await?
+            .unwrap();
+        assert_eq!(result.temperature_data.value_held_duration, Duration::minutes(90));
+        assert_eq!(result.temperature_data.value_held_at, Some(date));
+
+        let result = repo
+            .update_value_held(cmd_uuid, Duration::minutes(90), None)
+            .await?
+            .unwrap();
+        assert_eq!(result.temperature_data.value_held_duration, Duration::minutes(90));
+        assert_eq!(result.temperature_data.value_held_at, None);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_only_update_the_holding_timer_of_a_running_command(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let date = OffsetDateTime::now_utc();
+        let planned_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        let executed_uuid = Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap();
+        repo.update_status(executed_uuid, &CommandStatus::Executed { at: date })
+            .await?;
+        let timeline = repo.fetch_session_timeline(session_uuid).await?;
+        for cmd_uuid in [planned_uuid, executed_uuid] {
+            assert!(repo.update_value_reached_at(cmd_uuid, date).await?.is_none());
+            assert!(repo.pause_value_reached_at(cmd_uuid, date).await?.is_none());
+            assert!(repo.reset_value_reached_at(cmd_uuid, date).await?.is_none());
+            assert!(
+                repo.update_value_held(cmd_uuid, Duration::minutes(90), Some(date))
+                    .await?
+                    .is_none()
+            );
+        }
+        assert_eq!(repo.fetch_session_timeline(session_uuid).await?, timeline);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_record_and_fetch_hardware_switch_history(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let date = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        assert_eq!(
+            repo.fetch_hardware_switch_history("cooling_id", date - Duration::HOUR)
+                .await?,
+            HardwareSwitchHistory::default()
+        );
+
+        let switches = [
+            (HardwareState::On, date - Duration::hours(2)),
+            (HardwareState::Off, date - Duration::minutes(30)),
+            (HardwareState::On, date - Duration::minutes(10)),
+        ];
+        for (state, at) in switches {
+            repo.record_hardware_switch(Uuid::new_v4(), "cooling_id", HardwareSwitch { state, at }, false, true)
+                .await?;
+        }
+        repo.record_hardware_switch(
+            Uuid::new_v4(),
+            "heating_id",
+            HardwareSwitch {
+                state: HardwareState::Off,
+                at: date,
+            },
+            false,
+            true,
+        )
+        .await?;
+
+        let result = repo
+            .fetch_hardware_switch_history("cooling_id", date - Duration::HOUR)
+            .await?;
+        assert_eq!(
+            result.last_switch,
+            Some(HardwareSwitch {
+                state: HardwareState::On,
+                at: date - Duration::minutes(10)
+            })
+        );
+        assert_eq!(result.toggles_last_hour, 2);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn should_insert_and_fetch_session_temperature_limits(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let cmds = vec![NewCommand::default()];
+        let settings = SessionSettings {
+            temperature_limits: TemperatureLimits {
+                min: Some(-2.5),
+                max: Some(30.0),
+            },
+            ..Default::default()
+        };
+        repo.insert(
+            cmds,
+            Hardware::new(String::from("heating_id"), HardwareType::Heating),
+            Hardware::new(String::from("cooling_id"), HardwareType::Cooling),
+            settings.clone(),
+            Uuid::new_v4(),
+        )
+        .await?;
+        let result = repo.fetch_session_settings(Uuid::default()).await?;
+        assert_eq!(result, settings);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_lock_out_hardware_and_reset_lockout(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool.clone());
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let faulted_at = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        repo.lock_out_hardware(session_uuid, &HardwareType::Heating, faulted_at)
+            .await?;
+        let result = repo.fetch_session_settings(session_uuid).await?;
+        assert_eq!(
+            result.lockout,
+            HardwareLockout {
+                heating: true,
+                cooling: false
+            }
+        );
+        let is_faulted: bool = sqlx::query_scalar("SELECT faulted_at IS NOT NULL FROM session WHERE uuid = $1")
+            .bind(session_uuid)
+            .fetch_one(&pool)
+            .await?;
+        assert!(is_faulted);
+        assert_eq!(result.status, SessionStatus::Faulted { since: faulted_at });
+
+        let event_id = Uuid::new_v4();
+        assert!(repo.reset_lockout(session_uuid, event_id).await?);
+        assert!(!repo.is_new_event(event_id).await?);
+        // a redelivered reset doesn't lift a lockout set since
+        repo.lock_out_hardware(session_uuid, &HardwareType::Heating, faulted_at)
+            .await?;
+        assert!(!repo.reset_lockout(session_uuid, event_id).await?);
+        assert!(repo.fetch_session_settings(session_uuid).await?.lockout.heating);
+        assert!(repo.reset_lockout(session_uuid, Uuid::new_v4()).await?);
+        let result = repo.fetch_session_settings(session_uuid).await?;
+        assert_eq!(result.lockout, HardwareLockout::default());
+        assert_eq!(result.status, SessionStatus::Active);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_complete_session(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        assert_eq!(
+            repo.fetch_session_settings(session_uuid).await?.status,
+            SessionStatus::Active
+        );
+        let completed_at = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let status = SessionStatus::Completed { at: completed_at };
+        repo.update_session_status(session_uuid, &status).await?;
+        assert_eq!(repo.fetch_session_settings(session_uuid).await?.status, status);
+        assert!(
+            repo.update_session_status(session_uuid, &SessionStatus::Active)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_only_update_last_reading_at_with_newer_readings(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let measured_at = OffsetDateTime::now_utc();
+        assert!(repo.record_reading(session_uuid, measured_at, None, None, None).await?);
+        assert!(!repo.record_reading(session_uuid, measured_at, None, None, None).await?);
+        assert!(
+            !repo
+                .record_reading(session_uuid, measured_at - Duration::minutes(1), None, None, None)
+                .await?
+        );
+        assert!(
+            repo.record_reading(session_uuid, measured_at + Duration::minutes(1), None, None, None)
+                .await?
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_not_update_last_reading_at_with_an_already_processed_event(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let (event_id, measured_at) = (Uuid::new_v4(), OffsetDateTime::now_utc());
+        assert!(
+            repo.record_reading(session_uuid, measured_at, None, None, Some(event_id))
+                .await?
+        );
+        assert!(
+            !repo
+                .record_reading(
+                    session_uuid,
+                    measured_at + Duration::minutes(1),
+                    None,
+                    None,
+                    Some(event_id)
+                )
+                .await?
+        );
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::str::FromStr;
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_only_mark_a_reading_as_processed_once_its_last_reading_at_is_updated(
+        pool: PgPool,
+    ) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let (event_id, measured_at) = (Uuid::new_v4(), OffsetDateTime::now_utc());
+        assert!(repo.is_new_reading(session_uuid, measured_at, Some(event_id)).await?);
+        // the handling of the reading failed, its redelivery is still new
+        assert!(repo.is_new_reading(session_uuid, measured_at, Some(event_id)).await?);
+        assert!(
+            repo.record_reading(session_uuid, measured_at, None, None, Some(event_id))
+                .await?
+        );
+        assert!(
+            !repo
+                .is_new_reading(session_uuid, measured_at + Duration::minutes(1), Some(event_id))
+                .await?
+        );
+        assert!(
+            !repo
+                .is_new_reading(session_uuid, measured_at, Some(Uuid::new_v4()))
+                .await?
+        );
+        assert!(
+            repo.is_new_reading(session_uuid, measured_at + Duration::minutes(1), None)
+                .await?
+        );
+        assert!(!repo.is_new_reading(Uuid::new_v4(), measured_at, None).await?);
+        Ok(())
+    }
 
-    use super::{CommandRepository, NewCommandRecord};
-    use internal::{
-        domain::{
-            command::{CommandStatus, NewCommand},
-            message::{Hardware, HardwareType},
-            sorting::{QueryOptions, Sorting},
-        },
-        port::command::CommandDrivenPort,
-    };
-    use sqlx::{PgPool, types::BigDecimal};
-    use time::{Duration, OffsetDateTime};
-    use uuid::Uuid;
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_purge_processed_events(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let (event_id, measured_at) = (Uuid::new_v4(), OffsetDateTime::now_utc());
+        repo.record_reading(session_uuid, measured_at, None, None, Some(event_id))
+            .await?;
+        assert_eq!(
+            repo.purge_processed_events(OffsetDateTime::now_utc() - Duration::hours(1))
+                .await?,
+            0
+        );
+        assert_eq!(
+            repo.purge_processed_events(OffsetDateTime::now_utc() + Duration::seconds(1))
+                .await?,
+            1
+        );
+        // the event is forgotten, only the last reading date guards the session now
+        assert!(
+            repo.record_reading(
+                session_uuid,
+                measured_at + Duration::minutes(1),
+                None,
+                None,
+                Some(event_id)
+            )
+            .await?
+        );
+        Ok(())
+    }
 
-    #[test]
-    fn should_create_new_command_record() {
-        let session_id = 1;
-        let record = NewCommandRecord::from_command(&NewCommand::default(), 1).unwrap();
-        assert_eq!(record.value_holding_duration, 0);
-        assert_eq!(record.value, BigDecimal::from_str("0.0").unwrap());
-        assert_eq!(record.fermentation_step_id, 0);
-        assert_eq!(record.command_id, Uuid::default());
-        assert_eq!(record.session_id, session_id);
-        assert_eq!(record.status, "Planned");
-        assert_eq!(record.status_date, None);
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_fetch_temperature_readings_of_a_session_in_a_time_range(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let start = OffsetDateTime::parse("2025-07-26T10:00:00Z", &Rfc3339)?;
+        let mut conn = repo.pool.acquire().await?;
+        for (minutes, temperature) in [(20, 18.75), (0, 18.5), (10, 18.6), (30, 19.0)] {
+            repo.insert_temperature_reading(
+                &mut conn,
+                TemperatureReading {
+                    session_id: session_uuid,
+                    measured_at: start + Duration::minutes(minutes),
+                    temperature,
+                    source: Some("ispindel-1".to_string()),
+                },
+            )
+            .await?;
+        }
+        // a reading of an unknown session is dropped
+        repo.insert_temperature_reading(
+            &mut conn,
+            TemperatureReading {
+                session_id: Uuid::new_v4(),
+                measured_at: start,
+                temperature: 20.0,
+                source: None,
+            },
+        )
+        .await?;
+        let readings = repo
+            .fetch_temperature_readings(session_uuid, start, start + Duration::minutes(30))
+            .await?;
+        assert_eq!(
+            readings.iter().map(|r| r.temperature).collect::<Vec<_>>(),
+            vec![18.5, 18.6, 18.75]
+        );
+        assert_eq!(readings[0].measured_at, start);
+        assert_eq!(readings[0].source, Some("ispindel-1".to_string()));
+        assert!(
+            repo.fetch_temperature_readings(Uuid::new_v4(), start, start + Duration::hours(1))
+                .await?
+                .is_empty()
+        );
+        Ok(())
     }
-    #[sqlx::test(migrations = "./migrations")]
-    async fn should_insert_commands(pool: PgPool) -> anyhow::Result<()> {
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_record_nothing_of_a_reading_older_than_the_last_one(pool: PgPool) -> anyhow::Result<()> {
         let repo = CommandRepository::new(pool);
-        let cmds = vec![NewCommand::default()];
-        let heating_h = Hardware::new(String::from("heating_id"), HardwareType::Heating);
-        let cooling_h = Hardware::new(String::from("cooling_id"), HardwareType::Cooling);
-        let result = repo.insert(cmds, heating_h, cooling_h).await;
-        assert_eq!(result.unwrap(), 1);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let measured_at = OffsetDateTime::now_utc();
+        let reading = |measured_at, temperature| TemperatureReading {
+            session_id: session_uuid,
+            measured_at,
+            temperature,
+            source: None,
+        };
+        assert!(
+            repo.record_reading(session_uuid, measured_at, Some(reading(measured_at, 18.5)), None, None)
+                .await?
+        );
+        let (event_id, older) = (Uuid::new_v4(), measured_at - Duration::minutes(1));
+        let window = ReadingWindow {
+            readings: vec![18.0],
+            filtered: Some(18.0),
+            outliers: 0,
+        };
+        assert!(
+            !repo
+                .record_reading(
+                    session_uuid,
+                    older,
+                    Some(reading(older, 18.0)),
+                    Some(window),
+                    Some(event_id)
+                )
+                .await?
+        );
+        let readings = repo
+            .fetch_temperature_readings(session_uuid, older, measured_at + Duration::seconds(1))
+            .await?;
+        assert_eq!(readings.iter().map(|r| r.temperature).collect::<Vec<_>>(), vec![18.5]);
+        assert_eq!(repo.fetch_reading_window(session_uuid).await?, ReadingWindow::default());
+        // the event was not marked as processed either
+        assert_eq!(
+            repo.purge_processed_events(OffsetDateTime::now_utc() + Duration::seconds(1))
+                .await?,
+            0
+        );
         Ok(())
     }
 
     #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
-    async fn should_fetch_commands(pool: PgPool) -> anyhow::Result<()> {
+    async fn should_purge_temperature_readings_older_than_the_retention(pool: PgPool) -> anyhow::Result<()> {
         let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let now = OffsetDateTime::now_utc();
+        for measured_at in [now - Duration::days(40), now - Duration::days(31), now] {
+            let reading = TemperatureReading {
+                session_id: session_uuid,
+                measured_at,
+                temperature: 18.5,
+                source: None,
+            };
+            repo.record_reading(session_uuid, measured_at, Some(reading), None, None)
+                .await?;
+        }
+        assert_eq!(repo.purge_temperature_readings(now - Duration::days(30)).await?, 2);
+        let readings = repo
+            .fetch_temperature_readings(session_uuid, now - Duration::days(50), now + Duration::seconds(1))
+            .await?;
+        assert_eq!(readings.len(), 1);
+        Ok(())
+    }
 
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_fetch_first_planned_command(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
         let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
         let result = repo
             .fetch_commands_by_order(
                 session_uuid,
                 &CommandStatus::Planned,
-                QueryOptions::new(None, Sorting::DESC),
+                QueryOptions::new(Some(1), Sorting::ASC),
             )
-            .await;
-        let result = result.unwrap();
+            .await?;
         assert_eq!(result.len(), 1);
-        assert_eq!(result.first().unwrap().status, CommandStatus::Planned);
         assert_eq!(
             result.first().unwrap().uuid,
             Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap()
         );
         Ok(())
     }
+
     #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
-    async fn should_update_command_status(pool: PgPool) -> anyhow::Result<()> {
+    async fn should_fetch_running_commands_with_their_session(pool: PgPool) -> anyhow::Result<()> {
         let repo = CommandRepository::new(pool);
-        let date = {
+        let result = repo.fetch_running_commands().await?;
+        assert_eq!(result.len(), 1);
+        let (session_uuid, cmd) = result.first().unwrap();
+        assert_eq!(
+            *session_uuid,
+            Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap()
+        );
+        assert_eq!(
+            cmd.uuid,
+            Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap()
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_fetch_last_reading_date_of_sessions_with_a_running_command(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let running = repo.fetch_running_commands().await?;
+        // no reading yet, the session is silent since its command started
+        assert_eq!(
+            repo.fetch_last_reading_dates().await?,
+            vec![(session_uuid, running[0].1.status.date().unwrap())]
+        );
+        let measured_at = {
+            let dt = OffsetDateTime::now_utc() + Duration::minutes(1);
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        repo.record_reading(session_uuid, measured_at, None, None, None).await?;
+        assert_eq!(
+            repo.fetch_last_reading_dates().await?,
+            vec![(session_uuid, measured_at)]
+        );
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_cancel_planned_and_running_commands(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let cancelled_at = {
             let dt = OffsetDateTime::now_utc();
             let microseconds = dt.nanosecond() / 1000;
             dt.replace_nanosecond(microseconds * 1000).unwrap()
         };
-        let status = CommandStatus::Running { since: date };
-        let cmd_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        let event_id = Uuid::new_v4();
+        assert_eq!(repo.cancel_commands(session_uuid, cancelled_at, event_id).await?, 2);
+        let status = CommandStatus::Cancelled { at: cancelled_at };
+        let result = repo
+            .fetch_commands_by_order(session_uuid, &status, QueryOptions::new(None, Sorting::ASC))
+            .await?;
+        assert_eq!(result.len(), 2);
+        assert!(result.iter().all(|cmd| cmd.status == status));
+        assert!(!repo.is_new_event(event_id).await?);
+        assert_eq!(
+            repo.cancel_commands(session_uuid, cancelled_at, Uuid::new_v4()).await?,
+            0
+        );
+        Ok(())
+    }
 
-        let result = repo.update_status(cmd_uuid, &status).await.unwrap();
-        assert_eq!(result.session_id, 1); //this field is not updatable
-        assert_eq!(result.fermentation_step_id, 1); //this field is not updatable
-        assert_eq!(result.temperature_data.value, 20.4); //this field is not updatable
-        assert_eq!(result.temperature_data.value_holding_duration, Duration::hours(1)); //this field is not updatable
-        assert_eq!(result.temperature_data.value_reached_at, None);
-        assert_eq!(result.status, status);
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_replace_planned_commands_and_keep_running_one(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let cmds = (0..2)
+            .map(|position| NewCommand {
+                id: Uuid::new_v4(),
+                session_data: SessionData {
+                    id: session_uuid,
+                    step_position: position,
+                },
+                ..Default::default()
+            })
+            .collect();
         assert_eq!(
-            result.uuid,
-            Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap()
+            repo.replace_commands(session_uuid, cmds, false, OffsetDateTime::now_utc(), Uuid::new_v4())
+                .await?,
+            2
+        );
+        let planned = repo
+            .fetch_commands_by_order(
+                session_uuid,
+                &CommandStatus::Planned,
+                QueryOptions::new(None, Sorting::ASC),
+            )
+            .await?;
+        assert_eq!(planned.len(), 2);
+        assert!(
+            planned
+                .iter()
+                .all(|cmd| cmd.uuid != Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap())
         );
+        // the new steps follow the step of the running command
+        assert_eq!(planned[0].fermentation_step_id, 2);
+        assert_eq!(planned[1].fermentation_step_id, 3);
+        let running = repo
+            .fetch_commands_by_order(
+                session_uuid,
+                &CommandStatus::Running {
+                    since: OffsetDateTime::now_utc(),
+                },
+                QueryOptions::new(None, Sorting::ASC),
+            )
+            .await?;
+        assert_eq!(running.len(), 1);
         Ok(())
     }
+
     #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
-    async fn should_update_command_value_reached_at(pool: PgPool) -> anyhow::Result<()> {
+    async fn should_replace_running_command_when_asked_to(pool: PgPool) -> anyhow::Result<()> {
         let repo = CommandRepository::new(pool);
-        let date = {
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let replaced_at = {
             let dt = OffsetDateTime::now_utc();
             let microseconds = dt.nanosecond() / 1000;
             dt.replace_nanosecond(microseconds * 1000).unwrap()
         };
-        let cmd_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        let cmds = vec![NewCommand {
+            session_data: SessionData {
+                id: session_uuid,
+                step_position: 0,
+            },
+            ..Default::default()
+        }];
+        assert_eq!(
+            repo.replace_commands(session_uuid, cmds, true, replaced_at, Uuid::new_v4())
+                .await?,
+            1
+        );
+        let status = CommandStatus::Cancelled { at: replaced_at };
+        let cancelled = repo
+            .fetch_commands_by_order(session_uuid, &status, QueryOptions::new(None, Sorting::ASC))
+            .await?;
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(
+            cancelled[0].uuid,
+            Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap()
+        );
+        assert!(repo.fetch_running_commands().await?.is_empty());
+        Ok(())
+    }
 
-        let result = repo.update_value_reached_at(cmd_uuid, date).await.unwrap();
-        assert_eq!(result.session_id, 1); //this field is not updatable
-        assert_eq!(result.fermentation_step_id, 1); //this field is not updatable
-        assert_eq!(result.temperature_data.value, 20.4); //this field is not updatable
-        assert_eq!(result.temperature_data.value_holding_duration, Duration::hours(1)); //this field is not updatable
-        assert_eq!(result.temperature_data.value_reached_at, Some(date));
-        assert_eq!(result.status, CommandStatus::Planned);
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_not_replace_commands_of_unknown_session(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let result = repo
+            .replace_commands(
+                Uuid::new_v4(),
+                vec![NewCommand::default()],
+                false,
+                OffsetDateTime::now_utc(),
+                Uuid::new_v4(),
+            )
+            .await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_not_replace_commands_again_with_a_redelivered_event(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let event_id = Uuid::new_v4();
+        let cmds = || {
+            vec![NewCommand {
+                id: Uuid::new_v4(),
+                session_data: SessionData {
+                    id: session_uuid,
+                    step_position: 0,
+                },
+                ..Default::default()
+            }]
+        };
         assert_eq!(
-            result.uuid,
-            Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap()
+            repo.replace_commands(session_uuid, cmds(), true, OffsetDateTime::now_utc(), event_id)
+                .await?,
+            1
+        );
+        assert_eq!(
+            repo.replace_commands(session_uuid, cmds(), true, OffsetDateTime::now_utc(), event_id)
+                .await?,
+            0
         );
+        let planned = repo
+            .fetch_commands_by_order(
+                session_uuid,
+                &CommandStatus::Planned,
+                QueryOptions::new(None, Sorting::ASC),
+            )
+            .await?;
+        assert_eq!(planned.len(), 1);
         Ok(())
     }
+
     #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
-    async fn should_fetch_active_hardware_type(pool: PgPool) -> anyhow::Result<()> {
+    async fn should_execute_running_command_and_skip_planned_ones_once(pool: PgPool) -> anyhow::Result<()> {
         let repo = CommandRepository::new(pool);
         let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let planned_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        let running_uuid = Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap();
+        let (event_id, now) = (Uuid::new_v4(), OffsetDateTime::now_utc());
+        assert_eq!(
+            repo.skip_commands(Some(running_uuid), vec![planned_uuid], now, event_id)
+                .await?,
+            Some(1)
+        );
+        assert!(repo.fetch_running_commands().await?.is_empty());
+        assert!(
+            repo.fetch_commands_by_order(
+                session_uuid,
+                &CommandStatus::Planned,
+                QueryOptions::new(None, Sorting::ASC),
+            )
+            .await?
+            .is_empty()
+        );
+        // the redelivered event is ignored, as is a running command executed meanwhile
+        assert_eq!(repo.skip_commands(None, vec![], now, event_id).await?, None);
+        assert_eq!(
+            repo.skip_commands(Some(running_uuid), vec![], now, Uuid::new_v4())
+                .await?,
+            None
+        );
+        Ok(())
+    }
 
-        let result = repo.fetch_active_hardware_type(&session_uuid).await.unwrap().unwrap();
-        assert_eq!(result, HardwareType::Cooling);
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_update_command_status_to_skipped(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let skipped_at = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let status = CommandStatus::Skipped { at: skipped_at };
+        let cmd_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        assert_eq!(repo.update_status(cmd_uuid, &status).await?.unwrap().status, status);
         Ok(())
     }
 
     #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
-    async fn should_update_active_hardware_type(pool: PgPool) -> anyhow::Result<()> {
+    async fn should_only_update_command_status_from_the_expected_one(pool: PgPool) -> anyhow::Result<()> {
         let repo = CommandRepository::new(pool);
+        let now = OffsetDateTime::now_utc();
+        let planned_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        let running_uuid = Uuid::parse_str("b51a3a1b-9e4c-4e6d-ab96-3f0972afbd9c").unwrap();
+        assert!(
+            repo.update_status(planned_uuid, &CommandStatus::Executed { at: now })
+                .await?
+                .is_none()
+        );
+        assert!(
+            repo.update_status(running_uuid, &CommandStatus::Running { since: now })
+                .await?
+                .is_none()
+        );
+        assert!(
+            repo.update_status(running_uuid, &CommandStatus::Executed { at: now })
+                .await?
+                .is_some()
+        );
+        assert!(
+            repo.update_status(running_uuid, &CommandStatus::Skipped { at: now })
+                .await?
+                .is_none()
+        );
+        assert!(
+            repo.update_status(Uuid::new_v4(), &CommandStatus::Executed { at: now })
+                .await
+                .is_err()
+        );
+        // a transition that didn't happen isn't part of the timeline
         let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
-        repo.update_active_hardware_type(session_uuid, None).await?;
-        let result = repo.fetch_active_hardware_type(&session_uuid).await.unwrap();
-        assert_eq!(result, None);
+        assert_eq!(repo.fetch_session_timeline(session_uuid).await?.len(), 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_start_a_command_once_when_instances_race(pool: PgPool) -> anyhow::Result<()> {
+        let (repo, other_repo) = (CommandRepository::new(pool.clone()), CommandRepository::new(pool));
+        let status = CommandStatus::Running {
+            since: OffsetDateTime::now_utc(),
+        };
+        let cmd_uuid = Uuid::parse_str("23bc0b04-05a4-4d28-a82d-2cc640fb3042").unwrap();
+        let (started, other_started) = tokio::join!(
+            repo.update_status(cmd_uuid, &status),
+            other_repo.update_status(cmd_uuid, &status)
+        );
+        assert_eq!([started?, other_started?].iter().filter(|cmd| cmd.is_some()).count(), 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_pause_and_resume_session(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let paused_at = {
+            let dt = OffsetDateTime::now_utc();
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let pause_event_id = Uuid::new_v4();
+        assert!(
+            repo.update_session_paused_at(session_uuid, Some(paused_at), pause_event_id)
+                .await?
+        );
+        assert_eq!(
+            repo.fetch_session_settings(session_uuid).await?.paused_at,
+            Some(paused_at)
+        );
+        assert!(
+            repo.update_session_paused_at(session_uuid, None, Uuid::new_v4())
+                .await?
+        );
+        assert_eq!(repo.fetch_session_settings(session_uuid).await?.paused_at, None);
+        // the pause redelivered after the resume doesn't pause the session again
+        assert!(
+            !repo
+                .update_session_paused_at(session_uuid, Some(paused_at), pause_event_id)
+                .await?
+        );
+        assert_eq!(repo.fetch_session_settings(session_uuid).await?.paused_at, None);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations", fixtures("session", "command"))]
+    async fn should_update_and_clear_hardware_override(pool: PgPool) -> anyhow::Result<()> {
+        let repo = CommandRepository::new(pool);
+        let session_uuid = Uuid::parse_str("871b888e-2185-4bb8-b8b0-f87d4be4c133").unwrap();
+        let until = {
+            let dt = OffsetDateTime::now_utc() + Duration::minutes(30);
+            let microseconds = dt.nanosecond() / 1000;
+            dt.replace_nanosecond(microseconds * 1000).unwrap()
+        };
+        let hardware_override = HardwareOverride {
+            hardware_type: HardwareType::Cooling,
+            state: HardwareState::On,
+            until,
+        };
+        let event_id = Uuid::new_v4();
+        assert!(
+            repo.update_hardware_override(session_uuid, Some(hardware_override.clone()), Some(event_id))
+                .await?
+        );
+        assert_eq!(
+            repo.fetch_session_settings(session_uuid).await?.hardware_override,
+            Some(hardware_override.clone())
+        );
+        assert!(repo.update_hardware_override(session_uuid, None, None).await?);
+        assert_eq!(repo.fetch_session_settings(session_uuid).await?.hardware_override, None);
+        assert!(
+            !repo
+                .update_hardware_override(session_uuid, Some(hardware_override), Some(event_id))
+                .await?
+        );
+        assert_eq!(repo.fetch_session_settings(session_uuid).await?.hardware_override, None);
         Ok(())
     }
 }
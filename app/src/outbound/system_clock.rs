@@ -0,0 +1,11 @@
+use internal::port::clock::ClockPort;
+use time::OffsetDateTime;
+
+#[derive(Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl ClockPort for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
@@ -0,0 +1,34 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::message::HardwareType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub session_id: Uuid,
+    pub raised_at: OffsetDateTime,
+    pub kind: AlertKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlertKind {
+    // no tracking message has been received since, the hardware has been put in a safe state
+    SensorLost {
+        last_tracked_at: OffsetDateTime,
+    },
+    // the hardware responsible for crossing the limit is locked out until the session is reset
+    TemperatureLimitCrossed {
+        temperature: f32,
+        limit: f32,
+        hardware_type: HardwareType,
+    },
+}
+
+impl AlertKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertKind::SensorLost { .. } => "SensorLost",
+            AlertKind::TemperatureLimitCrossed { .. } => "TemperatureLimitCrossed",
+        }
+    }
+}
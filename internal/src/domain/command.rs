@@ -4,6 +4,8 @@ use time::Duration;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use super::controller::HoldMode;
+
 #[derive(Default, Debug)]
 pub struct NewCommand {
     pub id: Uuid,
@@ -24,11 +26,32 @@ pub struct Command {
     pub temperature_data: CommandTemperatureData,
 }
 
+impl Command {
+    // date at which the holding duration is over, provided the temperature stays in the band until then
+    pub fn holding_deadline(&self, hold_mode: &HoldMode) -> Option<OffsetDateTime> {
+        let data = &self.temperature_data;
+        match hold_mode {
+            HoldMode::Accumulate => data
+                .value_held_at
+                .map(|held_at| held_at + data.value_holding_duration - data.value_held_duration),
+            HoldMode::Reset | HoldMode::Pause if data.hold_paused_at.is_none() => {
+                data.value_reached_at.map(|d| d + data.value_holding_duration)
+            }
+            HoldMode::Reset | HoldMode::Pause => None,
+        }
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct CommandTemperatureData {
     pub value: f32,
     pub value_reached_at: Option<OffsetDateTime>,
     pub value_holding_duration: Duration,
+    // set while the holding timer is paused because the temperature left the band
+    pub hold_paused_at: Option<OffsetDateTime>,
+    // time accumulated within the band, along with the last in band reading it was accumulated up to
+    pub value_held_duration: Duration,
+    pub value_held_at: Option<OffsetDateTime>,
 }
 
 #[derive(Debug, PartialEq, Default, Clone)]
@@ -42,6 +65,14 @@ pub enum CommandStatus {
     Executed {
         at: OffsetDateTime,
     }, // when the target_temp is reached and optional duration passed
+    // the session has been cancelled before the command could be executed
+    Cancelled {
+        at: OffsetDateTime,
+    },
+    // the profile has been manually advanced past the command before it could be executed
+    Skipped {
+        at: OffsetDateTime,
+    },
 }
 
 impl CommandStatus {
@@ -50,6 +81,8 @@ impl CommandStatus {
             CommandStatus::Planned => "Planned",
             CommandStatus::Running { .. } => "Running",
             CommandStatus::Executed { .. } => "Executed",
+            CommandStatus::Cancelled { .. } => "Cancelled",
+            CommandStatus::Skipped { .. } => "Skipped",
         }
     }
     pub fn date(&self) -> Option<OffsetDateTime> {
@@ -57,6 +90,8 @@ impl CommandStatus {
             CommandStatus::Planned => None,
             CommandStatus::Running { since } => Some(*since),
             CommandStatus::Executed { at } => Some(*at),
+            CommandStatus::Cancelled { at } => Some(*at),
+            CommandStatus::Skipped { at } => Some(*at),
         }
     }
 }
@@ -65,3 +100,71 @@ pub struct SessionData {
     pub id: Uuid,
     pub step_position: u8,
 }
+
+#[cfg(test)]
+mod test {
+    use time::{Duration, OffsetDateTime};
+
+    use crate::domain::controller::HoldMode;
+
+    use super::{Command, CommandTemperatureData};
+
+    fn command(temperature_data: CommandTemperatureData) -> Command {
+        Command {
+            temperature_data: CommandTemperatureData {
+                value_holding_duration: Duration::hours(4),
+                ..temperature_data
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_compute_holding_deadline_from_value_reached_at() {
+        let reached_at = OffsetDateTime::now_utc();
+        let cmd = command(CommandTemperatureData {
+            value_reached_at: Some(reached_at),
+            ..Default::default()
+        });
+        assert_eq!(
+            cmd.holding_deadline(&HoldMode::Reset),
+            Some(reached_at + Duration::hours(4))
+        );
+        assert_eq!(
+            cmd.holding_deadline(&HoldMode::Pause),
+            Some(reached_at + Duration::hours(4))
+        );
+        assert_eq!(
+            command(CommandTemperatureData::default()).holding_deadline(&HoldMode::Reset),
+            None
+        );
+    }
+
+    #[test]
+    fn should_not_have_holding_deadline_while_paused() {
+        let cmd = command(CommandTemperatureData {
+            value_reached_at: Some(OffsetDateTime::now_utc()),
+            hold_paused_at: Some(OffsetDateTime::now_utc()),
+            ..Default::default()
+        });
+        assert_eq!(cmd.holding_deadline(&HoldMode::Pause), None);
+    }
+
+    #[test]
+    fn should_compute_holding_deadline_from_accumulated_duration() {
+        let held_at = OffsetDateTime::now_utc();
+        let cmd = command(CommandTemperatureData {
+            value_held_duration: Duration::hours(3),
+            value_held_at: Some(held_at),
+            ..Default::default()
+        });
+        assert_eq!(
+            cmd.holding_deadline(&HoldMode::Accumulate),
+            Some(held_at + Duration::hours(1))
+        );
+        assert_eq!(
+            command(CommandTemperatureData::default()).holding_deadline(&HoldMode::Accumulate),
+            None
+        );
+    }
+}
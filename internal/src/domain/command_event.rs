@@ -0,0 +1,40 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::{command::CommandStatus, protection::HardwareState};
+
+// One entry of the timeline of a session, recorded along with the change it describes
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandEvent {
+    // hardware switched while no command is running, by an override or the watchdog, isn't tied to any command
+    pub command_id: Option<Uuid>,
+    pub occurred_at: OffsetDateTime,
+    pub kind: CommandEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandEventKind {
+    StatusChanged(CommandStatus),
+    ValueReached,
+    // the temperature left the band and the holding timer starts over
+    ValueReset,
+    HoldPaused,
+    // forced switches bypass the hardware protection, they come from an override or a safety stop
+    HardwareSwitched {
+        hardware_id: String,
+        state: HardwareState,
+        is_forced: bool,
+    },
+}
+
+impl CommandEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandEventKind::StatusChanged(_) => "StatusChanged",
+            CommandEventKind::ValueReached => "ValueReached",
+            CommandEventKind::ValueReset => "ValueReset",
+            CommandEventKind::HoldPaused => "HoldPaused",
+            CommandEventKind::HardwareSwitched { .. } => "HardwareSwitched",
+        }
+    }
+}
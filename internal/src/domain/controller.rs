@@ -0,0 +1,37 @@
+use super::{
+    filter::FilterSettings, message::HardwareType, pid::PidSettings, protection::HardwareProtection,
+    session::TemperatureLimits,
+};
+
+// Controller wide settings, used when the session doesn't override them.
+#[derive(Debug, Clone, Default)]
+pub struct ControllerSettings {
+    pub hysteresis: f32,
+    pub pid: PidSettings,
+    pub hold_mode: HoldMode,
+    pub cooling_protection: HardwareProtection,
+    pub heating_protection: HardwareProtection,
+    pub temperature_limits: TemperatureLimits,
+    pub filter: FilterSettings,
+}
+
+impl ControllerSettings {
+    pub fn protection_of(&self, hardware_type: &HardwareType) -> &HardwareProtection {
+        match hardware_type {
+            HardwareType::Cooling => &self.cooling_protection,
+            HardwareType::Heating => &self.heating_protection,
+        }
+    }
+}
+
+// What happens to the holding timer of a running command when the temperature leaves the band
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum HoldMode {
+    // the holding duration starts over once the target is reached again
+    #[default]
+    Reset,
+    // the holding timer is frozen until the target is reached again, only in band time is counted
+    Pause,
+    // only the time between consecutive in band readings is counted
+    Accumulate,
+}
@@ -12,6 +12,10 @@ pub enum CommandSchedulerServiceError {
         "Rate for step {0} is misconfigured, the final temperature after its execution would not match the whished targeted temperature"
     )]
     InvalidRateConfiguration(String),
+    #[error("Invalid hysteresis: {0}, it must be between 0 and 99.9 with at most one decimal place")]
+    InvalidHysteresis(f32),
+    #[error("Invalid temperature limits: min {0} must be lower than max {1}")]
+    InvalidTemperatureLimits(f32, f32),
     #[error("Invalid step position: {0} {1}")]
     InvalidPosition(usize, &'static str),
     #[error("Something wrong happened {0}")]
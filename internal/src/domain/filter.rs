@@ -0,0 +1,168 @@
+// How the raw tracking temperatures of a session are smoothed before any control decision
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum FilterKind {
+    #[default]
+    None,
+    MovingAverage,
+    Median,
+    // alpha is the weight of the new reading, in ]0, 1]
+    Exponential {
+        alpha: f32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterSettings {
+    pub kind: FilterKind,
+    // number of raw readings kept per session
+    pub window: usize,
+    // °C, a reading further than this from the filtered temperature is discarded
+    pub max_jump: Option<f32>,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        FilterSettings {
+            kind: FilterKind::None,
+            window: 5,
+            max_jump: None,
+        }
+    }
+}
+
+// Rolling window of the accepted raw readings of a session, the most recent last
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReadingWindow {
+    pub readings: Vec<f32>,
+    pub filtered: Option<f32>,
+    // consecutive discarded readings, once they fill the window the temperature is considered to have really moved
+    pub outliers: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reading {
+    Accepted(f32),
+    Outlier,
+}
+
+impl FilterSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.kind != FilterKind::None || self.max_jump.is_some()
+    }
+}
+
+impl ReadingWindow {
+    pub fn push(&self, settings: &FilterSettings, temperature: f32) -> (ReadingWindow, Reading) {
+        let window = settings.window.max(1);
+        let is_outlier = match (settings.max_jump, self.filtered) {
+            (Some(max_jump), Some(filtered)) => (temperature - filtered).abs() > max_jump,
+            _ => false,
+        };
+        if is_outlier && (self.outliers as usize + 1) < window {
+            let next = ReadingWindow {
+                outliers: self.outliers + 1,
+                ..self.clone()
+            };
+            return (next, Reading::Outlier);
+        }
+        // a sustained jump starts a new window instead of being averaged with the old readings
+        let mut readings = if is_outlier { vec![] } else { self.readings.clone() };
+        readings.push(temperature);
+        if readings.len() > window {
+            readings.drain(..readings.len() - window);
+        }
+        let filtered = match &settings.kind {
+            FilterKind::None => temperature,
+            FilterKind::MovingAverage => readings.iter().sum::<f32>() / readings.len() as f32,
+            FilterKind::Median => {
+                let mut sorted = readings.clone();
+                sorted.sort_by(f32::total_cmp);
+                let middle = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+            FilterKind::Exponential { alpha } => match self.filtered {
+                Some(filtered) if !is_outlier => alpha * temperature + (1.0 - alpha) * filtered,
+                _ => temperature,
+            },
+        };
+        (
+            ReadingWindow {
+                readings,
+                filtered: Some(filtered),
+                outliers: 0,
+            },
+            Reading::Accepted(filtered),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn settings(kind: FilterKind) -> FilterSettings {
+        FilterSettings {
+            kind,
+            window: 3,
+            max_jump: Some(2.0),
+        }
+    }
+
+    fn push_all(settings: &FilterSettings, temperatures: &[f32]) -> (ReadingWindow, Reading) {
+        temperatures.iter().fold(
+            (ReadingWindow::default(), Reading::Outlier),
+            |(window, _), temperature| window.push(settings, *temperature),
+        )
+    }
+
+    #[test]
+    fn should_not_filter_by_default() {
+        assert!(!FilterSettings::default().is_enabled());
+        let (window, reading) = push_all(&FilterSettings::default(), &[18.0, 25.0]);
+        assert_eq!(reading, Reading::Accepted(25.0));
+        assert_eq!(window.readings, vec![18.0, 25.0]);
+    }
+
+    #[test]
+    fn should_average_the_window() {
+        let (window, reading) = push_all(&settings(FilterKind::MovingAverage), &[17.0, 18.0, 19.0, 20.0]);
+        assert_eq!(reading, Reading::Accepted(19.0));
+        assert_eq!(window.readings, vec![18.0, 19.0, 20.0]);
+    }
+
+    #[test]
+    fn should_take_the_median_of_the_window() {
+        let (_, reading) = push_all(&settings(FilterKind::Median), &[18.0, 19.5, 18.2]);
+        assert_eq!(reading, Reading::Accepted(18.2));
+        let (_, reading) = push_all(&settings(FilterKind::Median), &[18.0, 19.0]);
+        assert_eq!(reading, Reading::Accepted(18.5));
+    }
+
+    #[test]
+    fn should_smooth_exponentially() {
+        let (_, reading) = push_all(&settings(FilterKind::Exponential { alpha: 0.25 }), &[18.0, 20.0]);
+        assert_eq!(reading, Reading::Accepted(18.5));
+    }
+
+    #[test]
+    fn should_discard_isolated_outliers() {
+        let settings = settings(FilterKind::MovingAverage);
+        let (window, reading) = push_all(&settings, &[18.0, 18.0, 30.0]);
+        assert_eq!(reading, Reading::Outlier);
+        assert_eq!(window.readings, vec![18.0, 18.0]);
+        let (window, reading) = window.push(&settings, 19.5);
+        assert_eq!(reading, Reading::Accepted(18.5));
+        assert_eq!(window.outliers, 0);
+    }
+
+    #[test]
+    fn should_accept_a_sustained_jump_once_it_fills_the_window() {
+        let (window, reading) = push_all(&settings(FilterKind::MovingAverage), &[18.0, 24.0, 24.0, 24.0]);
+        assert_eq!(reading, Reading::Accepted(24.0));
+        assert_eq!(window.readings, vec![24.0]);
+    }
+}
@@ -1,6 +1,8 @@
 use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
+use super::{protection::HardwareState, session::ControlMode};
+
 #[derive(Debug)]
 pub struct Message {
     pub id: Uuid,
@@ -13,20 +15,103 @@ pub struct Message {
 pub enum MessageType {
     Schedule(ScheduleMessageData),
     Tracking(TrackingMessageData),
+    Reset(ResetMessageData),
+    Cancel(CancelMessageData),
+    Pause(PauseMessageData),
+    Resume(ResumeMessageData),
+    Reschedule(RescheduleMessageData),
+    Skip(SkipMessageData),
+    Override(OverrideMessageData),
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct TrackingMessageData {
     pub session_id: Uuid,
     pub temperature: f32,
+    // when the hydrometer took the reading, hold times are computed from it rather than from the processing time
+    pub measured_at: OffsetDateTime,
+    // id of the tracking event, missing when the last known reading of a session is evaluated again
+    pub event_id: Option<Uuid>,
+    pub source: Option<String>,
 }
 
-#[derive(Debug)]
+impl Default for TrackingMessageData {
+    fn default() -> Self {
+        TrackingMessageData {
+            session_id: Uuid::default(),
+            temperature: f32::default(),
+            measured_at: OffsetDateTime::now_utc(),
+            event_id: None,
+            source: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct ScheduleMessageData {
+    // id of the schedule event, recorded with the session so a redelivered event doesn't schedule it twice
+    pub event_id: Uuid,
     pub session_id: Uuid,
     pub hardwares: Vec<Hardware>,
     pub steps: Vec<FermentationStep>,
+    pub hysteresis: Option<f32>,
+    pub control_mode: Option<ControlMode>,
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+}
+
+// the manual events carry their id as well, it is recorded with the change they make so a redelivery is ignored
+#[derive(Debug, Default)]
+pub struct ResetMessageData {
+    pub event_id: Uuid,
+    pub session_id: Uuid,
+}
+
+#[derive(Debug, Default)]
+pub struct CancelMessageData {
+    pub event_id: Uuid,
+    pub session_id: Uuid,
+}
+
+#[derive(Debug, Default)]
+pub struct PauseMessageData {
+    pub event_id: Uuid,
+    pub session_id: Uuid,
 }
+
+#[derive(Debug, Default)]
+pub struct ResumeMessageData {
+    pub event_id: Uuid,
+    pub session_id: Uuid,
+}
+
+#[derive(Debug, Default)]
+pub struct RescheduleMessageData {
+    pub event_id: Uuid,
+    pub session_id: Uuid,
+    // the remaining profile, positions start again at 0
+    pub steps: Vec<FermentationStep>,
+    // when false the running command completes before the new profile starts
+    pub replace_running: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct SkipMessageData {
+    pub event_id: Uuid,
+    pub session_id: Uuid,
+    // fermentation_step_id of the step to jump to, the next planned command is started when missing
+    pub to_step: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct OverrideMessageData {
+    pub event_id: Uuid,
+    pub session_id: Uuid,
+    pub hardware_type: HardwareType,
+    pub state: HardwareState,
+    pub duration: Duration,
+}
+
 impl ScheduleMessageData {
     pub fn get_hardware_of_type(&self, hardware_type: &HardwareType) -> Option<&Hardware> {
         self.hardwares.iter().find(|h| &h.hardware_type == hardware_type)
@@ -1,4 +1,13 @@
+pub mod alert;
 pub mod command;
+pub mod command_event;
+pub mod controller;
 pub mod error;
+pub mod filter;
 pub mod message;
+pub mod pid;
+pub mod protection;
+pub mod session;
+pub mod session_event;
 pub mod sorting;
+pub mod temperature_reading;
@@ -0,0 +1,165 @@
+use time::{Duration, OffsetDateTime};
+
+use super::message::HardwareType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PidSettings {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    // duty cycle window, the output is turned into an on time within this window
+    pub window: Duration,
+}
+
+impl Default for PidSettings {
+    fn default() -> Self {
+        PidSettings {
+            kp: 0.5,
+            ki: 0.0001,
+            kd: 0.0,
+            window: Duration::minutes(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PidState {
+    pub integral: f32,
+    pub last_error: Option<f32>,
+    pub window_start: Option<OffsetDateTime>,
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+impl PidState {
+    // Returns the next state along with the output in [-1, 1], a positive output means heating is needed
+    pub fn next(&self, settings: &PidSettings, error: f32, now: OffsetDateTime) -> (PidState, f32) {
+        let dt = self
+            .updated_at
+            .map(|updated_at| (now - updated_at).as_seconds_f32().max(0.0))
+            .unwrap_or(0.0);
+        let mut integral = self.integral + error * dt;
+        if settings.ki != 0.0 {
+            // anti windup, the integral term alone can't exceed the output range
+            let bound = (1.0 / settings.ki).abs();
+            integral = integral.clamp(-bound, bound);
+        }
+        let derivative = match self.last_error {
+            Some(last_error) if dt > 0.0 => (error - last_error) / dt,
+            _ => 0.0,
+        };
+        let output = (settings.kp * error + settings.ki * integral + settings.kd * derivative).clamp(-1.0, 1.0);
+        let window_start = match self.window_start {
+            Some(start) if now < start + settings.window => start,
+            _ => now,
+        };
+        (
+            PidState {
+                integral,
+                last_error: Some(error),
+                window_start: Some(window_start),
+                updated_at: Some(now),
+            },
+            output,
+        )
+    }
+
+    // Hardware that must be on at `now`, the output is spread from the start of the current window
+    pub fn hardware_for(&self, settings: &PidSettings, output: f32, now: OffsetDateTime) -> Option<HardwareType> {
+        let window_start = self.window_start?;
+        let on_time = settings.window * output.abs();
+        if output == 0.0 || now - window_start >= on_time {
+            None
+        } else if output > 0.0 {
+            Some(HardwareType::Heating)
+        } else {
+            Some(HardwareType::Cooling)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::{Duration, OffsetDateTime};
+
+    use crate::domain::message::HardwareType;
+
+    use super::{PidSettings, PidState};
+
+    #[test]
+    fn should_saturate_output() {
+        let settings = PidSettings::default();
+        let (_, output) = PidState::default().next(&settings, 10.0, OffsetDateTime::now_utc());
+        assert_eq!(output, 1.0);
+        let (_, output) = PidState::default().next(&settings, -10.0, OffsetDateTime::now_utc());
+        assert_eq!(output, -1.0);
+    }
+
+    #[test]
+    fn should_accumulate_integral_over_time() {
+        let settings = PidSettings {
+            kp: 0.0,
+            ki: 0.001,
+            kd: 0.0,
+            window: Duration::minutes(10),
+        };
+        let now = OffsetDateTime::now_utc();
+        let (state, output) = PidState::default().next(&settings, 1.0, now);
+        assert_eq!(output, 0.0);
+        let (state, output) = state.next(&settings, 1.0, now + Duration::seconds(100));
+        assert_eq!(state.integral, 100.0);
+        assert!((output - 0.1).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn should_bound_integral() {
+        let settings = PidSettings {
+            kp: 0.0,
+            ki: 0.01,
+            kd: 0.0,
+            window: Duration::minutes(10),
+        };
+        let now = OffsetDateTime::now_utc();
+        let state = PidState {
+            updated_at: Some(now - Duration::hours(10)),
+            ..Default::default()
+        };
+        let (state, output) = state.next(&settings, 5.0, now);
+        assert_eq!(state.integral, 100.0);
+        assert_eq!(output, 1.0);
+    }
+
+    #[test]
+    fn should_start_a_new_window_once_elapsed() {
+        let settings = PidSettings::default();
+        let now = OffsetDateTime::now_utc();
+        let state = PidState {
+            window_start: Some(now - Duration::minutes(4)),
+            updated_at: Some(now - Duration::minutes(1)),
+            ..Default::default()
+        };
+        let (next, _) = state.next(&settings, 0.0, now);
+        assert_eq!(next.window_start, state.window_start);
+        let (next, _) = state.next(&settings, 0.0, now + Duration::minutes(6));
+        assert_eq!(next.window_start, Some(now + Duration::minutes(6)));
+    }
+
+    #[test]
+    fn should_turn_output_into_duty_cycle() {
+        let settings = PidSettings::default();
+        let now = OffsetDateTime::now_utc();
+        let state = PidState {
+            window_start: Some(now),
+            ..Default::default()
+        };
+        assert_eq!(
+            state.hardware_for(&settings, 0.5, now + Duration::minutes(4)),
+            Some(HardwareType::Heating)
+        );
+        assert_eq!(state.hardware_for(&settings, 0.5, now + Duration::minutes(5)), None);
+        assert_eq!(
+            state.hardware_for(&settings, -0.3, now + Duration::minutes(2)),
+            Some(HardwareType::Cooling)
+        );
+        assert_eq!(state.hardware_for(&settings, 0.0, now), None);
+    }
+}
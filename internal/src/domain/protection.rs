@@ -0,0 +1,154 @@
+use time::{Duration, OffsetDateTime};
+
+// Guards a hardware against short cycling, a compressor that is switched too often wears out quickly.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HardwareProtection {
+    // minimum time the hardware runs once started
+    pub min_run: Duration,
+    // minimum time the hardware rests once stopped
+    pub min_off: Duration,
+    pub max_toggles_per_hour: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HardwareState {
+    On,
+    Off,
+}
+
+impl HardwareState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HardwareState::On => "On",
+            HardwareState::Off => "Off",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HardwareSwitch {
+    pub state: HardwareState,
+    pub at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HardwareSwitchHistory {
+    pub last_switch: Option<HardwareSwitch>,
+    pub toggles_last_hour: u32,
+}
+
+impl HardwareSwitchHistory {
+    // a switch to the state the hardware is already in isn't a toggle
+    pub fn is_toggle(&self, state: &HardwareState) -> bool {
+        self.last_switch.as_ref().is_none_or(|last| &last.state != state)
+    }
+}
+
+impl HardwareProtection {
+    pub fn is_enabled(&self) -> bool {
+        self != &HardwareProtection::default()
+    }
+
+    pub fn allows(&self, history: &HardwareSwitchHistory, state: &HardwareState, now: OffsetDateTime) -> bool {
+        if !history.is_toggle(state) {
+            return true;
+        }
+        let elapsed = history.last_switch.as_ref().map(|last| now - last.at);
+        match state {
+            HardwareState::On => {
+                elapsed.is_none_or(|elapsed| elapsed >= self.min_off)
+                    && self
+                        .max_toggles_per_hour
+                        .is_none_or(|max| history.toggles_last_hour < max)
+            }
+            // stopping is never capped, only the minimum run time is enforced
+            HardwareState::Off => elapsed.is_none_or(|elapsed| elapsed >= self.min_run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn protection() -> HardwareProtection {
+        HardwareProtection {
+            min_run: Duration::minutes(5),
+            min_off: Duration::minutes(3),
+            max_toggles_per_hour: Some(4),
+        }
+    }
+
+    fn history(state: HardwareState, ago: Duration, toggles_last_hour: u32) -> HardwareSwitchHistory {
+        HardwareSwitchHistory {
+            last_switch: Some(HardwareSwitch {
+                state,
+                at: OffsetDateTime::now_utc() - ago,
+            }),
+            toggles_last_hour,
+        }
+    }
+
+    #[test]
+    fn should_not_be_enabled_by_default() {
+        assert!(!HardwareProtection::default().is_enabled());
+        assert!(protection().is_enabled());
+    }
+
+    #[test]
+    fn should_enforce_min_off_before_starting() {
+        let now = OffsetDateTime::now_utc();
+        assert!(!protection().allows(
+            &history(HardwareState::Off, Duration::minutes(1), 0),
+            &HardwareState::On,
+            now
+        ));
+        assert!(protection().allows(
+            &history(HardwareState::Off, Duration::minutes(4), 0),
+            &HardwareState::On,
+            now
+        ));
+    }
+
+    #[test]
+    fn should_enforce_min_run_before_stopping() {
+        let now = OffsetDateTime::now_utc();
+        assert!(!protection().allows(
+            &history(HardwareState::On, Duration::minutes(4), 0),
+            &HardwareState::Off,
+            now
+        ));
+        assert!(protection().allows(
+            &history(HardwareState::On, Duration::minutes(6), 0),
+            &HardwareState::Off,
+            now
+        ));
+    }
+
+    #[test]
+    fn should_cap_starts_per_hour() {
+        let now = OffsetDateTime::now_utc();
+        assert!(!protection().allows(
+            &history(HardwareState::Off, Duration::minutes(10), 4),
+            &HardwareState::On,
+            now
+        ));
+        assert!(protection().allows(
+            &history(HardwareState::On, Duration::minutes(10), 4),
+            &HardwareState::Off,
+            now
+        ));
+    }
+
+    #[test]
+    fn should_allow_switches_that_are_not_toggles() {
+        let now = OffsetDateTime::now_utc();
+        assert!(protection().allows(&history(HardwareState::On, Duration::ZERO, 10), &HardwareState::On, now));
+        assert!(protection().allows(
+            &history(HardwareState::Off, Duration::ZERO, 10),
+            &HardwareState::Off,
+            now
+        ));
+        assert!(protection().allows(&HardwareSwitchHistory::default(), &HardwareState::On, now));
+    }
+}
@@ -0,0 +1,171 @@
+use time::OffsetDateTime;
+
+use super::{message::HardwareType, protection::HardwareState};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SessionSettings {
+    // deadband (°C) around the command target temperature, hardware is only switched on outside of it.
+    pub hysteresis: f32,
+    pub control_mode: ControlMode,
+    pub temperature_limits: TemperatureLimits,
+    // set once a limit is crossed, until a reset event is received
+    pub lockout: HardwareLockout,
+    // set while the session is paused, readings are recorded but not acted on
+    pub paused_at: Option<OffsetDateTime>,
+    // set while an operator forces a hardware, the profile isn't acted on until it expires
+    pub hardware_override: Option<HardwareOverride>,
+    pub status: SessionStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SessionStatus {
+    #[default]
+    Active,
+    // the last command has been executed, both hardware are off
+    Completed {
+        at: OffsetDateTime,
+    },
+    Cancelled {
+        at: OffsetDateTime,
+    },
+    // a hardware is locked out since a temperature limit has been crossed, the other one keeps regulating
+    Faulted {
+        since: OffsetDateTime,
+    },
+}
+
+impl SessionStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionStatus::Active => "Active",
+            SessionStatus::Completed { .. } => "Completed",
+            SessionStatus::Cancelled { .. } => "Cancelled",
+            SessionStatus::Faulted { .. } => "Faulted",
+        }
+    }
+
+    // nothing is left to control once a session is over
+    pub fn is_finished(&self) -> bool {
+        matches!(self, SessionStatus::Completed { .. } | SessionStatus::Cancelled { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HardwareOverride {
+    pub hardware_type: HardwareType,
+    pub state: HardwareState,
+    pub until: OffsetDateTime,
+}
+
+impl HardwareOverride {
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        now < self.until
+    }
+}
+
+// Absolute temperatures (°C) the session must never go past, whatever its profile.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TemperatureLimits {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+impl TemperatureLimits {
+    // the crossed limit along with the hardware responsible for crossing it
+    pub fn crossed_by(&self, temperature: f32) -> Option<(f32, HardwareType)> {
+        match (self.min, self.max) {
+            (_, Some(max)) if temperature > max => Some((max, HardwareType::Heating)),
+            (Some(min), _) if temperature < min => Some((min, HardwareType::Cooling)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HardwareLockout {
+    pub heating: bool,
+    pub cooling: bool,
+}
+
+impl HardwareLockout {
+    pub fn is_locked_out(&self, hardware_type: &HardwareType) -> bool {
+        match hardware_type {
+            HardwareType::Heating => self.heating,
+            HardwareType::Cooling => self.cooling,
+        }
+    }
+
+    pub fn lock(&mut self, hardware_type: &HardwareType) {
+        match hardware_type {
+            HardwareType::Heating => self.heating = true,
+            HardwareType::Cooling => self.cooling = true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ControlMode {
+    // on/off control around the hysteresis band
+    #[default]
+    Hysteresis,
+    // time proportional PID, the PID output is turned into a duty cycle
+    Pid,
+}
+impl ControlMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlMode::Hysteresis => "Hysteresis",
+            ControlMode::Pid => "Pid",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::{Duration, OffsetDateTime};
+
+    use crate::domain::{message::HardwareType, protection::HardwareState};
+
+    use super::{HardwareLockout, HardwareOverride, SessionStatus, TemperatureLimits};
+
+    #[test]
+    fn should_blame_the_hardware_that_crossed_the_limit() {
+        let limits = TemperatureLimits {
+            min: Some(2.0),
+            max: Some(30.0),
+        };
+        assert_eq!(limits.crossed_by(30.5), Some((30.0, HardwareType::Heating)));
+        assert_eq!(limits.crossed_by(1.5), Some((2.0, HardwareType::Cooling)));
+        assert_eq!(limits.crossed_by(30.0), None);
+        assert_eq!(TemperatureLimits::default().crossed_by(99.0), None);
+    }
+
+    #[test]
+    fn should_lock_out_hardware() {
+        let mut lockout = HardwareLockout::default();
+        lockout.lock(&HardwareType::Heating);
+        assert!(lockout.is_locked_out(&HardwareType::Heating));
+        assert!(!lockout.is_locked_out(&HardwareType::Cooling));
+    }
+
+    #[test]
+    fn should_expire_hardware_override() {
+        let now = OffsetDateTime::now_utc();
+        let hardware_override = HardwareOverride {
+            hardware_type: HardwareType::Cooling,
+            state: HardwareState::On,
+            until: now + Duration::minutes(30),
+        };
+        assert!(hardware_override.is_active(now));
+        assert!(!hardware_override.is_active(now + Duration::minutes(30)));
+    }
+
+    #[test]
+    fn should_only_be_finished_once_completed_or_cancelled() {
+        let now = OffsetDateTime::now_utc();
+        assert!(SessionStatus::Completed { at: now }.is_finished());
+        assert!(SessionStatus::Cancelled { at: now }.is_finished());
+        assert!(!SessionStatus::Faulted { since: now }.is_finished());
+        assert!(!SessionStatus::Active.is_finished());
+    }
+}
@@ -0,0 +1,35 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+// Progress of a session profile, published back to the API
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionEvent {
+    pub session_id: Uuid,
+    // fermentation_step_id of the step, only missing when a session completes without any executed command
+    pub step_position: Option<i32>,
+    pub occurred_at: OffsetDateTime,
+    pub temperature: f32,
+    pub kind: SessionEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEventKind {
+    // the first command of the step is running
+    StepStarted,
+    // the target temperature of the running command of the step has been reached, its hold starts
+    StepTargetReached,
+    // the last command of the step has been executed
+    StepCompleted,
+    SessionCompleted,
+}
+
+impl SessionEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionEventKind::StepStarted => "StepStarted",
+            SessionEventKind::StepTargetReached => "StepTargetReached",
+            SessionEventKind::StepCompleted => "StepCompleted",
+            SessionEventKind::SessionCompleted => "SessionCompleted",
+        }
+    }
+}
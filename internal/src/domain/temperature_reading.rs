@@ -0,0 +1,12 @@
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+// A raw reading accepted by the filter of its session, kept to follow the trend of a fermentation
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemperatureReading {
+    pub session_id: Uuid,
+    pub measured_at: OffsetDateTime,
+    pub temperature: f32,
+    // device that took the reading, as announced by the tracking event
+    pub source: Option<String>,
+}
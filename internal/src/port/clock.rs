@@ -0,0 +1,47 @@
+use time::OffsetDateTime;
+
+pub trait ClockPort {
+    fn now(&self) -> OffsetDateTime;
+}
+
+#[cfg(test)]
+pub use fake::FakeClock;
+
+#[cfg(test)]
+mod fake {
+    use std::sync::{Arc, Mutex};
+
+    use time::{Duration, OffsetDateTime};
+
+    use super::ClockPort;
+
+    // Clones share the same time, a test keeps one to move the time of the service it built
+    #[derive(Clone)]
+    pub struct FakeClock {
+        now: Arc<Mutex<OffsetDateTime>>,
+    }
+
+    impl FakeClock {
+        pub fn at(now: OffsetDateTime) -> Self {
+            FakeClock {
+                now: Arc::new(Mutex::new(now)),
+            }
+        }
+
+        pub fn advance(&self, duration: Duration) {
+            *self.now.lock().unwrap() += duration;
+        }
+    }
+
+    impl Default for FakeClock {
+        fn default() -> Self {
+            FakeClock::at(OffsetDateTime::now_utc())
+        }
+    }
+
+    impl ClockPort for FakeClock {
+        fn now(&self) -> OffsetDateTime {
+            *self.now.lock().unwrap()
+        }
+    }
+}
@@ -1,20 +1,76 @@
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::domain::{
     command::{Command, CommandStatus, NewCommand},
+    command_event::CommandEvent,
     error::{CommandExecutorServiceError, CommandSchedulerServiceError},
-    message::{Hardware, HardwareType, ScheduleMessageData, TrackingMessageData},
+    filter::ReadingWindow,
+    message::{
+        Hardware, HardwareType, OverrideMessageData, RescheduleMessageData, ScheduleMessageData, TrackingMessageData,
+    },
+    pid::PidState,
+    protection::{HardwareSwitch, HardwareSwitchHistory},
+    session::{HardwareOverride, SessionSettings, SessionStatus},
     sorting::QueryOptions,
+    temperature_reading::TemperatureReading,
 };
 
 pub trait CommandSchedulerDriverPort {
     fn schedule(&self, data: ScheduleMessageData) -> impl Future<Output = Result<u64, CommandSchedulerServiceError>>;
+    // replaces the planned commands of an already scheduled session, returns the number of new commands
+    fn reschedule(
+        &self, data: RescheduleMessageData,
+    ) -> impl Future<Output = Result<u64, CommandSchedulerServiceError>>;
 }
 pub trait CommandExecutorDriverPort {
     fn process(
         &self, tracking_message_data: TrackingMessageData,
     ) -> impl Future<Output = Result<(), CommandExecutorServiceError>>;
+    // evaluates a reading again at the current time, it isn't checked against nor recorded as the last reading
+    fn reevaluate(
+        &self, tracking_message_data: TrackingMessageData,
+    ) -> impl Future<Output = Result<(), CommandExecutorServiceError>>;
+    // puts the hardware of a session in a safe state, returns false if the session has no running command or is paused
+    fn process_sensor_loss(
+        &self, session_id: Uuid, last_tracked_at: OffsetDateTime,
+    ) -> impl Future<Output = Result<bool, CommandExecutorServiceError>>;
+    // sessions whose running command holding deadline is over, along with the running sessions driven by the pid
+    fn fetch_due_sessions(&self) -> impl Future<Output = Result<Vec<Uuid>, CommandExecutorServiceError>>;
+    // sessions with a running command along with the date their last reading was processed
+    fn fetch_watched_sessions(
+        &self,
+    ) -> impl Future<Output = Result<Vec<(Uuid, OffsetDateTime)>, CommandExecutorServiceError>>;
+    // the manual events below are ignored once their event_id has been processed
+    // lifts the lockout set when a temperature limit has been crossed
+    fn reset_lockout(
+        &self, session_id: Uuid, event_id: Uuid,
+    ) -> impl Future<Output = Result<(), CommandExecutorServiceError>>;
+    // stops the session hardware and cancels its planned and running commands, returns the number of cancelled commands
+    fn cancel(
+        &self, session_id: Uuid, event_id: Uuid,
+    ) -> impl Future<Output = Result<u64, CommandExecutorServiceError>>;
+    // stops the session hardware and freezes the holding timer of its running command until it is resumed
+    fn pause(&self, session_id: Uuid, event_id: Uuid) -> impl Future<Output = Result<(), CommandExecutorServiceError>>;
+    fn resume(&self, session_id: Uuid, event_id: Uuid)
+    -> impl Future<Output = Result<(), CommandExecutorServiceError>>;
+    // marks the running command as executed and starts the next planned one, or the first one of to_step once the
+    // commands before it have been skipped, the last reading is needed to start it right away, returns the number of
+    // skipped commands
+    fn skip(
+        &self, session_id: Uuid, to_step: Option<i32>, last_reading: Option<TrackingMessageData>, event_id: Uuid,
+    ) -> impl Future<Output = Result<u64, CommandExecutorServiceError>>;
+    // forces a hardware of the session on or off for the given duration, whatever its profile
+    fn override_hardware(
+        &self, data: OverrideMessageData,
+    ) -> impl Future<Output = Result<(), CommandExecutorServiceError>>;
+    // forgets the events processed before the given date, a redelivery of one of them would be processed again
+    fn purge_processed_events(
+        &self, before: OffsetDateTime,
+    ) -> impl Future<Output = Result<u64, CommandExecutorServiceError>>;
+    fn purge_temperature_readings(
+        &self, before: OffsetDateTime,
+    ) -> impl Future<Output = Result<u64, CommandExecutorServiceError>>;
 }
 
 #[cfg_attr(test, mockall::automock)]
@@ -25,20 +81,110 @@ pub trait CommandDrivenPort {
     fn fetch_active_hardware_type(
         &self, session_uuid: &Uuid,
     ) -> impl Future<Output = anyhow::Result<Option<HardwareType>>> + Send;
+    fn fetch_session_settings(
+        &self, session_uuid: Uuid,
+    ) -> impl Future<Output = anyhow::Result<SessionSettings>> + Send;
+    fn fetch_pid_state(&self, session_uuid: Uuid) -> impl Future<Output = anyhow::Result<PidState>> + Send;
+    fn update_pid_state(&self, session_uuid: Uuid, state: PidState) -> impl Future<Output = anyhow::Result<()>> + Send;
+    // last switch of the hardware along with the number of toggles since the given date
+    fn fetch_hardware_switch_history(
+        &self, hardware_id: &str, since: OffsetDateTime,
+    ) -> impl Future<Output = anyhow::Result<HardwareSwitchHistory>> + Send;
+    // records a published switch in the session timeline, a toggle is also kept in the protection history
+    fn record_hardware_switch(
+        &self, session_uuid: Uuid, hardware_id: &str, switch: HardwareSwitch, is_forced: bool, is_toggle: bool,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+    // status, value reached and hardware changes of the session commands, in the order they happened
+    fn fetch_session_timeline(
+        &self, session_uuid: Uuid,
+    ) -> impl Future<Output = anyhow::Result<Vec<CommandEvent>>> + Send;
+    // moves the session to Completed or Cancelled, the Faulted status follows the hardware lockout
+    fn update_session_status(
+        &self, session_uuid: Uuid, status: &SessionStatus,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+    // locks out the hardware and marks the session as faulted
+    fn lock_out_hardware(
+        &self, session_uuid: Uuid, hardware_type: &HardwareType, faulted_at: OffsetDateTime,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+    // the manual events are marked as processed in the transaction of the change they make, the methods below return
+    // false, 0 or None when the event has already been processed, nothing is changed then
+    fn is_new_event(&self, event_id: Uuid) -> impl Future<Output = anyhow::Result<bool>> + Send;
+    fn reset_lockout(&self, session_uuid: Uuid, event_id: Uuid) -> impl Future<Output = anyhow::Result<bool>> + Send;
+    fn update_session_paused_at(
+        &self, session_uuid: Uuid, paused_at: Option<OffsetDateTime>, event_id: Uuid,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send;
+    // the event is missing when the profile takes the hardware back
+    fn update_hardware_override(
+        &self, session_uuid: Uuid, hardware_override: Option<HardwareOverride>, event_id: Option<Uuid>,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send;
+    // in a single transaction, deletes the planned commands of the session, cancels its running one if asked to,
+    // then appends the new commands after the remaining ones, returns the number of inserted commands
+    fn replace_commands(
+        &self, session_uuid: Uuid, commands: Vec<NewCommand>, replace_running: bool, replaced_at: OffsetDateTime,
+        event_id: Uuid,
+    ) -> impl Future<Output = anyhow::Result<u64>> + Send;
+    // moves the planned and running commands of the session to Cancelled
+    fn cancel_commands(
+        &self, session_uuid: Uuid, cancelled_at: OffsetDateTime, event_id: Uuid,
+    ) -> impl Future<Output = anyhow::Result<u64>> + Send;
+    // in a single transaction, executes the running command, if any, and skips the given planned ones, returns the
+    // number of skipped commands, None as well when the running command has already been executed by another instance
+    fn skip_commands(
+        &self, running: Option<Uuid>, skipped: Vec<Uuid>, skipped_at: OffsetDateTime, event_id: Uuid,
+    ) -> impl Future<Output = anyhow::Result<Option<u64>>> + Send;
+    fn fetch_reading_window(&self, session_uuid: Uuid) -> impl Future<Output = anyhow::Result<ReadingWindow>> + Send;
+    // returns false when a reading measured at or after measured_at, or the event itself, has already been processed
+    fn is_new_reading(
+        &self, session_uuid: Uuid, measured_at: OffsetDateTime, event_id: Option<Uuid>,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send;
+    // once the reading has been handled, stores it unless the filter discarded it, keeps the filter window and marks
+    // the reading and its event as processed, all in one transaction; returns false, recording nothing, when a newer
+    // reading or the event itself has been processed meanwhile
+    fn record_reading(
+        &self, session_uuid: Uuid, measured_at: OffsetDateTime, accepted: Option<TemperatureReading>,
+        window: Option<ReadingWindow>, event_id: Option<Uuid>,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send;
     fn update_active_hardware_type(
         &self, session_uuid: Uuid, active_hardware_type: Option<HardwareType>,
     ) -> impl Future<Output = anyhow::Result<()>> + Send;
+    // readings of the session measured in [from, to[, the oldest first
+    fn fetch_temperature_readings(
+        &self, session_uuid: Uuid, from: OffsetDateTime, to: OffsetDateTime,
+    ) -> impl Future<Output = anyhow::Result<Vec<TemperatureReading>>> + Send;
+    // forgets the readings measured before the given date, returns the number of purged readings
+    fn purge_temperature_readings(&self, before: OffsetDateTime) -> impl Future<Output = anyhow::Result<u64>> + Send;
+    // running commands of every session along with their session uuid
+    fn fetch_running_commands(&self) -> impl Future<Output = anyhow::Result<Vec<(Uuid, Command)>>> + Send;
+    // last reading date of the sessions with a running command, the command start when no reading was processed yet
+    fn fetch_last_reading_dates(&self) -> impl Future<Output = anyhow::Result<Vec<(Uuid, OffsetDateTime)>>> + Send;
     fn fetch_commands_by_order(
         &self, session_id: Uuid, status: &CommandStatus, options: QueryOptions,
     ) -> impl Future<Output = Result<Vec<Command>, anyhow::Error>> + Send;
 
+    // returns 0 when the event has already been processed, nothing is inserted then
     fn insert(
-        &self, commands: Vec<NewCommand>, heating_h: Hardware, cooling_h: Hardware,
+        &self, commands: Vec<NewCommand>, heating_h: Hardware, cooling_h: Hardware, settings: SessionSettings,
+        event_id: Uuid,
     ) -> impl Future<Output = anyhow::Result<u64>> + Send;
+    // forgets the events processed before the given date, returns the number of purged events
+    fn purge_processed_events(&self, before: OffsetDateTime) -> impl Future<Output = anyhow::Result<u64>> + Send;
 
-    fn update_status(&self, uuid: Uuid, status: &CommandStatus)
-    -> impl Future<Output = anyhow::Result<Command>> + Send;
+    // a Planned command can start, a Running one be executed and both be cancelled or skipped,
+    // returns None when the command already left these statuses, e.g. another instance started it first
+    fn update_status(
+        &self, uuid: Uuid, status: &CommandStatus,
+    ) -> impl Future<Output = anyhow::Result<Option<Command>>> + Send;
+    // the holding timer of a Running command only, returns None once the command left that status
     fn update_value_reached_at(
         &self, uuid: Uuid, value_reached_at: OffsetDateTime,
-    ) -> impl Future<Output = anyhow::Result<Command>> + Send;
+    ) -> impl Future<Output = anyhow::Result<Option<Command>>> + Send;
+    fn reset_value_reached_at(
+        &self, uuid: Uuid, reset_at: OffsetDateTime,
+    ) -> impl Future<Output = anyhow::Result<Option<Command>>> + Send;
+    fn pause_value_reached_at(
+        &self, uuid: Uuid, paused_at: OffsetDateTime,
+    ) -> impl Future<Output = anyhow::Result<Option<Command>>> + Send;
+    fn update_value_held(
+        &self, uuid: Uuid, value_held_duration: Duration, value_held_at: Option<OffsetDateTime>,
+    ) -> impl Future<Output = anyhow::Result<Option<Command>>> + Send;
 }
@@ -1,2 +1,4 @@
+pub mod clock;
 pub mod command;
 pub mod publisher;
+pub mod session_event;
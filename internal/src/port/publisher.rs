@@ -1,6 +1,9 @@
+use crate::domain::alert::Alert;
+
 #[cfg_attr(test, mockall::automock)]
 pub trait PublisherDrivenPort {
     fn publish(&self, action: HardwareAction) -> impl Future<Output = anyhow::Result<()>>;
+    fn alert(&self, alert: Alert) -> impl Future<Output = anyhow::Result<()>>;
 }
 #[derive(PartialEq, Debug)]
 pub enum HardwareAction {
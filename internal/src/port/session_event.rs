@@ -0,0 +1,39 @@
+use crate::domain::session_event::SessionEvent;
+
+pub trait SessionEventDrivenPort {
+    fn publish(&self, event: SessionEvent) -> impl Future<Output = anyhow::Result<()>>;
+}
+
+#[cfg(test)]
+pub use fake::FakeSessionEventPublisher;
+
+#[cfg(test)]
+mod fake {
+    use std::{
+        future::ready,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::domain::session_event::SessionEvent;
+
+    use super::SessionEventDrivenPort;
+
+    // Clones share the same events, a test keeps one to look at what the service it built published
+    #[derive(Clone, Default)]
+    pub struct FakeSessionEventPublisher {
+        events: Arc<Mutex<Vec<SessionEvent>>>,
+    }
+
+    impl FakeSessionEventPublisher {
+        pub fn events(&self) -> Vec<SessionEvent> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    impl SessionEventDrivenPort for FakeSessionEventPublisher {
+        fn publish(&self, event: SessionEvent) -> impl Future<Output = anyhow::Result<()>> {
+            self.events.lock().unwrap().push(event);
+            ready(Ok(()))
+        }
+    }
+}
@@ -1,72 +1,604 @@
 use std::sync::Arc;
 
-use log::info;
+use log::{debug, info, warn};
 use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::{
     domain::{
+        alert::{Alert, AlertKind},
         command::{Command, CommandStatus},
+        controller::{ControllerSettings, HoldMode},
         error::CommandExecutorServiceError,
-        message::{HardwareType, TrackingMessageData},
+        filter::{Reading, ReadingWindow},
+        message::{HardwareType, OverrideMessageData, TrackingMessageData},
+        pid::PidState,
+        protection::{HardwareState, HardwareSwitch},
+        session::{ControlMode, HardwareOverride, SessionSettings, SessionStatus},
+        session_event::{SessionEvent, SessionEventKind},
         sorting::{QueryOptions, Sorting},
+        temperature_reading::TemperatureReading,
     },
     port::{
+        clock::ClockPort,
         command::{CommandDrivenPort, CommandExecutorDriverPort},
         publisher::{HardwareAction, PublisherDrivenPort},
+        session_event::SessionEventDrivenPort,
     },
 };
 
-pub struct CommandExecutorService<R: CommandDrivenPort, P: PublisherDrivenPort> {
+pub struct CommandExecutorService<R: CommandDrivenPort, P: PublisherDrivenPort, E: SessionEventDrivenPort, C: ClockPort>
+{
     repository: Arc<R>,
     publisher: P,
+    session_events: E,
+    clock: C,
+    settings: ControllerSettings,
 }
 
-impl<R: CommandDrivenPort, P: PublisherDrivenPort> CommandExecutorDriverPort for CommandExecutorService<R, P> {
-    async fn process(
-        &self, tracking_message_data: crate::domain::message::TrackingMessageData,
-    ) -> Result<(), CommandExecutorServiceError> {
+impl<R: CommandDrivenPort, P: PublisherDrivenPort, E: SessionEventDrivenPort, C: ClockPort> CommandExecutorDriverPort
+    for CommandExecutorService<R, P, E, C>
+{
+    async fn process(&self, tracking_message_data: TrackingMessageData) -> Result<(), CommandExecutorServiceError> {
+        let is_new = self
+            .repository
+            .is_new_reading(
+                tracking_message_data.session_id,
+                tracking_message_data.measured_at,
+                tracking_message_data.event_id,
+            )
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to check last reading: {e}")))?;
+        if !is_new {
+            warn!(
+                "Reading of session {} measured at {} isn't newer than the last processed one, ignoring it",
+                tracking_message_data.session_id, tracking_message_data.measured_at
+            );
+            return Ok(());
+        }
+        // a reading that failed to be handled is left to the redelivery of its event
+        self.handle_reading(tracking_message_data).await
+    }
+
+    async fn reevaluate(&self, tracking_message_data: TrackingMessageData) -> Result<(), CommandExecutorServiceError> {
+        let temperature = if self.settings.filter.is_enabled() {
+            self.fetch_reading_window(tracking_message_data.session_id)
+                .await?
+                .filtered
+                .unwrap_or(tracking_message_data.temperature)
+        } else {
+            tracking_message_data.temperature
+        };
+        self.evaluate(TrackingMessageData {
+            temperature,
+            measured_at: self.clock.now(),
+            ..tracking_message_data
+        })
+        .await
+    }
+
+    async fn process_sensor_loss(
+        &self, session_id: Uuid, last_tracked_at: OffsetDateTime,
+    ) -> Result<bool, CommandExecutorServiceError> {
         let status = CommandStatus::Running {
-            since: OffsetDateTime::now_utc(),
+            since: self.clock.now(),
         };
-        let running_cmds = self.fetch_command(tracking_message_data.session_id, &status).await?;
+        if self.fetch_command(session_id, &status).await?.is_empty() {
+            return Ok(false);
+        }
+        // the hardware has already been stopped when the session was paused
+        if self.fetch_session_settings(session_id).await?.paused_at.is_some() {
+            return Ok(false);
+        }
+        // the protection can't hold a hardware on while the temperature is unknown
+        self.shut_down(session_id).await?;
+        let alert = Alert {
+            session_id,
+            raised_at: self.clock.now(),
+            kind: AlertKind::SensorLost { last_tracked_at },
+        };
+        self.publisher
+            .alert(alert)
+            .await
+            .map(|_| true)
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to raise alert: {e}")))
+    }
 
-        if running_cmds.is_empty() {
-            self.execute_next_command(tracking_message_data).await?;
-        } else {
-            let cmd = running_cmds.first().cloned().unwrap();
-            let active_hardware = self
+    async fn fetch_due_sessions(&self) -> Result<Vec<Uuid>, CommandExecutorServiceError> {
+        let now = self.clock.now();
+        let running_cmds = self
+            .repository
+            .fetch_running_commands()
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(e.root_cause().to_string()))?;
+        let mut due_sessions = vec![];
+        for (session_id, cmd) in running_cmds {
+            if cmd
+                .holding_deadline(&self.settings.hold_mode)
+                .is_some_and(|deadline| deadline <= now)
+                || self.is_driven_by_pid(session_id).await?
+            {
+                due_sessions.push(session_id);
+            }
+        }
+        Ok(due_sessions)
+    }
+
+    async fn fetch_watched_sessions(&self) -> Result<Vec<(Uuid, OffsetDateTime)>, CommandExecutorServiceError> {
+        self.repository
+            .fetch_last_reading_dates()
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(e.root_cause().to_string()))
+    }
+
+    async fn reset_lockout(&self, session_id: Uuid, event_id: Uuid) -> Result<(), CommandExecutorServiceError> {
+        info!("Resetting the hardware lockout of session {session_id}");
+        let is_reset = self
+            .repository
+            .reset_lockout(session_id, event_id)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to reset lockout: {e}")))?;
+        if !is_reset {
+            Self::log_processed_event(session_id, event_id);
+        }
+        Ok(())
+    }
+
+    async fn cancel(&self, session_id: Uuid, event_id: Uuid) -> Result<u64, CommandExecutorServiceError> {
+        if self
+            .fetch_active_session_settings(session_id, "cancelled")
+            .await?
+            .is_none()
+            || !self.is_new_event(session_id, event_id).await?
+        {
+            return Ok(0);
+        }
+        info!("Cancelling session {session_id}");
+        // whatever the protection says, nothing must keep running once the session is cancelled
+        self.shut_down(session_id).await?;
+        let now = self.clock.now();
+        let cancelled = self
+            .repository
+            .cancel_commands(session_id, now, event_id)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to cancel commands: {e}")))?;
+        self.update_session_status(session_id, &SessionStatus::Cancelled { at: now })
+            .await?;
+        Ok(cancelled)
+    }
+
+    async fn pause(&self, session_id: Uuid, event_id: Uuid) -> Result<(), CommandExecutorServiceError> {
+        let Some(settings) = self.fetch_active_session_settings(session_id, "paused").await? else {
+            return Ok(());
+        };
+        if settings.paused_at.is_some() {
+            info!("Session {session_id} is already paused");
+            return Ok(());
+        }
+        // a pause redelivered after the resume would stop the session again
+        if !self.is_new_event(session_id, event_id).await? {
+            return Ok(());
+        }
+        info!("Pausing session {session_id}");
+        let now = self.clock.now();
+        self.shut_down(session_id).await?;
+        let status = CommandStatus::Running { since: now };
+        if let Some(cmd) = self.fetch_command(session_id, &status).await?.first() {
+            self.freeze_holding_timer(cmd, now).await?;
+        }
+        self.update_session_paused_at(session_id, Some(now), event_id).await
+    }
+
+    async fn resume(&self, session_id: Uuid, event_id: Uuid) -> Result<(), CommandExecutorServiceError> {
+        let Some(settings) = self.fetch_active_session_settings(session_id, "resumed").await? else {
+            return Ok(());
+        };
+        let Some(paused_at) = settings.paused_at else {
+            info!("Session {session_id} isn't paused, nothing to resume");
+            return Ok(());
+        };
+        if !self.is_new_event(session_id, event_id).await? {
+            return Ok(());
+        }
+        info!("Resuming session {session_id} paused at {paused_at}");
+        let now = self.clock.now();
+        let status = CommandStatus::Running { since: now };
+        // a holding timer paused before the session was is left to the hold mode, it resumes once the target is reached again
+        if let Some(cmd) = self.fetch_command(session_id, &status).await?.first()
+            && let (Some(value_reached_at), Some(hold_paused_at)) = (
+                cmd.temperature_data.value_reached_at,
+                cmd.temperature_data.hold_paused_at,
+            )
+            && hold_paused_at >= paused_at
+        {
+            self.repository
+                .update_value_reached_at(cmd.uuid, value_reached_at + (now - hold_paused_at))
+                .await
+                .map_err(|e| {
+                    CommandExecutorServiceError::TechnicalError(format!("Unable to shift value reached at {e:?}"))
+                })?;
+        }
+        self.update_session_paused_at(session_id, None, event_id).await
+    }
+
+    async fn skip(
+        &self, session_id: Uuid, to_step: Option<i32>, last_reading: Option<TrackingMessageData>, event_id: Uuid,
+    ) -> Result<u64, CommandExecutorServiceError> {
+        // a redelivered skip would advance the profile once more
+        if self
+            .fetch_active_session_settings(session_id, "skipped")
+            .await?
+            .is_none()
+            || !self.is_new_event(session_id, event_id).await?
+        {
+            return Ok(0);
+        }
+        let now = self.clock.now();
+        let planned_cmds = self
+            .repository
+            .fetch_commands_by_order(
+                session_id,
+                &CommandStatus::Planned,
+                QueryOptions::new(None, Sorting::ASC),
+            )
+            .await
+            .map_err(|err| CommandExecutorServiceError::TechnicalError(err.root_cause().to_string()))?;
+        let skipped_cmds = match to_step {
+            Some(step) => {
+                let first_of_step = planned_cmds
+                    .iter()
+                    .position(|cmd| cmd.fermentation_step_id == step)
+                    .ok_or(CommandExecutorServiceError::NotFound(format!(
+                        "planned step {step} of session {session_id}"
+                    )))?;
+                &planned_cmds[..first_of_step]
+            }
+            None => &[],
+        };
+        info!(
+            "Advancing the profile of session {session_id}, {} planned command(s) skipped",
+            skipped_cmds.len()
+        );
+        let status = CommandStatus::Running { since: now };
+        let running_cmd = self.fetch_command(session_id, &status).await?.first().cloned();
+        // the commands are claimed along with the event before any action is published
+        let skipped = self
+            .repository
+            .skip_commands(
+                running_cmd.as_ref().map(|cmd| cmd.uuid),
+                skipped_cmds.iter().map(|cmd| cmd.uuid).collect(),
+                now,
+                event_id,
+            )
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to skip commands: {e}")))?;
+        let Some(skipped) = skipped else {
+            info!("The profile of session {session_id} has already been advanced");
+            return Ok(0);
+        };
+        let active_hardware = match &running_cmd {
+            Some(_) => self.switch_off_all(session_id).await?,
+            None => self
                 .repository
-                .fetch_active_hardware_type(&tracking_message_data.session_id)
+                .fetch_active_hardware_type(&session_id)
                 .await
-                .map_err(|e| CommandExecutorServiceError::TechnicalError(e.to_string()))?
-                .ok_or(CommandExecutorServiceError::NotFound("active hardware id".to_string()))?;
+                .map_err(|e| CommandExecutorServiceError::TechnicalError(e.to_string()))?,
+        };
+        let settings = self.fetch_session_settings(session_id).await?;
+        match last_reading {
+            Some(last_reading) if settings.paused_at.is_none() => {
+                self.execute_next_command(
+                    TrackingMessageData {
+                        measured_at: now,
+                        ..last_reading
+                    },
+                    &settings,
+                    active_hardware,
+                    running_cmd.map(|cmd| cmd.fermentation_step_id),
+                )
+                .await?
+            }
+            _ => info!("The next command of session {session_id} will start with its next tracking message"),
+        }
+        Ok(skipped)
+    }
+
+    async fn override_hardware(&self, data: OverrideMessageData) -> Result<(), CommandExecutorServiceError> {
+        let session_id = data.session_id;
+        let settings = self.fetch_session_settings(session_id).await?;
+        // the readings that would clear the override aren't acted on, it would never expire
+        if settings.status.is_finished() {
+            warn!(
+                "Session {session_id} is {}, its hardware can't be overridden",
+                settings.status.name()
+            );
+            return Ok(());
+        }
+        if settings.paused_at.is_some() {
+            warn!("Session {session_id} is paused, its hardware can't be overridden until it is resumed");
+            return Ok(());
+        }
+        if data.state == HardwareState::On && settings.lockout.is_locked_out(&data.hardware_type) {
+            warn!(
+                "{} hardware of session {session_id} is locked out, it can't be forced on",
+                data.hardware_type.name()
+            );
+            return Ok(());
+        }
+        if !self.is_new_event(session_id, data.event_id).await? {
+            return Ok(());
+        }
+        let hardware_override = HardwareOverride {
+            until: self.clock.now() + data.duration,
+            hardware_type: data.hardware_type,
+            state: data.state,
+        };
+        info!("Overriding the hardware of session {session_id} with {hardware_override:?}");
+        // the operator is in charge, the switches bypass the protection
+        let active_hardware = match hardware_override.state {
+            HardwareState::On => {
+                let other = match hardware_override.hardware_type {
+                    HardwareType::Heating => HardwareType::Cooling,
+                    HardwareType::Cooling => HardwareType::Heating,
+                };
+                for (hardware_type, state) in [
+                    (&other, HardwareState::Off),
+                    (&hardware_override.hardware_type, HardwareState::On),
+                ] {
+                    let hardware_id = self.get_hardware_id(session_id, hardware_type).await?;
+                    self.publish_switch(session_id, hardware_type, hardware_id, state, true)
+                        .await?;
+                }
+                Some(hardware_override.hardware_type.clone())
+            }
+            HardwareState::Off => {
+                let hardware_id = self
+                    .get_hardware_id(session_id, &hardware_override.hardware_type)
+                    .await?;
+                self.publish_switch(
+                    session_id,
+                    &hardware_override.hardware_type,
+                    hardware_id,
+                    HardwareState::Off,
+                    true,
+                )
+                .await?;
+                self.repository
+                    .fetch_active_hardware_type(&session_id)
+                    .await
+                    .map_err(|e| CommandExecutorServiceError::TechnicalError(e.to_string()))?
+                    .filter(|active| *active != hardware_override.hardware_type)
+            }
+        };
+        self.repository
+            .update_active_hardware_type(session_id, active_hardware)
+            .await
+            .map_err(|e| {
+                CommandExecutorServiceError::TechnicalError(format!("Unable to update active hardware type: {e}"))
+            })?;
+        self.update_hardware_override(session_id, Some(hardware_override), Some(data.event_id))
+            .await
+    }
+
+    async fn purge_processed_events(&self, before: OffsetDateTime) -> Result<u64, CommandExecutorServiceError> {
+        self.repository
+            .purge_processed_events(before)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to purge processed events: {e}")))
+    }
 
-            let is_target_reached = match active_hardware {
-                HardwareType::Cooling => tracking_message_data.temperature <= cmd.temperature_data.value,
-                HardwareType::Heating => tracking_message_data.temperature >= cmd.temperature_data.value,
+    async fn purge_temperature_readings(&self, before: OffsetDateTime) -> Result<u64, CommandExecutorServiceError> {
+        self.repository
+            .purge_temperature_readings(before)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to purge readings: {e}")))
+    }
+}
+
+impl<R: CommandDrivenPort, P: PublisherDrivenPort, E: SessionEventDrivenPort, C: ClockPort>
+    CommandExecutorService<R, P, E, C>
+{
+    pub fn new(repository: Arc<R>, publisher: P, session_events: E, clock: C, settings: ControllerSettings) -> Self {
+        CommandExecutorService {
+            repository,
+            publisher,
+            session_events,
+            clock,
+            settings,
+        }
+    }
+    async fn evaluate(&self, tracking_message_data: TrackingMessageData) -> Result<(), CommandExecutorServiceError> {
+        let status = CommandStatus::Running {
+            since: self.clock.now(),
+        };
+        let mut settings = self.fetch_session_settings(tracking_message_data.session_id).await?;
+        if settings.status.is_finished() {
+            debug!(
+                "Session {} is {}, temperature {} is ignored",
+                tracking_message_data.session_id,
+                settings.status.name(),
+                tracking_message_data.temperature
+            );
+            return Ok(());
+        }
+        let mut active_hardware = self
+            .repository
+            .fetch_active_hardware_type(&tracking_message_data.session_id)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(e.to_string()))?;
+        // the limits are enforced whatever the operator forced and even while the session is paused
+        if let Some((limit, hardware_type)) = settings
+            .temperature_limits
+            .crossed_by(tracking_message_data.temperature)
+            && !settings.lockout.is_locked_out(&hardware_type)
+        {
+            self.cut_off(&tracking_message_data, limit, &hardware_type, &active_hardware)
+                .await?;
+            if active_hardware.as_ref() == Some(&hardware_type) {
+                active_hardware = None;
+            }
+            settings.lockout.lock(&hardware_type);
+            if settings
+                .hardware_override
+                .as_ref()
+                .is_some_and(|hardware_override| hardware_override.hardware_type == hardware_type)
+            {
+                info!(
+                    "Hardware override of session {} ends with the cut off of its {} hardware",
+                    tracking_message_data.session_id,
+                    hardware_type.name()
+                );
+                self.update_hardware_override(tracking_message_data.session_id, None, None)
+                    .await?;
+                settings.hardware_override = None;
+            }
+        }
+        if settings.paused_at.is_some() {
+            debug!(
+                "Session {} is paused, temperature {} isn't acted on",
+                tracking_message_data.session_id, tracking_message_data.temperature
+            );
+            return Ok(());
+        }
+        if let Some(hardware_override) = &settings.hardware_override {
+            if hardware_override.is_active(self.clock.now()) {
+                debug!(
+                    "Session {} hardware is overridden until {}, temperature {} isn't acted on",
+                    tracking_message_data.session_id, hardware_override.until, tracking_message_data.temperature
+                );
+                return Ok(());
+            }
+            info!(
+                "Hardware override of session {} is over, back to its profile",
+                tracking_message_data.session_id
+            );
+            self.update_hardware_override(tracking_message_data.session_id, None, None)
+                .await?;
+        }
+        let running_cmds = self.fetch_command(tracking_message_data.session_id, &status).await?;
+        if running_cmds.is_empty() {
+            self.execute_next_command(tracking_message_data, &settings, active_hardware, None)
+                .await?;
+        } else {
+            let mut cmd = running_cmds.first().cloned().unwrap();
+
+            let is_in_band =
+                (tracking_message_data.temperature - cmd.temperature_data.value).abs() <= settings.hysteresis;
+            if !is_in_band
+                && cmd.temperature_data.value_reached_at.is_some()
+                && !self
+                    .rearm_holding_timer(&mut cmd, tracking_message_data.measured_at)
+                    .await?
+            {
+                return Ok(());
+            }
+            let is_target_reached = match (&settings.control_mode, &active_hardware) {
+                (ControlMode::Hysteresis, Some(HardwareType::Cooling)) => {
+                    tracking_message_data.temperature <= cmd.temperature_data.value
+                }
+                (ControlMode::Hysteresis, Some(HardwareType::Heating)) => {
+                    tracking_message_data.temperature >= cmd.temperature_data.value
+                }
+                // nothing is running or the PID drives the hardware, the temperature is considered reached as long as it stays in the band
+                _ => is_in_band,
             };
-            if is_target_reached {
-                // TODO V2:  if for some reason the condition is not true anymore (electricity outage,
-                // so temp is to high again) => Reset value_reached_at to None (maybe not critical
-                // for now...)
-                let value_reached_at = self.mark_value_as_reached(&cmd).await?;
-                if Self::is_holding_duration_matched(cmd.temperature_data.value_holding_duration, value_reached_at) {
-                    self.stop_all(&cmd, tracking_message_data.session_id).await?;
-                    self.execute_next_command(tracking_message_data).await?;
-                } else {
-                    info!("target temperature has been reached for cmd {cmd:?} but holding duration isn't matched yet");
+            // a paused or accumulated hold only runs in the band, a reading past it would count the time spent out of it
+            let is_holding = is_target_reached && (is_in_band || matches!(self.settings.hold_mode, HoldMode::Reset));
+            if is_holding {
+                let Some(value_reached_at) = self
+                    .mark_value_as_reached(&cmd, tracking_message_data.measured_at)
+                    .await?
+                else {
+                    return Ok(());
+                };
+                if cmd.temperature_data.value_reached_at.is_none() {
+                    self.publish_session_event(
+                        &tracking_message_data,
+                        Some(cmd.fermentation_step_id),
+                        SessionEventKind::StepTargetReached,
+                    )
+                    .await?;
                 }
+                let Some(is_holding_done) = self
+                    .is_holding_done(&cmd, value_reached_at, tracking_message_data.measured_at)
+                    .await?
+                else {
+                    return Ok(());
+                };
+                if is_holding_done {
+                    let Some(active_hardware) = self
+                        .stop_all(
+                            &cmd,
+                            tracking_message_data.session_id,
+                            tracking_message_data.measured_at,
+                        )
+                        .await?
+                    else {
+                        return Ok(());
+                    };
+                    return self
+                        .execute_next_command(
+                            tracking_message_data,
+                            &settings,
+                            active_hardware,
+                            Some(cmd.fermentation_step_id),
+                        )
+                        .await;
+                }
+                info!("target temperature has been reached for cmd {cmd:?} but holding duration isn't matched yet");
             }
+            self.regulate(
+                cmd.temperature_data.value,
+                &tracking_message_data,
+                &settings,
+                active_hardware,
+                is_target_reached,
+            )
+            .await?;
         }
         Ok(())
     }
-}
 
-impl<R: CommandDrivenPort, P: PublisherDrivenPort> CommandExecutorService<R, P> {
-    pub fn new(repository: Arc<R>, publisher: P) -> Self {
-        CommandExecutorService { repository, publisher }
+    // filters and evaluates a new reading, then records it along with its processed marker
+    async fn handle_reading(
+        &self, tracking_message_data: TrackingMessageData,
+    ) -> Result<(), CommandExecutorServiceError> {
+        let (window, reading) = self.filter_reading(&tracking_message_data).await?;
+        let (session_id, measured_at, event_id) = (
+            tracking_message_data.session_id,
+            tracking_message_data.measured_at,
+            tracking_message_data.event_id,
+        );
+        let accepted = match reading {
+            Reading::Accepted(temperature) => {
+                let accepted = TemperatureReading {
+                    session_id,
+                    measured_at,
+                    temperature: tracking_message_data.temperature,
+                    source: tracking_message_data.source.clone(),
+                };
+                self.evaluate(TrackingMessageData {
+                    temperature,
+                    ..tracking_message_data
+                })
+                .await?;
+                Some(accepted)
+            }
+            Reading::Outlier => {
+                warn!(
+                    "Reading {} of session {} is too far from the filtered temperature, discarding it",
+                    tracking_message_data.temperature, session_id
+                );
+                None
+            }
+        };
+        self.repository
+            .record_reading(session_id, measured_at, accepted, window, event_id)
+            .await
+            .map(|_| ())
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to record the reading: {e}")))
     }
+
     async fn fetch_command(
         &self, session_id: Uuid, status: &CommandStatus,
     ) -> Result<Vec<Command>, CommandExecutorServiceError> {
@@ -77,6 +609,55 @@ impl<R: CommandDrivenPort, P: PublisherDrivenPort> CommandExecutorService<R, P>
             .map_err(|err| CommandExecutorServiceError::TechnicalError(err.root_cause().to_string()))
     }
 
+    async fn fetch_reading_window(&self, session_id: Uuid) -> Result<ReadingWindow, CommandExecutorServiceError> {
+        self.repository
+            .fetch_reading_window(session_id)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(e.root_cause().to_string()))
+    }
+
+    // the window is only kept once the reading is recorded, a failed handling leaves it untouched for the redelivery
+    async fn filter_reading(
+        &self, tracking_message_data: &TrackingMessageData,
+    ) -> Result<(Option<ReadingWindow>, Reading), CommandExecutorServiceError> {
+        if !self.settings.filter.is_enabled() {
+            return Ok((None, Reading::Accepted(tracking_message_data.temperature)));
+        }
+        let (window, reading) = self
+            .fetch_reading_window(tracking_message_data.session_id)
+            .await?
+            .push(&self.settings.filter, tracking_message_data.temperature);
+        Ok((Some(window), reading))
+    }
+
+    // the on time of the pid ends, and its next window starts, between the readings, the session is followed on each tick
+    async fn is_driven_by_pid(&self, session_id: Uuid) -> Result<bool, CommandExecutorServiceError> {
+        let settings = self.fetch_session_settings(session_id).await?;
+        Ok(settings.control_mode == ControlMode::Pid && settings.paused_at.is_none())
+    }
+
+    async fn fetch_session_settings(&self, session_id: Uuid) -> Result<SessionSettings, CommandExecutorServiceError> {
+        self.repository
+            .fetch_session_settings(session_id)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(e.root_cause().to_string()))
+    }
+
+    // returns None when the session is over, the manual events have nothing left to act on then
+    async fn fetch_active_session_settings(
+        &self, session_id: Uuid, action: &str,
+    ) -> Result<Option<SessionSettings>, CommandExecutorServiceError> {
+        let settings = self.fetch_session_settings(session_id).await?;
+        if settings.status.is_finished() {
+            info!(
+                "Session {session_id} is {}, it can't be {action}",
+                settings.status.name()
+            );
+            return Ok(None);
+        }
+        Ok(Some(settings))
+    }
+
     async fn get_hardware_id(
         &self, session_id: Uuid, hardware_type: &HardwareType,
     ) -> Result<String, CommandExecutorServiceError> {
@@ -86,12 +667,26 @@ impl<R: CommandDrivenPort, P: PublisherDrivenPort> CommandExecutorService<R, P>
             .map_err(|e| CommandExecutorServiceError::TechnicalError(e.root_cause().to_string()))
     }
 
-    async fn mark_value_as_reached(&self, cmd: &Command) -> Result<OffsetDateTime, CommandExecutorServiceError> {
-        if let Some(d) = cmd.temperature_data.value_reached_at {
-            Ok(d)
+    // returns None when the command isn't running anymore, e.g. another instance executed it first
+    async fn mark_value_as_reached(
+        &self, cmd: &Command, now: OffsetDateTime,
+    ) -> Result<Option<OffsetDateTime>, CommandExecutorServiceError> {
+        if let (Some(d), None) = (
+            cmd.temperature_data.value_reached_at,
+            cmd.temperature_data.hold_paused_at,
+        ) {
+            Ok(Some(d))
         } else {
-            let date = OffsetDateTime::now_utc();
-            self.repository
+            // a paused holding timer resumes where it was, the time spent out of the band is skipped
+            let date = match (
+                cmd.temperature_data.value_reached_at,
+                cmd.temperature_data.hold_paused_at,
+            ) {
+                (Some(d), Some(paused_at)) => d + (now - paused_at),
+                _ => now,
+            };
+            let updated = self
+                .repository
                 .update_value_reached_at(cmd.uuid, date)
                 .await
                 .map_err(|e| {
@@ -100,93 +695,562 @@ impl<R: CommandDrivenPort, P: PublisherDrivenPort> CommandExecutorService<R, P>
                         &cmd.status
                     ))
                 })?;
-            Ok(date)
+            Ok(Self::if_still_running(cmd, updated).map(|_| date))
+        }
+    }
+
+    async fn freeze_holding_timer(
+        &self, cmd: &Command, now: OffsetDateTime,
+    ) -> Result<(), CommandExecutorServiceError> {
+        let result = match self.settings.hold_mode {
+            HoldMode::Reset | HoldMode::Pause
+                if cmd.temperature_data.value_reached_at.is_some() && cmd.temperature_data.hold_paused_at.is_none() =>
+            {
+                self.repository.pause_value_reached_at(cmd.uuid, now).await
+            }
+            // the time since the last in band reading isn't accumulated
+            HoldMode::Accumulate if cmd.temperature_data.value_held_at.is_some() => {
+                self.repository
+                    .update_value_held(cmd.uuid, cmd.temperature_data.value_held_duration, None)
+                    .await
+            }
+            HoldMode::Reset | HoldMode::Pause | HoldMode::Accumulate => return Ok(()),
+        };
+        result.map(|_| ()).map_err(|e| {
+            CommandExecutorServiceError::TechnicalError(format!("Unable to freeze the holding timer {e:?}"))
+        })
+    }
+
+    async fn update_session_paused_at(
+        &self, session_id: Uuid, paused_at: Option<OffsetDateTime>, event_id: Uuid,
+    ) -> Result<(), CommandExecutorServiceError> {
+        let is_updated = self
+            .repository
+            .update_session_paused_at(session_id, paused_at, event_id)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to update paused at: {e}")))?;
+        if !is_updated {
+            Self::log_processed_event(session_id, event_id);
+        }
+        Ok(())
+    }
+
+    // returns false when the manual event has already been processed, it is ignored then
+    async fn is_new_event(&self, session_id: Uuid, event_id: Uuid) -> Result<bool, CommandExecutorServiceError> {
+        let is_new = self
+            .repository
+            .is_new_event(event_id)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to check the event: {e}")))?;
+        if !is_new {
+            Self::log_processed_event(session_id, event_id);
+        }
+        Ok(is_new)
+    }
+
+    fn log_processed_event(session_id: Uuid, event_id: Uuid) {
+        info!("Event {event_id} of session {session_id} has already been processed, ignoring it");
+    }
+
+    async fn update_session_status(
+        &self, session_id: Uuid, status: &SessionStatus,
+    ) -> Result<(), CommandExecutorServiceError> {
+        self.repository
+            .update_session_status(session_id, status)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to update session status: {e}")))
+    }
+
+    async fn update_hardware_override(
+        &self, session_id: Uuid, hardware_override: Option<HardwareOverride>, event_id: Option<Uuid>,
+    ) -> Result<(), CommandExecutorServiceError> {
+        let is_updated = self
+            .repository
+            .update_hardware_override(session_id, hardware_override, event_id)
+            .await
+            .map_err(|e| {
+                CommandExecutorServiceError::TechnicalError(format!("Unable to update hardware override: {e}"))
+            })?;
+        if let Some(event_id) = event_id
+            && !is_updated
+        {
+            Self::log_processed_event(session_id, event_id);
+        }
+        Ok(())
+    }
+
+    // returns false when the command isn't running anymore
+    async fn rearm_holding_timer(
+        &self, cmd: &mut Command, now: OffsetDateTime,
+    ) -> Result<bool, CommandExecutorServiceError> {
+        let result = match self.settings.hold_mode {
+            HoldMode::Reset => {
+                info!(
+                    "temperature left the band of cmd {:?}, resetting its holding timer",
+                    cmd.uuid
+                );
+                cmd.temperature_data.value_reached_at = None;
+                self.repository.reset_value_reached_at(cmd.uuid, now).await
+            }
+            HoldMode::Pause if cmd.temperature_data.hold_paused_at.is_none() => {
+                info!(
+                    "temperature left the band of cmd {:?}, pausing its holding timer",
+                    cmd.uuid
+                );
+                cmd.temperature_data.hold_paused_at = Some(now);
+                self.repository.pause_value_reached_at(cmd.uuid, now).await
+            }
+            HoldMode::Accumulate if cmd.temperature_data.value_held_at.is_some() => {
+                info!(
+                    "temperature left the band of cmd {:?}, holding time is no longer accumulated",
+                    cmd.uuid
+                );
+                cmd.temperature_data.value_held_at = None;
+                self.repository
+                    .update_value_held(cmd.uuid, cmd.temperature_data.value_held_duration, None)
+                    .await
+            }
+            HoldMode::Pause | HoldMode::Accumulate => return Ok(true),
+        };
+        result
+            .map(|updated| Self::if_still_running(cmd, updated).is_some())
+            .map_err(|e| {
+                CommandExecutorServiceError::TechnicalError(format!("Unable to rearm the holding timer {e:?}"))
+            })
+    }
+
+    // returns None when the command isn't running anymore
+    async fn is_holding_done(
+        &self, cmd: &Command, value_reached_at: OffsetDateTime, now: OffsetDateTime,
+    ) -> Result<Option<bool>, CommandExecutorServiceError> {
+        match self.settings.hold_mode {
+            HoldMode::Accumulate => Ok(self
+                .accumulate_held_duration(cmd, now)
+                .await?
+                .map(|held| held >= cmd.temperature_data.value_holding_duration)),
+            HoldMode::Reset | HoldMode::Pause => Ok(Some(Self::is_holding_duration_matched(
+                cmd.temperature_data.value_holding_duration,
+                value_reached_at,
+                now,
+            ))),
+        }
+    }
+
+    async fn accumulate_held_duration(
+        &self, cmd: &Command, measured_at: OffsetDateTime,
+    ) -> Result<Option<Duration>, CommandExecutorServiceError> {
+        // a re-evaluation may already have accounted for the time up to after this reading
+        let now = cmd
+            .temperature_data
+            .value_held_at
+            .map_or(measured_at, |held_at| held_at.max(measured_at));
+        let held = cmd.temperature_data.value_held_duration
+            + cmd
+                .temperature_data
+                .value_held_at
+                .map_or(Duration::ZERO, |held_at| now - held_at);
+        let updated = self
+            .repository
+            .update_value_held(cmd.uuid, held, Some(now))
+            .await
+            .map_err(|e| {
+                CommandExecutorServiceError::TechnicalError(format!("Unable to update held duration {e:?}"))
+            })?;
+        Ok(Self::if_still_running(cmd, updated).map(|_| held))
+    }
+
+    // an update of the holding timer is a no-op once another instance moved the command out of Running
+    fn if_still_running(cmd: &Command, updated: Option<Command>) -> Option<Command> {
+        if updated.is_none() {
+            info!(
+                "Command {:?} isn't running anymore, its holding timer is left as is",
+                cmd.uuid
+            );
         }
+        updated
+    }
+
+    fn is_holding_duration_matched(
+        holding_duration: Duration, value_reached_at: OffsetDateTime, now: OffsetDateTime,
+    ) -> bool {
+        value_reached_at + holding_duration <= now
     }
 
-    fn is_holding_duration_matched(holding_duration: Duration, value_reached_at: OffsetDateTime) -> bool {
-        value_reached_at + holding_duration <= OffsetDateTime::now_utc()
+    // Hardware is only needed once the temperature leaves the band around the target
+    fn select_hardware_type(target: f32, temperature: f32, hysteresis: f32) -> Option<HardwareType> {
+        if temperature < target - hysteresis {
+            Some(HardwareType::Heating)
+        } else if temperature > target + hysteresis {
+            Some(HardwareType::Cooling)
+        } else {
+            None
+        }
     }
 
+    // the active hardware is the one whose stop has been deferred by its protection, if any
+    // the previous step is the one of the command that has just been executed, if any
     async fn execute_next_command(
-        &self, tracking_message_data: TrackingMessageData,
+        &self, tracking_message_data: TrackingMessageData, settings: &SessionSettings,
+        active_hardware: Option<HardwareType>, previous_step: Option<i32>,
     ) -> Result<(), CommandExecutorServiceError> {
         let planned_cmds = self
             .fetch_command(tracking_message_data.session_id, &CommandStatus::Planned)
             .await?;
+        let next_step = planned_cmds.first().map(|cmd| cmd.fermentation_step_id);
+        if previous_step.is_some() && previous_step != next_step {
+            self.publish_session_event(&tracking_message_data, previous_step, SessionEventKind::StepCompleted)
+                .await?;
+        }
         if planned_cmds.is_empty() {
             info!(
                 "No more planned command to execute for session {:?}, profile execution is over.",
                 tracking_message_data.session_id
             );
-            Ok(())
+            // the fermentation is over, nothing is left running whatever the protection says
+            self.shut_down(tracking_message_data.session_id).await?;
+            self.update_session_status(
+                tracking_message_data.session_id,
+                &SessionStatus::Completed {
+                    at: tracking_message_data.measured_at,
+                },
+            )
+            .await?;
+            self.publish_session_event(
+                &tracking_message_data,
+                previous_step,
+                SessionEventKind::SessionCompleted,
+            )
+            .await
         } else {
             let planned_command = planned_cmds.first().ok_or(CommandExecutorServiceError::TechnicalError(
                 "Unable to find the first command in a non empty vec".to_string(),
             ))?;
-            let hardware_type = if planned_command.temperature_data.value > tracking_message_data.temperature {
-                HardwareType::Heating
-            } else {
-                HardwareType::Cooling
-            };
-
-            let hardware_id = self
-                .get_hardware_id(tracking_message_data.session_id, &hardware_type)
-                .await?;
-
-            let action = HardwareAction::START(hardware_id);
+            // the command is claimed before any action is published, an instance that lost the race has nothing to do
             let status = CommandStatus::Running {
-                since: OffsetDateTime::now_utc(),
+                since: tracking_message_data.measured_at,
             };
-            self.publisher
-                .publish(action)
-                .await
-                .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to publish: {e}")))?;
-            self.repository
-                .update_active_hardware_type(tracking_message_data.session_id, Some(hardware_type))
-                .await
-                .map_err(|e| {
-                    CommandExecutorServiceError::TechnicalError(format!("Unable to update active hardware type: {e}"))
-                })?;
-            self.repository
+            let claimed = self
+                .repository
                 .update_status(planned_command.uuid, &status)
                 .await
-                .map(|_| ())
                 .map_err(|e| {
                     CommandExecutorServiceError::TechnicalError(format!(
                         "Unable to update status to {:?} {e:?}",
                         &status
                     ))
-                })
+                })?;
+            if claimed.is_none() {
+                info!(
+                    "Command {:?} has already been started by another instance, nothing to do",
+                    planned_command.uuid
+                );
+                return Ok(());
+            }
+            match settings.control_mode {
+                ControlMode::Hysteresis => {
+                    let hardware_type = Self::select_hardware_type(
+                        planned_command.temperature_data.value,
+                        tracking_message_data.temperature,
+                        settings.hysteresis,
+                    );
+                    match (hardware_type, active_hardware) {
+                        (Some(hardware_type), Some(active)) if hardware_type == active => {
+                            info!("{} hardware is still running, nothing to start", active.name())
+                        }
+                        (Some(hardware_type), Some(active)) => {
+                            if self.switch_off(tracking_message_data.session_id, &active).await? {
+                                self.switch_on(tracking_message_data.session_id, hardware_type, settings)
+                                    .await?;
+                            }
+                        }
+                        (Some(hardware_type), None) => {
+                            self.switch_on(tracking_message_data.session_id, hardware_type, settings)
+                                .await?;
+                        }
+                        (None, _) => info!(
+                            "Temperature {} is already in the band of cmd {:?}, no hardware to start",
+                            tracking_message_data.temperature, planned_command.uuid
+                        ),
+                    }
+                }
+                // each command starts with a fresh PID state as the target changes
+                ControlMode::Pid => {
+                    self.apply_pid(
+                        planned_command.temperature_data.value,
+                        &tracking_message_data,
+                        settings,
+                        active_hardware,
+                        PidState::default(),
+                    )
+                    .await?
+                }
+            }
+            if previous_step != next_step {
+                self.publish_session_event(&tracking_message_data, next_step, SessionEventKind::StepStarted)
+                    .await?;
+            }
+            Ok(())
         }
     }
-    async fn stop_all(&self, cmd: &Command, session_id: Uuid) -> Result<(), CommandExecutorServiceError> {
-        let heating_hw_id = self.get_hardware_id(session_id, &HardwareType::Heating).await?;
-        let cooling_hw_id = self.get_hardware_id(session_id, &HardwareType::Cooling).await?;
-        self.publisher
-            .publish(HardwareAction::STOP(heating_hw_id))
+
+    async fn publish_session_event(
+        &self, tracking_message_data: &TrackingMessageData, step_position: Option<i32>, kind: SessionEventKind,
+    ) -> Result<(), CommandExecutorServiceError> {
+        let event = SessionEvent {
+            session_id: tracking_message_data.session_id,
+            step_position,
+            occurred_at: tracking_message_data.measured_at,
+            temperature: tracking_message_data.temperature,
+            kind,
+        };
+        self.session_events
+            .publish(event)
             .await
-            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to publish: {e}")))?;
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to publish session event: {e}")))
+    }
+
+    async fn regulate(
+        &self, target: f32, tracking_message_data: &TrackingMessageData, settings: &SessionSettings,
+        active_hardware: Option<HardwareType>, is_target_reached: bool,
+    ) -> Result<(), CommandExecutorServiceError> {
+        match settings.control_mode {
+            ControlMode::Hysteresis => match active_hardware {
+                Some(hardware_type) if is_target_reached => self
+                    .switch_off(tracking_message_data.session_id, &hardware_type)
+                    .await
+                    .map(|_| ()),
+                None if !is_target_reached => {
+                    match Self::select_hardware_type(target, tracking_message_data.temperature, settings.hysteresis) {
+                        Some(hardware_type) => self
+                            .switch_on(tracking_message_data.session_id, hardware_type, settings)
+                            .await
+                            .map(|_| ()),
+                        None => Ok(()),
+                    }
+                }
+                _ => Ok(()),
+            },
+            ControlMode::Pid => {
+                let state = self
+                    .repository
+                    .fetch_pid_state(tracking_message_data.session_id)
+                    .await
+                    .map_err(|e| CommandExecutorServiceError::TechnicalError(e.root_cause().to_string()))?;
+                self.apply_pid(target, tracking_message_data, settings, active_hardware, state)
+                    .await
+            }
+        }
+    }
+
+    async fn apply_pid(
+        &self, target: f32, tracking_message_data: &TrackingMessageData, settings: &SessionSettings,
+        active_hardware: Option<HardwareType>, state: PidState,
+    ) -> Result<(), CommandExecutorServiceError> {
+        let now = tracking_message_data.measured_at;
+        let (state, output) = state.next(&self.settings.pid, target - tracking_message_data.temperature, now);
+        let expected_hardware = state.hardware_for(&self.settings.pid, output, now);
+        if expected_hardware != active_hardware {
+            let is_off = match &active_hardware {
+                Some(hardware_type) => self.switch_off(tracking_message_data.session_id, hardware_type).await?,
+                None => true,
+            };
+            if let (true, Some(hardware_type)) = (is_off, expected_hardware) {
+                self.switch_on(tracking_message_data.session_id, hardware_type, settings)
+                    .await?;
+            }
+        }
+        self.repository
+            .update_pid_state(tracking_message_data.session_id, state)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to update pid state: {e}")))
+    }
+
+    // returns false when the hardware protection defers the switch, it is retried with the next tracking message
+    async fn switch_on(
+        &self, session_id: Uuid, hardware_type: HardwareType, settings: &SessionSettings,
+    ) -> Result<bool, CommandExecutorServiceError> {
+        if settings.lockout.is_locked_out(&hardware_type) {
+            warn!(
+                "{} hardware of session {session_id} is locked out until the session is reset",
+                hardware_type.name()
+            );
+            return Ok(false);
+        }
+        let hardware_id = self.get_hardware_id(session_id, &hardware_type).await?;
+        if !self
+            .publish_switch(session_id, &hardware_type, hardware_id, HardwareState::On, false)
+            .await?
+        {
+            return Ok(false);
+        }
+        self.repository
+            .update_active_hardware_type(session_id, Some(hardware_type))
+            .await
+            .map(|_| true)
+            .map_err(|e| {
+                CommandExecutorServiceError::TechnicalError(format!("Unable to update active hardware type: {e}"))
+            })
+    }
+
+    async fn switch_off(
+        &self, session_id: Uuid, hardware_type: &HardwareType,
+    ) -> Result<bool, CommandExecutorServiceError> {
+        let hardware_id = self.get_hardware_id(session_id, hardware_type).await?;
+        if !self
+            .publish_switch(session_id, hardware_type, hardware_id, HardwareState::Off, false)
+            .await?
+        {
+            return Ok(false);
+        }
+        self.repository
+            .update_active_hardware_type(session_id, None)
+            .await
+            .map(|_| true)
+            .map_err(|e| {
+                CommandExecutorServiceError::TechnicalError(format!("Unable to update active hardware type: {e}"))
+            })
+    }
+
+    // a forced switch bypasses the protection but is still recorded
+    async fn publish_switch(
+        &self, session_id: Uuid, hardware_type: &HardwareType, hardware_id: String, state: HardwareState,
+        is_forced: bool,
+    ) -> Result<bool, CommandExecutorServiceError> {
+        let protection = self.settings.protection_of(hardware_type);
+        let now = self.clock.now();
+        let history = if protection.is_enabled() {
+            let history = self
+                .repository
+                .fetch_hardware_switch_history(&hardware_id, now - Duration::HOUR)
+                .await
+                .map_err(|e| CommandExecutorServiceError::TechnicalError(e.root_cause().to_string()))?;
+            if !is_forced && !protection.allows(&history, &state, now) {
+                info!(
+                    "Switching {} hardware {hardware_id} {} is deferred by its protection",
+                    hardware_type.name(),
+                    state.name()
+                );
+                return Ok(false);
+            }
+            Some(history)
+        } else {
+            None
+        };
+        let action = match state {
+            HardwareState::On => HardwareAction::START(hardware_id.clone()),
+            HardwareState::Off => HardwareAction::STOP(hardware_id.clone()),
+        };
         self.publisher
-            .publish(HardwareAction::STOP(cooling_hw_id))
+            .publish(action)
             .await
             .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to publish: {e}")))?;
-        let status = CommandStatus::Executed {
-            at: OffsetDateTime::now_utc(),
+        let is_toggle = history.is_some_and(|history| history.is_toggle(&state));
+        self.repository
+            .record_hardware_switch(
+                session_id,
+                &hardware_id,
+                HardwareSwitch { state, at: now },
+                is_forced,
+                is_toggle,
+            )
+            .await
+            .map(|_| true)
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to record hardware switch: {e}")))
+    }
+
+    async fn cut_off(
+        &self, tracking_message_data: &TrackingMessageData, limit: f32, hardware_type: &HardwareType,
+        active_hardware: &Option<HardwareType>,
+    ) -> Result<(), CommandExecutorServiceError> {
+        let session_id = tracking_message_data.session_id;
+        warn!(
+            "Temperature {} of session {session_id} crossed the {limit} limit, locking out {} hardware",
+            tracking_message_data.temperature,
+            hardware_type.name()
+        );
+        // the relay may be stuck, the stop is published whatever the active hardware is
+        let hardware_id = self.get_hardware_id(session_id, hardware_type).await?;
+        self.publish_switch(session_id, hardware_type, hardware_id, HardwareState::Off, true)
+            .await?;
+        if active_hardware.as_ref() == Some(hardware_type) {
+            self.repository
+                .update_active_hardware_type(session_id, None)
+                .await
+                .map_err(|e| {
+                    CommandExecutorServiceError::TechnicalError(format!("Unable to update active hardware type: {e}"))
+                })?;
+        }
+        let now = self.clock.now();
+        self.repository
+            .lock_out_hardware(session_id, hardware_type, now)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to lock out hardware: {e}")))?;
+        let alert = Alert {
+            session_id,
+            raised_at: now,
+            kind: AlertKind::TemperatureLimitCrossed {
+                temperature: tracking_message_data.temperature,
+                limit,
+                hardware_type: hardware_type.clone(),
+            },
         };
+        self.publisher
+            .alert(alert)
+            .await
+            .map_err(|e| CommandExecutorServiceError::TechnicalError(format!("Unable to raise alert: {e}")))
+    }
+
+    // stops both hardware regardless of their protection
+    async fn shut_down(&self, session_id: Uuid) -> Result<(), CommandExecutorServiceError> {
+        for hardware_type in [HardwareType::Heating, HardwareType::Cooling] {
+            let hardware_id = self.get_hardware_id(session_id, &hardware_type).await?;
+            self.publish_switch(session_id, &hardware_type, hardware_id, HardwareState::Off, true)
+                .await?;
+        }
         self.repository
             .update_active_hardware_type(session_id, None)
             .await
             .map_err(|e| {
                 CommandExecutorServiceError::TechnicalError(format!("Unable to update active hardware type: {e}"))
-            })?;
+            })
+    }
+
+    // returns the hardware that is still running because its protection deferred the stop,
+    // None when another instance executed the command first
+    async fn stop_all(
+        &self, cmd: &Command, session_id: Uuid, executed_at: OffsetDateTime,
+    ) -> Result<Option<Option<HardwareType>>, CommandExecutorServiceError> {
+        // the command is claimed before any action is published, the hardware may belong to the next command already
+        let status = CommandStatus::Executed { at: executed_at };
+        let executed = self.repository.update_status(cmd.uuid, &status).await.map_err(|e| {
+            CommandExecutorServiceError::TechnicalError(format!("Unable to update status to {:?} {e:?}", &status))
+        })?;
+        if executed.is_none() {
+            info!("Command {:?} has already been executed by another instance", cmd.uuid);
+            return Ok(None);
+        }
+        self.switch_off_all(session_id).await.map(Some)
+    }
+
+    // returns the hardware the protection keeps running
+    async fn switch_off_all(&self, session_id: Uuid) -> Result<Option<HardwareType>, CommandExecutorServiceError> {
+        let mut still_running = None;
+        for hardware_type in [HardwareType::Heating, HardwareType::Cooling] {
+            let hardware_id = self.get_hardware_id(session_id, &hardware_type).await?;
+            if !self
+                .publish_switch(session_id, &hardware_type, hardware_id, HardwareState::Off, false)
+                .await?
+            {
+                still_running = Some(hardware_type);
+            }
+        }
         self.repository
-            .update_status(cmd.uuid, &status)
+            .update_active_hardware_type(session_id, still_running.clone())
             .await
-            .map(|_| ())
             .map_err(|e| {
-                CommandExecutorServiceError::TechnicalError(format!("Unable to update status to {:?} {e:?}", &status))
-            })
+                CommandExecutorServiceError::TechnicalError(format!("Unable to update active hardware type: {e}"))
+            })?;
+        Ok(still_running)
     }
 }
 
@@ -196,35 +1260,62 @@ mod test {
 
     use time::{Duration, OffsetDateTime};
 
+    use uuid::Uuid;
+
     use crate::{
         domain::{
+            alert::AlertKind,
             command::{Command, CommandStatus, CommandTemperatureData},
+            controller::{ControllerSettings, HoldMode},
             error::CommandExecutorServiceError,
-            message::{HardwareType, TrackingMessageData},
+            filter::{FilterKind, FilterSettings, ReadingWindow},
+            message::{HardwareType, OverrideMessageData, TrackingMessageData},
+            pid::PidState,
+            protection::{HardwareProtection, HardwareState, HardwareSwitch, HardwareSwitchHistory},
+            session::{
+                ControlMode, HardwareLockout, HardwareOverride, SessionSettings, SessionStatus, TemperatureLimits,
+            },
+            session_event::SessionEventKind,
         },
         port::{
+            clock::{ClockPort, FakeClock},
             command::{CommandExecutorDriverPort, MockCommandDrivenPort},
             publisher::{HardwareAction, MockPublisherDrivenPort},
+            session_event::FakeSessionEventPublisher,
         },
         service::command_executor_service::CommandExecutorService,
     };
 
+    type Service =
+        CommandExecutorService<MockCommandDrivenPort, MockPublisherDrivenPort, FakeSessionEventPublisher, FakeClock>;
+
     #[tokio::test]
     async fn should_not_update_value_reached_at_if_already_done() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
         repository.expect_update_status().never();
         repository.expect_update_value_reached_at().never();
         let publisher = MockPublisherDrivenPort::new();
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         let mut cmd = Command::default();
         let reached_date = OffsetDateTime::now_utc();
         cmd.temperature_data.value_reached_at = Some(reached_date);
-        let result = service.mark_value_as_reached(&cmd).await.unwrap();
-        assert_eq!(reached_date, result);
+        let result = service
+            .mark_value_as_reached(&cmd, OffsetDateTime::now_utc())
+            .await
+            .unwrap();
+        assert_eq!(Some(reached_date), result);
     }
     #[tokio::test]
     async fn should_update_value_reached_at() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
         repository.expect_update_status().never();
         repository
             .expect_update_value_reached_at()
@@ -232,35 +1323,41 @@ mod test {
             .return_once(move |_, date| {
                 let mut cmd = Command::default();
                 cmd.temperature_data.value_reached_at = Some(date);
-                Box::pin(ready(Ok(cmd)))
+                Box::pin(ready(Ok(Some(cmd))))
             });
         let cmd = Command::default();
         let publisher = MockPublisherDrivenPort::new();
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         assert!(cmd.temperature_data.value_reached_at.is_none());
-        service.mark_value_as_reached(&cmd).await.unwrap();
+        service
+            .mark_value_as_reached(&cmd, OffsetDateTime::now_utc())
+            .await
+            .unwrap();
     }
     #[test]
     fn is_holding_duration_matched_should_return_false() {
-        assert!(
-            !CommandExecutorService::<MockCommandDrivenPort, MockPublisherDrivenPort>::is_holding_duration_matched(
-                Duration::hours(5),
-                OffsetDateTime::now_utc()
-            )
-        );
+        let now = OffsetDateTime::now_utc();
+        assert!(!Service::is_holding_duration_matched(Duration::hours(5), now, now));
     }
     #[test]
     fn is_holding_duration_matched_should_return_true() {
-        assert!(
-            CommandExecutorService::<MockCommandDrivenPort, MockPublisherDrivenPort>::is_holding_duration_matched(
-                Duration::hours(5),
-                OffsetDateTime::now_utc() - Duration::hours(5)
-            )
-        );
+        let now = OffsetDateTime::now_utc();
+        assert!(Service::is_holding_duration_matched(
+            Duration::hours(5),
+            now - Duration::hours(5),
+            now
+        ));
     }
     #[tokio::test]
-    async fn execute_next_command_should_do_nothing_if_no_planned_commands() {
+    async fn execute_next_command_should_complete_the_session_if_no_planned_commands() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
         let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData::default();
 
@@ -269,14 +1366,36 @@ mod test {
         repository
             .expect_fetch_commands_by_order()
             .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
-
-        publisher.expect_publish().never();
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
-        service.execute_next_command(tracking_data).await.unwrap();
+        let settings = SessionSettings::default();
+        expect_completion(&mut repository, &mut publisher);
+        let session_events = FakeSessionEventPublisher::default();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            session_events.clone(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .execute_next_command(tracking_data, &settings, None, Some(2))
+            .await
+            .unwrap();
+        let events = session_events.events();
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| (e.kind.clone(), e.step_position))
+                .collect::<Vec<_>>(),
+            vec![
+                (SessionEventKind::StepCompleted, Some(2)),
+                (SessionEventKind::SessionCompleted, Some(2))
+            ]
+        );
     }
     #[tokio::test]
     async fn execute_next_command_should_publish_start_action_for_heating_hardware() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
         let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData {
             temperature: 16.0,
@@ -310,7 +1429,7 @@ mod test {
                         })
             })
             .once()
-            .return_once(|_, _| Box::pin(ready(Ok(Command::default()))));
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
 
         publisher
             .expect_publish()
@@ -323,12 +1442,23 @@ mod test {
             .withf(|_, hardware_type| hardware_type.as_ref().is_some_and(|t| *t == HardwareType::Heating))
             .return_once(|_, _| Box::pin(ready(Ok(()))))
             .once();
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
-        service.execute_next_command(tracking_data).await.unwrap();
+        let settings = SessionSettings::default();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .execute_next_command(tracking_data, &settings, None, None)
+            .await
+            .unwrap();
     }
     #[tokio::test]
     async fn execute_next_command_should_publish_start_action_for_cooling_hardware() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
         let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData {
             temperature: 22.0,
@@ -362,7 +1492,7 @@ mod test {
                         })
             })
             .once()
-            .return_once(|_, _| Box::pin(ready(Ok(Command::default()))));
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
 
         publisher
             .expect_publish()
@@ -375,12 +1505,139 @@ mod test {
             .withf(|_, hardware_type| hardware_type.as_ref().is_some_and(|t| *t == HardwareType::Cooling))
             .return_once(|_, _| Box::pin(ready(Ok(()))))
             .once();
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
-        service.execute_next_command(tracking_data).await.unwrap();
+        let settings = SessionSettings::default();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .execute_next_command(tracking_data, &settings, None, None)
+            .await
+            .unwrap();
+    }
+    #[test]
+    fn select_hardware_type_should_respect_the_band() {
+        assert_eq!(
+            Service::select_hardware_type(20.0, 19.4, 0.5),
+            Some(HardwareType::Heating)
+        );
+        assert_eq!(
+            Service::select_hardware_type(20.0, 20.6, 0.5),
+            Some(HardwareType::Cooling)
+        );
+        assert_eq!(Service::select_hardware_type(20.0, 19.5, 0.5), None);
+        assert_eq!(Service::select_hardware_type(20.0, 20.5, 0.5), None);
+    }
+    #[tokio::test]
+    async fn execute_next_command_should_not_start_hardware_if_temp_is_in_the_band() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 20.3,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 20.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository.expect_fetch_hardware_id().never();
+        repository.expect_update_active_hardware_type().never();
+        publisher.expect_publish().never();
+        repository
+            .expect_update_status()
+            .withf(|_, status| {
+                discriminant(status)
+                    == discriminant(&CommandStatus::Running {
+                        since: OffsetDateTime::now_utc(),
+                    })
+            })
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let settings = SessionSettings {
+            hysteresis: 0.5,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .execute_next_command(tracking_data, &settings, None, None)
+            .await
+            .unwrap();
+    }
+    #[tokio::test]
+    async fn execute_next_command_should_publish_step_transitions_only_when_the_step_changes() {
+        for (previous_step, expected) in [
+            (None, vec![(SessionEventKind::StepStarted, Some(1))]),
+            (
+                Some(0),
+                vec![
+                    (SessionEventKind::StepCompleted, Some(0)),
+                    (SessionEventKind::StepStarted, Some(1)),
+                ],
+            ),
+            (Some(1), vec![]),
+        ] {
+            let mut repository = MockCommandDrivenPort::new();
+            expect_recorded_switches(&mut repository);
+            let tracking_data = TrackingMessageData {
+                temperature: 20.0,
+                ..Default::default()
+            };
+            repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+                Box::pin(ready(Ok(vec![Command {
+                    fermentation_step_id: 1,
+                    temperature_data: CommandTemperatureData {
+                        value: 20.0,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }])))
+            });
+            repository
+                .expect_update_status()
+                .once()
+                .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+            let session_events = FakeSessionEventPublisher::default();
+            let service = CommandExecutorService::new(
+                Arc::new(repository),
+                MockPublisherDrivenPort::new(),
+                session_events.clone(),
+                FakeClock::default(),
+                ControllerSettings::default(),
+            );
+            service
+                .execute_next_command(tracking_data, &SessionSettings::default(), None, previous_step)
+                .await
+                .unwrap();
+            let events = session_events.events();
+            assert_eq!(
+                events
+                    .iter()
+                    .map(|e| (e.kind.clone(), e.step_position))
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
     }
+
     #[tokio::test]
     async fn stop_all_should_publish_stop_action_for_cooling_and_heating_hardware() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
         let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData::default();
         let cmd = Command::default();
@@ -424,15 +1681,26 @@ mod test {
                         })
             })
             .once()
-            .return_once(|_, _| Box::pin(ready(Ok(Command::default()))));
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
-        service.stop_all(&cmd, tracking_data.session_id).await.unwrap()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .stop_all(&cmd, tracking_data.session_id, OffsetDateTime::now_utc())
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
     async fn process_should_execute_next_command_if_no_command_is_running() {
         let mut repository = MockCommandDrivenPort::new();
-        let publisher = MockPublisherDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData::default();
         repository
             .expect_fetch_commands_by_order()
@@ -445,6 +1713,12 @@ mod test {
             })
             .once()
             .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
 
         //Called in execute_next_command
         repository
@@ -454,13 +1728,175 @@ mod test {
             })
             .once()
             .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
+        expect_completion(&mut repository, &mut publisher);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_not_act_on_a_command_another_instance_started_first() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_newer_reading(&mut repository);
+        let tracking_data = TrackingMessageData {
+            temperature: 25.0,
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| {
+                discriminant(status)
+                    == discriminant(&CommandStatus::Running {
+                        since: OffsetDateTime::now_utc(),
+                    })
+            })
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| discriminant(status) == discriminant(&CommandStatus::Planned))
+            .once()
+            .return_once(|_, _, _| {
+                Box::pin(ready(Ok(vec![Command {
+                    temperature_data: CommandTemperatureData {
+                        value: 20.0,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }])))
+            });
+        // the command is already Running, the start of the other instance is the only one
+        repository
+            .expect_update_status()
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_hardware_id().never();
+        repository.expect_record_hardware_switch().never();
+        publisher.expect_publish().never();
+        let session_events = FakeSessionEventPublisher::default();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            session_events.clone(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+        assert!(session_events.events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn process_should_not_execute_the_next_command_when_another_instance_executed_the_running_one() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_newer_reading(&mut repository);
+        let tracking_data = TrackingMessageData {
+            temperature: 21.0,
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| {
+                discriminant(status)
+                    == discriminant(&CommandStatus::Running {
+                        since: OffsetDateTime::now_utc(),
+                    })
+            })
+            .return_once(|_, _, _| {
+                Box::pin(ready(Ok(vec![Command {
+                    temperature_data: CommandTemperatureData {
+                        value: 20.0,
+                        value_holding_duration: Duration::hours(0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }])))
+            });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_update_value_reached_at()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        // the hardware may already be driven by the next command of the other instance
+        repository
+            .expect_update_status()
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_hardware_id().never();
+        repository.expect_record_hardware_switch().never();
+        repository.expect_update_active_hardware_type().never();
+        publisher.expect_publish().never();
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| discriminant(status) == discriminant(&CommandStatus::Planned))
+            .never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_leave_a_command_that_is_not_running_anymore_as_is() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_newer_reading(&mut repository);
+        let tracking_data = TrackingMessageData {
+            temperature: 21.0,
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(running_command_at(20.0)))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        // another instance executed the command since it has been fetched
+        repository
+            .expect_update_value_reached_at()
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(None))));
+        repository.expect_update_status().never();
+        repository.expect_fetch_hardware_id().never();
+        repository.expect_update_active_hardware_type().never();
+        publisher.expect_publish().never();
+        let session_events = FakeSessionEventPublisher::default();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            session_events.clone(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         service.process(tracking_data).await.unwrap();
+        assert!(session_events.events().is_empty());
     }
 
     #[tokio::test]
     async fn process_should_update_heating_command_as_executed() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
         let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData {
             temperature: 21.0,
@@ -488,23 +1924,26 @@ mod test {
         repository
             .expect_fetch_active_hardware_type()
             .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
         repository
             .expect_update_value_reached_at()
             .once()
-            .returning(|_, _| Box::pin(ready(Ok(Command::default())))); //mark as reached
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default()))))); //mark as reached
         repository
             .expect_fetch_hardware_id()
-            .times(2)
+            .times(4)
             .returning(|_, _| Box::pin(ready(Ok("hardware_id".to_string())))); //stop all
         publisher
             .expect_publish()
-            .times(2)
+            .times(4)
             .returning(|_| Box::pin(ready(Ok(())))); //stop all 
         repository
             .expect_update_active_hardware_type()
             .withf(|_, hardware_type| hardware_type.is_none())
-            .return_once(|_, _| Box::pin(ready(Ok(()))))
-            .once();
+            .returning(|_, _| Box::pin(ready(Ok(()))))
+            .times(2);
 
         repository
             .expect_update_status()
@@ -515,7 +1954,7 @@ mod test {
                     })
             })
             .once()
-            .returning(|_, _| Box::pin(ready(Ok(Command::default())))); //stop all //stop all 
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default()))))); //stop all //stop all 
         //Called in execute_next_command
         repository
             .expect_fetch_commands_by_order()
@@ -524,13 +1963,23 @@ mod test {
             })
             .once()
             .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
+        // the profile is over
+        expect_completed_status(&mut repository);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         service.process(tracking_data).await.unwrap();
     }
 
     #[tokio::test]
     async fn process_should_update_cooling_command_as_executed() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
         let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData {
             temperature: 21.0,
@@ -558,23 +2007,26 @@ mod test {
         repository
             .expect_fetch_active_hardware_type()
             .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Cooling)))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
         repository
             .expect_update_value_reached_at()
             .once()
-            .returning(|_, _| Box::pin(ready(Ok(Command::default())))); //mark as reached
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default()))))); //mark as reached
         repository
             .expect_fetch_hardware_id()
-            .times(2)
+            .times(4)
             .returning(|_, _| Box::pin(ready(Ok("hardware_id".to_string())))); //stop all
         publisher
             .expect_publish()
-            .times(2)
+            .times(4)
             .returning(|_| Box::pin(ready(Ok(())))); //stop all 
         repository
             .expect_update_active_hardware_type()
             .withf(|_, hardware_type| hardware_type.is_none())
-            .return_once(|_, _| Box::pin(ready(Ok(()))))
-            .once();
+            .returning(|_, _| Box::pin(ready(Ok(()))))
+            .times(2);
 
         repository
             .expect_update_status()
@@ -585,7 +2037,7 @@ mod test {
                     })
             })
             .once()
-            .returning(|_, _| Box::pin(ready(Ok(Command::default())))); //stop all //stop all 
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default()))))); //stop all //stop all 
         //Called in execute_next_command
         repository
             .expect_fetch_commands_by_order()
@@ -594,14 +2046,24 @@ mod test {
             })
             .once()
             .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
+        // the profile is over
+        expect_completed_status(&mut repository);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         service.process(tracking_data).await.unwrap();
     }
 
     #[tokio::test]
-    async fn process_should_err_if_no_active_hardware() {
+    async fn process_should_start_hardware_if_no_active_hardware_and_temp_leaves_the_band() {
         let mut repository = MockCommandDrivenPort::new();
-        let publisher = MockPublisherDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData {
             temperature: 18.0,
             ..Default::default()
@@ -628,17 +2090,152 @@ mod test {
         repository
             .expect_fetch_active_hardware_type()
             .return_once(|_| Box::pin(ready(Ok(None))));
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
-        let result = service.process(tracking_data).await.unwrap_err();
-        assert_eq!(
-            discriminant(&result),
-            discriminant(&CommandExecutorServiceError::NotFound("test".to_string()))
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 1.0,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Cooling)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok("cooling_hw_id".into()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::START("cooling_hw_id".to_string()))
+            .return_once(|_| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.as_ref().is_some_and(|t| *t == HardwareType::Cooling))
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository.expect_update_value_reached_at().never();
+        repository.expect_update_status().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_do_nothing_if_no_active_hardware_and_temp_is_in_the_band() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 19.6,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 20.0,
+                    value_reached_at: Some(OffsetDateTime::now_utc()),
+                    value_holding_duration: Duration::hours(5),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            })))
+        });
+        repository.expect_update_value_reached_at().never();
+        repository.expect_update_status().never();
+        repository.expect_update_active_hardware_type().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_stop_active_hardware_if_target_is_passed_but_holding_duration_is_not_matched() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 20.1,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 20.0,
+                    value_holding_duration: Duration::hours(5),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_update_value_reached_at()
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Heating)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok("heating_hw_id".into()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP("heating_hw_id".to_string()))
+            .return_once(|_| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.is_none())
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository.expect_update_status().never();
+        let session_events = FakeSessionEventPublisher::default();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            session_events.clone(),
+            FakeClock::default(),
+            ControllerSettings::default(),
         );
+        service.process(tracking_data).await.unwrap();
+        let events = session_events.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, SessionEventKind::StepTargetReached);
+        assert_eq!(events[0].temperature, 20.1);
     }
 
     #[tokio::test]
     async fn process_should_do_nothing_if_running_command_target_temp_is_not_reached_for_cooling_hardware() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
         let publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData {
             temperature: 18.0,
@@ -666,13 +2263,24 @@ mod test {
         repository
             .expect_fetch_active_hardware_type()
             .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Cooling)))));
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         service.process(tracking_data).await.unwrap();
     }
 
     #[tokio::test]
     async fn process_should_do_nothing_if_running_command_target_temp_is_not_reached_for_heating_hardware() {
         let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
         let publisher = MockPublisherDrivenPort::new();
         let tracking_data = TrackingMessageData {
             temperature: 18.0,
@@ -700,7 +2308,2300 @@ mod test {
         repository
             .expect_fetch_active_hardware_type()
             .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
-        let service = CommandExecutorService::new(Arc::new(repository), publisher);
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn execute_next_command_should_start_hardware_driven_by_pid() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 16.0,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 20.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Heating)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok("heating_hw_id".into()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::START("heating_hw_id".to_string()))
+            .return_once(|_| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.as_ref().is_some_and(|t| *t == HardwareType::Heating))
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_pid_state()
+            .withf(|_, state| state.last_error == Some(4.0) && state.window_start.is_some())
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_status()
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let settings = SessionSettings {
+            control_mode: ControlMode::Pid,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .execute_next_command(tracking_data, &settings, None, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_stop_hardware_once_pid_on_time_is_elapsed() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 19.9,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 20.0,
+                    value_holding_duration: Duration::hours(5),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                control_mode: ControlMode::Pid,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
+        repository.expect_fetch_pid_state().return_once(|_| {
+            let now = OffsetDateTime::now_utc();
+            Box::pin(ready(Ok(PidState {
+                window_start: Some(now - Duration::minutes(9)),
+                updated_at: Some(now - Duration::minutes(1)),
+                last_error: Some(0.1),
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Heating)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok("heating_hw_id".into()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP("heating_hw_id".to_string()))
+            .return_once(|_| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.is_none())
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_pid_state()
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository.expect_update_value_reached_at().never();
+        repository.expect_update_status().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_resume_paused_value_reached_at() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let now = OffsetDateTime::now_utc();
+        let mut cmd = Command::default();
+        cmd.temperature_data.value_reached_at = Some(now - Duration::hours(2));
+        cmd.temperature_data.hold_paused_at = Some(now - Duration::hours(1));
+        repository
+            .expect_update_value_reached_at()
+            .withf(move |_, date| *date >= now - Duration::hours(1) && *date < now - Duration::minutes(59))
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let publisher = MockPublisherDrivenPort::new();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let result = service
+            .mark_value_as_reached(&cmd, OffsetDateTime::now_utc())
+            .await
+            .unwrap();
+        assert!(result.is_some_and(|date| date >= now - Duration::hours(1)));
+    }
+
+    fn out_of_band_running_command(hold_paused_at: Option<OffsetDateTime>) -> Command {
+        Command {
+            temperature_data: CommandTemperatureData {
+                value: 20.0,
+                value_reached_at: Some(OffsetDateTime::now_utc() - Duration::hours(1)),
+                value_holding_duration: Duration::hours(5),
+                hold_paused_at,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn expect_cooling_restart(repository: &mut MockCommandDrivenPort, publisher: &mut MockPublisherDrivenPort) {
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Cooling)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok("cooling_hw_id".into()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::START("cooling_hw_id".to_string()))
+            .return_once(|_| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.as_ref().is_some_and(|t| *t == HardwareType::Cooling))
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository.expect_update_value_reached_at().never();
+        repository.expect_update_status().never();
+    }
+
+    #[tokio::test]
+    async fn process_should_reset_holding_timer_and_restart_hardware_when_temp_leaves_the_band() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 22.0,
+            measured_at: OffsetDateTime::now_utc() - Duration::minutes(5),
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![out_of_band_running_command(None)]))));
+        // the reset is dated like the reading that left the band
+        repository
+            .expect_reset_value_reached_at()
+            .withf(move |_, reset_at| *reset_at == tracking_data.measured_at)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository.expect_pause_value_reached_at().never();
+        expect_cooling_restart(&mut repository, &mut publisher);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         service.process(tracking_data).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn process_should_pause_holding_timer_and_restart_hardware_when_temp_leaves_the_band() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 22.0,
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![out_of_band_running_command(None)]))));
+        repository
+            .expect_pause_value_reached_at()
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository.expect_reset_value_reached_at().never();
+        expect_cooling_restart(&mut repository, &mut publisher);
+        let settings = ControllerSettings {
+            hold_mode: HoldMode::Pause,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            settings,
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_not_pause_holding_timer_twice() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 22.0,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![out_of_band_running_command(Some(
+                OffsetDateTime::now_utc() - Duration::minutes(10),
+            ))])))
+        });
+        repository.expect_pause_value_reached_at().never();
+        repository.expect_reset_value_reached_at().never();
+        expect_cooling_restart(&mut repository, &mut publisher);
+        let settings = ControllerSettings {
+            hold_mode: HoldMode::Pause,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            settings,
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_accumulate_held_duration_while_in_the_band() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let publisher = MockPublisherDrivenPort::new();
+        let now = OffsetDateTime::now_utc();
+        let tracking_data = TrackingMessageData {
+            temperature: 20.2,
+            measured_at: now,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(move |_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 20.0,
+                    value_reached_at: Some(now - Duration::hours(10)),
+                    value_holding_duration: Duration::hours(5),
+                    value_held_duration: Duration::hours(2),
+                    value_held_at: Some(now - Duration::hours(1)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_update_value_held()
+            .withf(move |_, held, held_at| *held == Duration::hours(3) && *held_at == Some(now))
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository.expect_update_value_reached_at().never();
+        repository.expect_update_status().never();
+        let settings = ControllerSettings {
+            hold_mode: HoldMode::Accumulate,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            settings,
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_complete_command_once_held_duration_is_accumulated() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let now = OffsetDateTime::now_utc();
+        let tracking_data = TrackingMessageData {
+            temperature: 20.2,
+            measured_at: now,
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| discriminant(status) != discriminant(&CommandStatus::Planned))
+            .return_once(move |_, _, _| {
+                Box::pin(ready(Ok(vec![Command {
+                    temperature_data: CommandTemperatureData {
+                        value: 20.0,
+                        value_reached_at: Some(now - Duration::hours(10)),
+                        value_holding_duration: Duration::hours(5),
+                        value_held_duration: Duration::hours(4),
+                        value_held_at: Some(now - Duration::hours(1)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }])))
+            });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_update_value_held()
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(Some(Command::default())))));
+        //stop all
+        repository
+            .expect_fetch_hardware_id()
+            .times(4)
+            .returning(|_, _| Box::pin(ready(Ok("hardware_id".to_string()))));
+        publisher
+            .expect_publish()
+            .times(4)
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .times(2)
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_status()
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        //Called in execute_next_command
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| discriminant(status) == discriminant(&CommandStatus::Planned))
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
+        let settings = ControllerSettings {
+            hold_mode: HoldMode::Accumulate,
+            ..Default::default()
+        };
+        // the profile is over
+        expect_completed_status(&mut repository);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            settings,
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_stop_accumulating_held_duration_when_temp_leaves_the_band() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let tracking_data = TrackingMessageData {
+            temperature: 22.0,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            let mut cmd = out_of_band_running_command(None);
+            cmd.temperature_data.value_held_duration = Duration::hours(1);
+            cmd.temperature_data.value_held_at = Some(OffsetDateTime::now_utc() - Duration::minutes(10));
+            Box::pin(ready(Ok(vec![cmd])))
+        });
+        repository
+            .expect_update_value_held()
+            .withf(|_, held, held_at| *held == Duration::hours(1) && held_at.is_none())
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository.expect_reset_value_reached_at().never();
+        repository.expect_pause_value_reached_at().never();
+        expect_cooling_restart(&mut repository, &mut publisher);
+        let settings = ControllerSettings {
+            hold_mode: HoldMode::Accumulate,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            settings,
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    // the cooling overshot the band, it is stopped as the target is passed
+    fn expect_cooling_stop_below_the_band(
+        repository: &mut MockCommandDrivenPort, publisher: &mut MockPublisherDrivenPort,
+    ) -> TrackingMessageData {
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Cooling)))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Cooling)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok("cooling_hw_id".into()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP("cooling_hw_id".to_string()))
+            .return_once(|_| Box::pin(ready(Ok(()))))
+            .once();
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.is_none())
+            .return_once(|_, _| Box::pin(ready(Ok(()))))
+            .once();
+        repository.expect_update_value_reached_at().never();
+        repository.expect_update_status().never();
+        TrackingMessageData {
+            temperature: 19.0,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn process_should_not_accumulate_held_duration_below_the_band() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            let mut cmd = out_of_band_running_command(None);
+            cmd.temperature_data.value_held_duration = Duration::hours(1);
+            cmd.temperature_data.value_held_at = Some(OffsetDateTime::now_utc() - Duration::minutes(10));
+            Box::pin(ready(Ok(vec![cmd])))
+        });
+        // the accumulation stops and doesn't start again with the same reading
+        repository
+            .expect_update_value_held()
+            .withf(|_, held, held_at| *held == Duration::hours(1) && held_at.is_none())
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let tracking_data = expect_cooling_stop_below_the_band(&mut repository, &mut publisher);
+        let settings = ControllerSettings {
+            hold_mode: HoldMode::Accumulate,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            settings,
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_keep_the_holding_timer_paused_below_the_band() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![out_of_band_running_command(None)]))));
+        // the timer is paused and isn't resumed by the same reading
+        repository
+            .expect_pause_value_reached_at()
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let tracking_data = expect_cooling_stop_below_the_band(&mut repository, &mut publisher);
+        let settings = ControllerSettings {
+            hold_mode: HoldMode::Pause,
+            ..Default::default()
+        };
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            settings,
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    fn cooling_protected_settings() -> ControllerSettings {
+        ControllerSettings {
+            cooling_protection: HardwareProtection {
+                min_run: Duration::minutes(5),
+                min_off: Duration::minutes(5),
+                max_toggles_per_hour: Some(6),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn cooling_switch_history(state: HardwareState, ago: Duration) -> HardwareSwitchHistory {
+        HardwareSwitchHistory {
+            last_switch: Some(HardwareSwitch {
+                state,
+                at: OffsetDateTime::now_utc() - ago,
+            }),
+            toggles_last_hour: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn process_should_defer_start_if_cooling_min_off_is_not_elapsed() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_newer_reading(&mut repository);
+        let tracking_data = out_of_band_running_command_tracking(&mut repository);
+        repository
+            .expect_fetch_hardware_switch_history()
+            .withf(|hardware_id, _| hardware_id == "cooling_hw_id")
+            .once()
+            .return_once(|_, _| {
+                Box::pin(ready(Ok(cooling_switch_history(
+                    HardwareState::Off,
+                    Duration::minutes(1),
+                ))))
+            });
+        publisher.expect_publish().never();
+        repository.expect_record_hardware_switch().never();
+        repository.expect_update_active_hardware_type().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            cooling_protected_settings(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_start_and_record_switch_once_cooling_min_off_is_elapsed() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_newer_reading(&mut repository);
+        let tracking_data = out_of_band_running_command_tracking(&mut repository);
+        repository
+            .expect_fetch_hardware_switch_history()
+            .once()
+            .return_once(|_, _| {
+                Box::pin(ready(Ok(cooling_switch_history(
+                    HardwareState::Off,
+                    Duration::minutes(6),
+                ))))
+            });
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::START("cooling_hw_id".to_string()))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_record_hardware_switch()
+            .withf(|_, hardware_id, switch, is_forced, is_toggle| {
+                hardware_id == "cooling_hw_id" && switch.state == HardwareState::On && !is_forced && *is_toggle
+            })
+            .once()
+            .return_once(|_, _, _, _, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| *hardware_type == Some(HardwareType::Cooling))
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            cooling_protected_settings(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stop_all_should_not_touch_the_hardware_when_another_instance_executed_the_command() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        repository
+            .expect_update_status()
+            .withf(|_, status| {
+                discriminant(status)
+                    == discriminant(&CommandStatus::Executed {
+                        at: OffsetDateTime::now_utc(),
+                    })
+            })
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_hardware_id().never();
+        repository.expect_record_hardware_switch().never();
+        repository.expect_update_active_hardware_type().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let still_running = service
+            .stop_all(
+                &Command::default(),
+                TrackingMessageData::default().session_id,
+                OffsetDateTime::now_utc(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(still_running, None);
+    }
+
+    #[tokio::test]
+    async fn stop_all_should_keep_cooling_running_until_min_run_is_elapsed() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Heating)
+            .return_once(|_, _| Box::pin(ready(Ok("heating_hw_id".into()))));
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Cooling)
+            .return_once(|_, _| Box::pin(ready(Ok("cooling_hw_id".into()))));
+        repository
+            .expect_fetch_hardware_switch_history()
+            .withf(|hardware_id, _| hardware_id == "cooling_hw_id")
+            .once()
+            .return_once(|_, _| {
+                Box::pin(ready(Ok(cooling_switch_history(
+                    HardwareState::On,
+                    Duration::minutes(2),
+                ))))
+            });
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP("heating_hw_id".to_string()))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| *hardware_type == Some(HardwareType::Cooling))
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_status()
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            cooling_protected_settings(),
+        );
+        let still_running = service
+            .stop_all(
+                &Command::default(),
+                TrackingMessageData::default().session_id,
+                OffsetDateTime::now_utc(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(still_running, Some(Some(HardwareType::Cooling)));
+    }
+
+    #[tokio::test]
+    async fn execute_next_command_should_not_restart_hardware_that_is_still_running() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let tracking_data = TrackingMessageData {
+            temperature: 18.0,
+            ..Default::default()
+        };
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 12.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        publisher.expect_publish().never();
+        repository.expect_update_active_hardware_type().never();
+        repository
+            .expect_update_status()
+            .withf(|_, status| {
+                discriminant(status)
+                    == discriminant(&CommandStatus::Running {
+                        since: OffsetDateTime::now_utc(),
+                    })
+            })
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        let settings = SessionSettings::default();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            cooling_protected_settings(),
+        );
+        service
+            .execute_next_command(tracking_data, &settings, Some(HardwareType::Cooling), None)
+            .await
+            .unwrap();
+    }
+
+    // a running command at 11° with no active hardware while the temperature is at 18°, cooling has to start
+    fn out_of_band_running_command_tracking(repository: &mut MockCommandDrivenPort) -> TrackingMessageData {
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 11.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 1.0,
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Cooling)
+            .return_once(|_, _| Box::pin(ready(Ok("cooling_hw_id".into()))));
+        TrackingMessageData {
+            temperature: 18.0,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn process_sensor_loss_should_ignore_session_without_running_command() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
+        publisher.expect_publish().never();
+        publisher.expect_alert().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let result = service
+            .process_sensor_loss(Uuid::new_v4(), OffsetDateTime::now_utc())
+            .await
+            .unwrap();
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn process_sensor_loss_should_stop_all_hardware_and_raise_an_alert() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_newer_reading(&mut repository);
+        let session_id = Uuid::new_v4();
+        let last_tracked_at = OffsetDateTime::now_utc() - Duration::minutes(20);
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![Command::default()]))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Heating)
+            .return_once(|_, _| Box::pin(ready(Ok("heating_hw_id".into()))));
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Cooling)
+            .return_once(|_, _| Box::pin(ready(Ok("cooling_hw_id".into()))));
+        // the cooling protection doesn't defer a safety stop, the switch is still recorded
+        repository.expect_fetch_hardware_switch_history().return_once(|_, _| {
+            Box::pin(ready(Ok(cooling_switch_history(
+                HardwareState::On,
+                Duration::minutes(1),
+            ))))
+        });
+        repository
+            .expect_record_hardware_switch()
+            .withf(|_, hardware_id, switch, _, is_toggle| {
+                hardware_id == "cooling_hw_id" && switch.state == HardwareState::Off && *is_toggle
+            })
+            .once()
+            .return_once(|_, _, _, _, _| Box::pin(ready(Ok(()))));
+        expect_recorded_switches(&mut repository);
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP("heating_hw_id".to_string()))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP("cooling_hw_id".to_string()))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(move |&id, hardware_type| id == session_id && hardware_type.is_none())
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(()))));
+        publisher
+            .expect_alert()
+            .withf(move |alert| {
+                alert.session_id == session_id && alert.kind == AlertKind::SensorLost { last_tracked_at }
+            })
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            cooling_protected_settings(),
+        );
+        let result = service.process_sensor_loss(session_id, last_tracked_at).await.unwrap();
+        assert!(result);
+    }
+
+    fn running_command_at(value: f32) -> Vec<Command> {
+        vec![Command {
+            temperature_data: CommandTemperatureData {
+                value,
+                ..Default::default()
+            },
+            ..Default::default()
+        }]
+    }
+
+    #[tokio::test]
+    async fn process_should_cut_off_and_lock_out_heating_once_max_temperature_is_crossed() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let tracking_data = TrackingMessageData {
+            temperature: 36.0,
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(running_command_at(20.0)))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                temperature_limits: TemperatureLimits {
+                    min: Some(0.0),
+                    max: Some(35.0),
+                },
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Heating)
+            .return_once(|_, _| Box::pin(ready(Ok("heating_hw_id".into()))));
+        repository
+            .expect_fetch_hardware_id()
+            .withf(|_, hardware_type| *hardware_type == HardwareType::Cooling)
+            .return_once(|_, _| Box::pin(ready(Ok("cooling_hw_id".into()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP("heating_hw_id".to_string()))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_lock_out_hardware()
+            .withf(|_, hardware_type, _| *hardware_type == HardwareType::Heating)
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(()))));
+        publisher
+            .expect_alert()
+            .withf(|alert| {
+                alert.kind
+                    == AlertKind::TemperatureLimitCrossed {
+                        temperature: 36.0,
+                        limit: 35.0,
+                        hardware_type: HardwareType::Heating,
+                    }
+            })
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.is_none())
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(()))));
+        // cooling is still allowed to bring the temperature back
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::START("cooling_hw_id".to_string()))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| *hardware_type == Some(HardwareType::Cooling))
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_not_start_locked_out_hardware() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let tracking_data = TrackingMessageData {
+            temperature: 15.0,
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(running_command_at(20.0)))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                lockout: HardwareLockout {
+                    heating: true,
+                    cooling: false,
+                },
+                ..Default::default()
+            })))
+        });
+        publisher.expect_publish().never();
+        publisher.expect_alert().never();
+        repository.expect_lock_out_hardware().never();
+        repository.expect_update_active_hardware_type().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.process(tracking_data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reset_lockout_should_clear_the_session_lockout() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let (session_id, event_id) = (Uuid::new_v4(), Uuid::new_v4());
+        repository
+            .expect_reset_lockout()
+            .withf(move |&id, &reset_event_id| id == session_id && reset_event_id == event_id)
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(true))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            MockPublisherDrivenPort::new(),
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service.reset_lockout(session_id, event_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn fetch_due_sessions_should_only_return_sessions_whose_holding_deadline_is_over() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let (due, not_due, not_reached) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        repository
+            .expect_fetch_session_settings()
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        let now = OffsetDateTime::now_utc();
+        let reached_at = |reached_at| Command {
+            temperature_data: CommandTemperatureData {
+                value_reached_at: reached_at,
+                value_holding_duration: Duration::hours(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let running_cmds = vec![
+            (due, reached_at(Some(now - Duration::hours(3)))),
+            (not_due, reached_at(Some(now - Duration::hours(1)))),
+            (not_reached, reached_at(None)),
+        ];
+        repository
+            .expect_fetch_running_commands()
+            .return_once(|| Box::pin(ready(Ok(running_cmds))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            MockPublisherDrivenPort::new(),
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        assert_eq!(service.fetch_due_sessions().await.unwrap(), vec![due]);
+    }
+
+    #[tokio::test]
+    async fn fetch_due_sessions_should_return_the_running_sessions_driven_by_pid() {
+        let mut repository = MockCommandDrivenPort::new();
+        let (pid, paused_pid, hysteresis) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let now = OffsetDateTime::now_utc();
+        repository.expect_fetch_running_commands().return_once(move || {
+            Box::pin(ready(Ok(vec![
+                (pid, Command::default()),
+                (paused_pid, Command::default()),
+                (hysteresis, Command::default()),
+            ])))
+        });
+        repository.expect_fetch_session_settings().returning(move |session_id| {
+            let control_mode = match session_id {
+                id if id == hysteresis => ControlMode::Hysteresis,
+                _ => ControlMode::Pid,
+            };
+            Box::pin(ready(Ok(SessionSettings {
+                control_mode,
+                paused_at: (session_id == paused_pid).then_some(now),
+                ..Default::default()
+            })))
+        });
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            MockPublisherDrivenPort::new(),
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        // its on time may end before the next reading
+        assert_eq!(service.fetch_due_sessions().await.unwrap(), vec![pid]);
+    }
+
+    #[tokio::test]
+    async fn process_should_complete_a_fourteen_days_hold_exactly_on_time() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let started_at = OffsetDateTime::UNIX_EPOCH;
+        let clock = FakeClock::at(started_at);
+        let running_cmd = Command {
+            temperature_data: CommandTemperatureData {
+                value: 20.0,
+                value_holding_duration: Duration::days(14),
+                value_reached_at: Some(started_at),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| matches!(status, CommandStatus::Running { .. }))
+            .times(14)
+            .returning(move |_, _, _| Box::pin(ready(Ok(vec![running_cmd.clone()]))));
+        repository
+            .expect_fetch_session_settings()
+            .times(14)
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .times(14)
+            .returning(|_| Box::pin(ready(Ok(None))));
+        repository
+            .expect_fetch_hardware_id()
+            .times(4)
+            .returning(|_, _| Box::pin(ready(Ok("hardware_id".to_string()))));
+        publisher
+            .expect_publish()
+            .times(4)
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .times(2)
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_status()
+            .withf(move |_, status| {
+                status
+                    == &CommandStatus::Executed {
+                        at: started_at + Duration::days(14),
+                    }
+            })
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| status == &CommandStatus::Planned)
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(vec![]))));
+        // the profile is over
+        expect_completed_status(&mut repository);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            clock.clone(),
+            ControllerSettings::default(),
+        );
+        // one reading a day, the command is only executed once the 14th day is over
+        for _ in 0..14 {
+            clock.advance(Duration::DAY);
+            service
+                .process(TrackingMessageData {
+                    temperature: 20.0,
+                    measured_at: clock.now(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn process_should_ignore_reading_older_than_the_last_processed_one() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let measured_at = OffsetDateTime::now_utc() - Duration::minutes(5);
+        repository
+            .expect_is_new_reading()
+            .withf(move |_, at, _| *at == measured_at)
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(false))));
+        repository.expect_record_reading().never();
+        repository.expect_fetch_commands_by_order().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            MockPublisherDrivenPort::new(),
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 20.0,
+                measured_at,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_leave_a_reading_that_failed_to_be_evaluated_to_its_redelivery() {
+        let mut repository = MockCommandDrivenPort::new();
+        let event_id = Some(Uuid::new_v4());
+        repository
+            .expect_is_new_reading()
+            .withf(move |_, _, id| *id == event_id)
+            .times(2)
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        repository
+            .expect_fetch_session_settings()
+            .once()
+            .return_once(|_| Box::pin(ready(Err(anyhow::anyhow!("connection lost")))));
+        // the redelivered event is evaluated, the reading is only recorded and marked as processed then
+        repository
+            .expect_fetch_session_settings()
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(paused_settings(OffsetDateTime::now_utc())))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository
+            .expect_record_reading()
+            .withf(move |_, _, accepted, _, id| accepted.is_some() && *id == event_id)
+            .once()
+            .returning(|_, _, _, _, _| Box::pin(ready(Ok(true))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            MockPublisherDrivenPort::new(),
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let reading = || TrackingMessageData {
+            temperature: 20.0,
+            event_id,
+            ..Default::default()
+        };
+        assert!(service.process(reading()).await.is_err());
+        service.process(reading()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reevaluate_should_complete_command_at_the_current_time_without_recording_a_reading() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        let now = OffsetDateTime::now_utc();
+        repository.expect_record_reading().never();
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| matches!(status, CommandStatus::Running { .. }))
+            .return_once(move |_, _, _| {
+                Box::pin(ready(Ok(vec![Command {
+                    temperature_data: CommandTemperatureData {
+                        value: 20.0,
+                        value_reached_at: Some(now - Duration::hours(2)),
+                        value_holding_duration: Duration::hours(2),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }])))
+            });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_fetch_hardware_id()
+            .times(4)
+            .returning(|_, _| Box::pin(ready(Ok("hardware_id".to_string()))));
+        publisher
+            .expect_publish()
+            .times(4)
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .times(2)
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_status()
+            .withf(move |_, status| status == &CommandStatus::Executed { at: now })
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository
+            .expect_fetch_commands_by_order()
+            .withf(|_, status, _| status == &CommandStatus::Planned)
+            .return_once(|_, _, _| Box::pin(ready(Ok(vec![]))));
+        // the profile is over
+        expect_completed_status(&mut repository);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        // the last reading was taken before the holding deadline
+        service
+            .reevaluate(TrackingMessageData {
+                temperature: 20.0,
+                measured_at: now - Duration::minutes(10),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    fn filtered_settings(max_jump: Option<f32>) -> ControllerSettings {
+        ControllerSettings {
+            filter: FilterSettings {
+                kind: FilterKind::Median,
+                window: 3,
+                max_jump,
+            },
+            ..Default::default()
+        }
+    }
+
+    // the window and the reading, unless discarded, are recorded together once the reading is handled
+    fn expect_reading_window(repository: &mut MockCommandDrivenPort, expected: ReadingWindow, stored: Option<f32>) {
+        repository
+            .expect_is_new_reading()
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        repository.expect_fetch_reading_window().return_once(|_| {
+            Box::pin(ready(Ok(ReadingWindow {
+                readings: vec![19.8, 20.0],
+                filtered: Some(19.9),
+                outliers: 0,
+            })))
+        });
+        repository
+            .expect_record_reading()
+            .withf(move |_, _, accepted, window, _| {
+                accepted.as_ref().map(|reading| reading.temperature) == stored && window.as_ref() == Some(&expected)
+            })
+            .once()
+            .returning(|_, _, _, _, _| Box::pin(ready(Ok(true))));
+    }
+
+    #[tokio::test]
+    async fn process_should_decide_on_the_filtered_temperature() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        let mut publisher = MockPublisherDrivenPort::new();
+        // the raw temperature is stored, the filtered one is only used to decide
+        expect_reading_window(
+            &mut repository,
+            ReadingWindow {
+                readings: vec![19.8, 20.0, 25.0],
+                filtered: Some(20.0),
+                outliers: 0,
+            },
+            Some(25.0),
+        );
+        repository.expect_fetch_commands_by_order().return_once(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value: 20.0,
+                    value_reached_at: Some(OffsetDateTime::now_utc()),
+                    value_holding_duration: Duration::hours(5),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            })))
+        });
+        // the raw 25.0 would have started the cooling hardware
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            filtered_settings(None),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 25.0,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_discard_outlier_reading() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_reading_window(
+            &mut repository,
+            ReadingWindow {
+                readings: vec![19.8, 20.0],
+                filtered: Some(19.9),
+                outliers: 1,
+            },
+            None,
+        );
+        repository.expect_fetch_commands_by_order().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            MockPublisherDrivenPort::new(),
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            filtered_settings(Some(2.0)),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 25.0,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancel_should_stop_all_hardware_and_cancel_remaining_commands() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_new_event(&mut repository);
+        let session_id = Uuid::new_v4();
+        let now = OffsetDateTime::now_utc();
+        repository
+            .expect_fetch_session_settings()
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_fetch_hardware_id()
+            .times(2)
+            .returning(|_, hardware_type| Box::pin(ready(Ok(hardware_type.name().to_string()))));
+        publisher
+            .expect_publish()
+            .withf(|action| matches!(action, HardwareAction::STOP(_)))
+            .times(2)
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(move |id, hardware_type| *id == session_id && hardware_type.is_none())
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_cancel_commands()
+            .withf(move |id, cancelled_at, _| *id == session_id && *cancelled_at == now)
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(3))));
+        repository
+            .expect_update_session_status()
+            .withf(move |id, status| *id == session_id && *status == SessionStatus::Cancelled { at: now })
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        assert_eq!(service.cancel(session_id, Uuid::new_v4()).await.unwrap(), 3);
+    }
+
+    fn paused_settings(paused_at: OffsetDateTime) -> SessionSettings {
+        SessionSettings {
+            paused_at: Some(paused_at),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn process_should_record_but_not_act_on_readings_of_a_paused_session() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        repository
+            .expect_is_new_reading()
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        repository
+            .expect_record_reading()
+            .withf(|_, _, accepted, _, _| accepted.as_ref().is_some_and(|reading| reading.temperature == 30.0))
+            .once()
+            .returning(|_, _, _, _, _| Box::pin(ready(Ok(true))));
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(paused_settings(OffsetDateTime::now_utc())))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(None))));
+        repository.expect_fetch_commands_by_order().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 30.0,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn pause_should_stop_all_hardware_and_freeze_the_holding_timer() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_new_event(&mut repository);
+        let now = OffsetDateTime::now_utc();
+        repository
+            .expect_fetch_session_settings()
+            .return_once(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_fetch_hardware_id()
+            .times(2)
+            .returning(|_, _| Box::pin(ready(Ok("hardware_id".to_string()))));
+        publisher
+            .expect_publish()
+            .withf(|action| matches!(action, HardwareAction::STOP(_)))
+            .times(2)
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository.expect_fetch_commands_by_order().return_once(move |_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value_reached_at: Some(now - Duration::hours(1)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        repository
+            .expect_pause_value_reached_at()
+            .withf(move |_, paused_at| *paused_at == now)
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository
+            .expect_update_session_paused_at()
+            .withf(move |_, paused_at, _| *paused_at == Some(now))
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        service.pause(Uuid::new_v4(), Uuid::new_v4()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_should_preserve_the_elapsed_hold_time() {
+        let mut repository = MockCommandDrivenPort::new();
+        expect_recorded_switches(&mut repository);
+        expect_new_event(&mut repository);
+        let paused_at = OffsetDateTime::now_utc();
+        let clock = FakeClock::at(paused_at);
+        repository
+            .expect_fetch_session_settings()
+            .return_once(move |_| Box::pin(ready(Ok(paused_settings(paused_at)))));
+        repository.expect_fetch_commands_by_order().return_once(move |_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                temperature_data: CommandTemperatureData {
+                    value_reached_at: Some(paused_at - Duration::hours(1)),
+                    hold_paused_at: Some(paused_at),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])))
+        });
+        // one hour held before the pause, the timer goes on as if it had been reached one hour ago
+        let resumed_at = paused_at + Duration::hours(3);
+        repository
+            .expect_update_value_reached_at()
+            .withf(move |_, value_reached_at| *value_reached_at == resumed_at - Duration::hours(1))
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository
+            .expect_update_session_paused_at()
+            .withf(|_, paused_at, _| paused_at.is_none())
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            MockPublisherDrivenPort::new(),
+            FakeSessionEventPublisher::default(),
+            clock.clone(),
+            ControllerSettings::default(),
+        );
+        clock.advance(Duration::hours(3));
+        service.resume(Uuid::new_v4(), Uuid::new_v4()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn skip_should_execute_the_running_command_and_start_the_next_one() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_new_event(&mut repository);
+        let session_id = Uuid::new_v4();
+        let (running_uuid, next_uuid) = (Uuid::new_v4(), Uuid::new_v4());
+        repository
+            .expect_fetch_commands_by_order()
+            .returning(move |_, status, _| {
+                let cmd = match status {
+                    CommandStatus::Planned => Command {
+                        uuid: next_uuid,
+                        fermentation_step_id: 2,
+                        temperature_data: CommandTemperatureData {
+                            value: 20.0,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    _ => Command {
+                        uuid: running_uuid,
+                        fermentation_step_id: 1,
+                        ..Default::default()
+                    },
+                };
+                Box::pin(ready(Ok(vec![cmd])))
+            });
+        repository
+            .expect_fetch_hardware_id()
+            .returning(|_, hardware_type| Box::pin(ready(Ok(hardware_type.name().to_string()))));
+        publisher
+            .expect_publish()
+            .withf(|action| matches!(action, HardwareAction::STOP(_)))
+            .times(2)
+            .returning(|_| Box::pin(ready(Ok(()))));
+        publisher
+            .expect_publish()
+            .withf(|action| *action == HardwareAction::START("Heating".to_string()))
+            .once()
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.is_none())
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| *hardware_type == Some(HardwareType::Heating))
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_skip_commands()
+            .withf(move |running, skipped, _, _| *running == Some(running_uuid) && skipped.is_empty())
+            .once()
+            .returning(|_, _, _, _| Box::pin(ready(Ok(Some(0)))));
+        repository
+            .expect_update_status()
+            .withf(move |uuid, status| *uuid == next_uuid && matches!(status, CommandStatus::Running { .. }))
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(Some(Command::default())))));
+        repository
+            .expect_fetch_session_settings()
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let last_reading = TrackingMessageData {
+            session_id,
+            temperature: 16.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            service
+                .skip(session_id, None, Some(last_reading), Uuid::new_v4())
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn skip_should_mark_the_commands_before_the_target_step_as_skipped() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_new_event(&mut repository);
+        let session_id = Uuid::new_v4();
+        let planned_cmds =
+            [(Uuid::new_v4(), 1), (Uuid::new_v4(), 1), (Uuid::new_v4(), 2)].map(|(uuid, step)| Command {
+                uuid,
+                fermentation_step_id: step,
+                ..Default::default()
+            });
+        let skipped_uuids = [planned_cmds[0].uuid, planned_cmds[1].uuid];
+        repository
+            .expect_fetch_commands_by_order()
+            .returning(move |_, status, _| {
+                let cmds = match status {
+                    CommandStatus::Planned => planned_cmds.to_vec(),
+                    _ => vec![],
+                };
+                Box::pin(ready(Ok(cmds)))
+            });
+        repository
+            .expect_fetch_active_hardware_type()
+            .once()
+            .returning(|_| Box::pin(ready(Ok(None))));
+        repository
+            .expect_skip_commands()
+            .withf(move |running, skipped, _, _| running.is_none() && *skipped == skipped_uuids)
+            .once()
+            .returning(|_, _, _, _| Box::pin(ready(Ok(Some(2)))));
+        repository
+            .expect_fetch_session_settings()
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        // without a known reading the target step starts with the next tracking message
+        assert_eq!(
+            service.skip(session_id, Some(2), None, Uuid::new_v4()).await.unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn skip_should_fail_on_unknown_step() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_new_event(&mut repository);
+        repository
+            .expect_fetch_session_settings()
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository.expect_fetch_commands_by_order().returning(|_, _, _| {
+            Box::pin(ready(Ok(vec![Command {
+                fermentation_step_id: 1,
+                ..Default::default()
+            }])))
+        });
+        repository.expect_skip_commands().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let err = service
+            .skip(Uuid::new_v4(), Some(5), None, Uuid::new_v4())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CommandExecutorServiceError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn manual_events_should_leave_a_finished_session_as_is() {
+        let now = OffsetDateTime::now_utc();
+        for status in [
+            SessionStatus::Completed { at: now },
+            SessionStatus::Cancelled { at: now },
+        ] {
+            let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+            repository.expect_fetch_session_settings().returning(move |_| {
+                Box::pin(ready(Ok(SessionSettings {
+                    status: status.clone(),
+                    paused_at: Some(now),
+                    ..Default::default()
+                })))
+            });
+            repository.expect_fetch_commands_by_order().never();
+            repository.expect_update_status().never();
+            repository.expect_cancel_commands().never();
+            repository.expect_update_session_status().never();
+            repository.expect_update_session_paused_at().never();
+            repository.expect_update_active_hardware_type().never();
+            publisher.expect_publish().never();
+            let session_events = FakeSessionEventPublisher::default();
+            let service = CommandExecutorService::new(
+                Arc::new(repository),
+                publisher,
+                session_events.clone(),
+                FakeClock::at(now),
+                ControllerSettings::default(),
+            );
+            let session_id = Uuid::new_v4();
+            let last_reading = TrackingMessageData {
+                session_id,
+                temperature: 20.0,
+                ..Default::default()
+            };
+            let event_id = Uuid::new_v4();
+            assert_eq!(
+                service
+                    .skip(session_id, None, Some(last_reading), event_id)
+                    .await
+                    .unwrap(),
+                0
+            );
+            assert_eq!(service.cancel(session_id, event_id).await.unwrap(), 0);
+            service.pause(session_id, event_id).await.unwrap();
+            service.resume(session_id, event_id).await.unwrap();
+            assert!(session_events.events().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn manual_events_should_ignore_a_redelivered_event() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        let event_id = Uuid::new_v4();
+        repository
+            .expect_is_new_event()
+            .withf(move |id| *id == event_id)
+            .returning(|_| Box::pin(ready(Ok(false))));
+        repository
+            .expect_fetch_session_settings()
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository.expect_fetch_commands_by_order().never();
+        repository.expect_skip_commands().never();
+        repository.expect_cancel_commands().never();
+        repository.expect_update_session_paused_at().never();
+        repository.expect_update_hardware_override().never();
+        repository.expect_update_active_hardware_type().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let session_id = Uuid::new_v4();
+        let last_reading = TrackingMessageData {
+            session_id,
+            temperature: 20.0,
+            ..Default::default()
+        };
+        // the profile isn't advanced once more
+        assert_eq!(
+            service
+                .skip(session_id, None, Some(last_reading), event_id)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(service.cancel(session_id, event_id).await.unwrap(), 0);
+        service.pause(session_id, event_id).await.unwrap();
+        service
+            .override_hardware(OverrideMessageData {
+                event_id,
+                session_id,
+                hardware_type: HardwareType::Cooling,
+                state: HardwareState::On,
+                duration: Duration::minutes(30),
+            })
+            .await
+            .unwrap();
+    }
+
+    fn overridden_settings(until: OffsetDateTime) -> SessionSettings {
+        SessionSettings {
+            hardware_override: Some(HardwareOverride {
+                hardware_type: HardwareType::Cooling,
+                state: HardwareState::On,
+                until,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn override_hardware_should_force_the_hardware_until_it_expires() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_new_event(&mut repository);
+        let (session_id, event_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = OffsetDateTime::now_utc();
+        repository
+            .expect_fetch_session_settings()
+            .returning(|_| Box::pin(ready(Ok(SessionSettings::default()))));
+        repository
+            .expect_fetch_hardware_id()
+            .times(2)
+            .returning(|_, hardware_type| Box::pin(ready(Ok(hardware_type.name().to_string()))));
+        publisher
+            .expect_publish()
+            .withf(|action| *action == HardwareAction::STOP("Heating".to_string()))
+            .once()
+            .returning(|_| Box::pin(ready(Ok(()))));
+        publisher
+            .expect_publish()
+            .withf(|action| *action == HardwareAction::START("Cooling".to_string()))
+            .once()
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| *hardware_type == Some(HardwareType::Cooling))
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_hardware_override()
+            .withf(move |id, hardware_override, override_event_id| {
+                *id == session_id
+                    && *hardware_override == overridden_settings(now + Duration::minutes(30)).hardware_override
+                    && *override_event_id == Some(event_id)
+            })
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        service
+            .override_hardware(OverrideMessageData {
+                event_id,
+                session_id,
+                hardware_type: HardwareType::Cooling,
+                state: HardwareState::On,
+                duration: Duration::minutes(30),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn override_hardware_should_not_force_a_locked_out_hardware_on() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        repository.expect_fetch_session_settings().returning(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                lockout: HardwareLockout {
+                    heating: true,
+                    cooling: false,
+                },
+                ..Default::default()
+            })))
+        });
+        repository.expect_update_hardware_override().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .override_hardware(OverrideMessageData {
+                event_id: Uuid::new_v4(),
+                session_id: Uuid::new_v4(),
+                hardware_type: HardwareType::Heating,
+                state: HardwareState::On,
+                duration: Duration::minutes(30),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn override_hardware_should_not_force_the_hardware_of_a_paused_or_finished_session() {
+        let now = OffsetDateTime::now_utc();
+        for settings in [
+            paused_settings(now),
+            SessionSettings {
+                status: SessionStatus::Completed { at: now },
+                ..Default::default()
+            },
+            SessionSettings {
+                status: SessionStatus::Cancelled { at: now },
+                ..Default::default()
+            },
+        ] {
+            let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+            repository
+                .expect_fetch_session_settings()
+                .return_once(|_| Box::pin(ready(Ok(settings))));
+            repository.expect_update_hardware_override().never();
+            repository.expect_update_active_hardware_type().never();
+            publisher.expect_publish().never();
+            let service = CommandExecutorService::new(
+                Arc::new(repository),
+                publisher,
+                FakeSessionEventPublisher::default(),
+                FakeClock::at(now),
+                ControllerSettings::default(),
+            );
+            service
+                .override_hardware(OverrideMessageData {
+                    event_id: Uuid::new_v4(),
+                    session_id: Uuid::new_v4(),
+                    hardware_type: HardwareType::Heating,
+                    state: HardwareState::On,
+                    duration: Duration::minutes(30),
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn process_should_not_act_on_readings_while_the_hardware_is_overridden() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        let now = OffsetDateTime::now_utc();
+        expect_newer_reading(&mut repository);
+        repository
+            .expect_fetch_session_settings()
+            .return_once(move |_| Box::pin(ready(Ok(overridden_settings(now + Duration::minutes(10))))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Cooling)))));
+        repository.expect_fetch_commands_by_order().never();
+        repository.expect_update_hardware_override().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 30.0,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_cut_off_an_overridden_heating_once_max_temperature_is_crossed() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        expect_newer_reading(&mut repository);
+        let now = OffsetDateTime::now_utc();
+        repository
+            .expect_fetch_commands_by_order()
+            .return_once(|_, _, _| Box::pin(ready(Ok(running_command_at(20.0)))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .return_once(|_| Box::pin(ready(Ok(Some(HardwareType::Heating)))));
+        repository.expect_fetch_session_settings().return_once(move |_| {
+            Box::pin(ready(Ok(SessionSettings {
+                temperature_limits: TemperatureLimits {
+                    min: None,
+                    max: Some(35.0),
+                },
+                hardware_override: Some(HardwareOverride {
+                    hardware_type: HardwareType::Heating,
+                    state: HardwareState::On,
+                    until: now + Duration::minutes(30),
+                }),
+                ..Default::default()
+            })))
+        });
+        repository
+            .expect_fetch_hardware_id()
+            .returning(|_, hardware_type| Box::pin(ready(Ok(hardware_type.name().to_string()))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| *hardware_action == HardwareAction::STOP(HardwareType::Heating.name().to_string()))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_lock_out_hardware()
+            .withf(|_, hardware_type, _| *hardware_type == HardwareType::Heating)
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(()))));
+        publisher
+            .expect_alert()
+            .withf(|alert| matches!(alert.kind, AlertKind::TemperatureLimitCrossed { .. }))
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.is_none())
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(()))));
+        // the override is over, the profile brings the temperature back
+        repository
+            .expect_update_hardware_override()
+            .withf(|_, hardware_override, event_id| hardware_override.is_none() && event_id.is_none())
+            .once()
+            .return_once(|_, _, _| Box::pin(ready(Ok(true))));
+        publisher
+            .expect_publish()
+            .withf(|hardware_action| {
+                *hardware_action == HardwareAction::START(HardwareType::Cooling.name().to_string())
+            })
+            .once()
+            .return_once(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| *hardware_type == Some(HardwareType::Cooling))
+            .once()
+            .return_once(|_, _| Box::pin(ready(Ok(()))));
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 36.0,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_clear_an_expired_override_and_resume_the_profile() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_recorded_switches(&mut repository);
+        let now = OffsetDateTime::now_utc();
+        expect_newer_reading(&mut repository);
+        repository
+            .expect_fetch_session_settings()
+            .return_once(move |_| Box::pin(ready(Ok(overridden_settings(now - Duration::minutes(1))))));
+        repository
+            .expect_update_hardware_override()
+            .withf(|_, hardware_override, event_id| hardware_override.is_none() && event_id.is_none())
+            .once()
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        repository
+            .expect_fetch_commands_by_order()
+            .times(2)
+            .returning(|_, _, _| Box::pin(ready(Ok(vec![]))));
+        repository
+            .expect_fetch_active_hardware_type()
+            .returning(|_| Box::pin(ready(Ok(None))));
+        expect_completion(&mut repository, &mut publisher);
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::at(now),
+            ControllerSettings::default(),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 18.0,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn process_should_ignore_readings_of_a_finished_session() {
+        let (mut repository, mut publisher) = (MockCommandDrivenPort::new(), MockPublisherDrivenPort::new());
+        expect_newer_reading(&mut repository);
+        repository.expect_fetch_session_settings().return_once(|_| {
+            Box::pin(ready(Ok(SessionSettings {
+                status: SessionStatus::Completed {
+                    at: OffsetDateTime::now_utc(),
+                },
+                ..Default::default()
+            })))
+        });
+        repository.expect_fetch_commands_by_order().never();
+        repository.expect_fetch_active_hardware_type().never();
+        publisher.expect_publish().never();
+        let service = CommandExecutorService::new(
+            Arc::new(repository),
+            publisher,
+            FakeSessionEventPublisher::default(),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        service
+            .process(TrackingMessageData {
+                temperature: 30.0,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    fn expect_completed_status(repository: &mut MockCommandDrivenPort) {
+        repository
+            .expect_update_session_status()
+            .withf(|_, status| matches!(status, SessionStatus::Completed { .. }))
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+    }
+
+    // both hardware are stopped whatever their protection once the profile is over
+    fn expect_completion(repository: &mut MockCommandDrivenPort, publisher: &mut MockPublisherDrivenPort) {
+        repository
+            .expect_fetch_hardware_id()
+            .times(2)
+            .returning(|_, hardware_type| Box::pin(ready(Ok(hardware_type.name().to_string()))));
+        publisher
+            .expect_publish()
+            .withf(|action| matches!(action, HardwareAction::STOP(_)))
+            .times(2)
+            .returning(|_| Box::pin(ready(Ok(()))));
+        repository
+            .expect_update_active_hardware_type()
+            .withf(|_, hardware_type| hardware_type.is_none())
+            .once()
+            .returning(|_, _| Box::pin(ready(Ok(()))));
+        expect_completed_status(repository);
+    }
+
+    fn expect_recorded_switches(repository: &mut MockCommandDrivenPort) {
+        repository
+            .expect_record_hardware_switch()
+            .returning(|_, _, _, _, _| Box::pin(ready(Ok(()))));
+    }
+
+    fn expect_new_event(repository: &mut MockCommandDrivenPort) {
+        repository
+            .expect_is_new_event()
+            .returning(|_| Box::pin(ready(Ok(true))));
+    }
+
+    fn expect_newer_reading(repository: &mut MockCommandDrivenPort) {
+        repository
+            .expect_is_new_reading()
+            .returning(|_, _, _| Box::pin(ready(Ok(true))));
+        repository
+            .expect_record_reading()
+            .returning(|_, _, _, _, _| Box::pin(ready(Ok(true))));
+    }
 }
@@ -1,24 +1,30 @@
 use std::sync::Arc;
 
-use anyhow::bail;
-use log::warn;
-use time::Duration;
+use log::info;
+use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::{
     domain::{
         command::{CommandStatus, NewCommand, SessionData},
+        controller::ControllerSettings,
         error::CommandSchedulerServiceError,
-        message::{FermentationStep, HardwareType, ScheduleMessageData},
+        message::{FermentationStep, HardwareType, RescheduleMessageData, ScheduleMessageData},
+        session::{HardwareLockout, SessionSettings, SessionStatus, TemperatureLimits},
+    },
+    port::{
+        clock::ClockPort,
+        command::{CommandDrivenPort, CommandSchedulerDriverPort},
     },
-    port::command::{CommandDrivenPort, CommandSchedulerDriverPort},
 };
 
-pub struct CommandSchedulerService<R: CommandDrivenPort> {
+pub struct CommandSchedulerService<R: CommandDrivenPort, C: ClockPort> {
     repository: Arc<R>,
+    clock: C,
+    settings: ControllerSettings,
 }
 
-impl<R: CommandDrivenPort> CommandSchedulerDriverPort for CommandSchedulerService<R> {
+impl<R: CommandDrivenPort, C: ClockPort> CommandSchedulerDriverPort for CommandSchedulerService<R, C> {
     async fn schedule(&self, data: ScheduleMessageData) -> Result<u64, CommandSchedulerServiceError> {
         self.validate(&data.steps)?;
         let heating = data
@@ -31,15 +37,47 @@ impl<R: CommandDrivenPort> CommandSchedulerDriverPort for CommandSchedulerServic
                 "Unable to find cooling hardware".into(),
             ))
             .cloned()?;
-        let cmds = Self::build_commands(&data)?;
-        self.repository
-            .insert(cmds, heating, cooling)
+        let settings = self.session_settings(&data)?;
+        let cmds = Self::build_commands(data.session_id, &data.steps, self.clock.now())?;
+        let inserted = self
+            .repository
+            .insert(cmds, heating, cooling, settings, data.event_id)
+            .await
+            .map_err(|err| CommandSchedulerServiceError::TechnicalError(format!("{:?}", err.root_cause())))?;
+        if inserted == 0 {
+            info!(
+                "Schedule event {} of session {} has already been processed",
+                data.event_id, data.session_id
+            );
+        }
+        Ok(inserted)
+    }
+
+    async fn reschedule(&self, data: RescheduleMessageData) -> Result<u64, CommandSchedulerServiceError> {
+        self.validate(&data.steps)?;
+        let cmds = Self::build_commands(data.session_id, &data.steps, self.clock.now())?;
+        let inserted = self
+            .repository
+            .replace_commands(
+                data.session_id,
+                cmds,
+                data.replace_running,
+                self.clock.now(),
+                data.event_id,
+            )
             .await
-            .map_err(|err| CommandSchedulerServiceError::TechnicalError(format!("{:?}", err.root_cause())))
+            .map_err(|err| CommandSchedulerServiceError::TechnicalError(format!("{:?}", err.root_cause())))?;
+        if inserted == 0 {
+            info!(
+                "Reschedule event {} of session {} has already been processed",
+                data.event_id, data.session_id
+            );
+        }
+        Ok(inserted)
     }
 }
 
-impl<R: CommandDrivenPort> CommandSchedulerService<R> {
+impl<R: CommandDrivenPort, C: ClockPort> CommandSchedulerService<R, C> {
     fn validate(&self, steps: &[FermentationStep]) -> Result<bool, CommandSchedulerServiceError> {
         if steps.is_empty() {
             return Err(CommandSchedulerServiceError::NoFermentationStep);
@@ -65,14 +103,47 @@ impl<R: CommandDrivenPort> CommandSchedulerService<R> {
         }
     }
 
+    fn session_settings(&self, data: &ScheduleMessageData) -> Result<SessionSettings, CommandSchedulerServiceError> {
+        let hysteresis = data.hysteresis.unwrap_or(self.settings.hysteresis);
+        if !Self::is_valid_hysteresis(hysteresis) {
+            return Err(CommandSchedulerServiceError::InvalidHysteresis(hysteresis));
+        }
+        let temperature_limits = TemperatureLimits {
+            min: data.min_temperature.or(self.settings.temperature_limits.min),
+            max: data.max_temperature.or(self.settings.temperature_limits.max),
+        };
+        if let (Some(min), Some(max)) = (temperature_limits.min, temperature_limits.max)
+            && min >= max
+        {
+            return Err(CommandSchedulerServiceError::InvalidTemperatureLimits(min, max));
+        }
+        Ok(SessionSettings {
+            hysteresis,
+            control_mode: data.control_mode.clone().unwrap_or_default(),
+            temperature_limits,
+            lockout: HardwareLockout::default(),
+            paused_at: None,
+            hardware_override: None,
+            status: SessionStatus::Active,
+        })
+    }
+
+    // the hysteresis is stored as NUMERIC(3,1), it would be rounded otherwise
+    fn is_valid_hysteresis(hysteresis: f32) -> bool {
+        let tenths = hysteresis * 10.0;
+        hysteresis.is_finite() && (0.0..=99.9).contains(&hysteresis) && (tenths - tenths.round()).abs() < 1e-3
+    }
+
     fn calculate_required_amount_of_command(previous_target_temp: f32, next_target_temp: f32, rate: f32) -> i32 {
         let delta = (previous_target_temp - next_target_temp).abs();
         (delta / rate).ceil() as i32
     }
-    fn build_command(session_id: Uuid, step_position: usize, target_temp: f32, duration: Duration) -> NewCommand {
+    fn build_command(
+        session_id: Uuid, step_position: usize, target_temp: f32, duration: Duration, scheduled_at: OffsetDateTime,
+    ) -> NewCommand {
         NewCommand {
             id: Uuid::new_v4(),
-            sent_at: None,
+            sent_at: Some(scheduled_at),
             version: 1,
             session_data: SessionData {
                 id: session_id,
@@ -84,15 +155,16 @@ impl<R: CommandDrivenPort> CommandSchedulerService<R> {
         }
     }
 
-    fn build_commands(data: &ScheduleMessageData) -> Result<Vec<NewCommand>, CommandSchedulerServiceError> {
-        Ok(data
-            .steps
+    fn build_commands(
+        session_id: Uuid, steps: &[FermentationStep], scheduled_at: OffsetDateTime,
+    ) -> Result<Vec<NewCommand>, CommandSchedulerServiceError> {
+        Ok(steps
             .iter()
             .map(|step| -> Result<Vec<NewCommand>, CommandSchedulerServiceError> {
                 match step.rate.as_ref() {
                     Some(rate) => {
                         if step.position > 0 {
-                            let prev_step = data.steps.iter().find(|s| s.position == step.position - 1).ok_or(
+                            let prev_step = steps.iter().find(|s| s.position == step.position - 1).ok_or(
                                 CommandSchedulerServiceError::InvalidPosition(step.position - 1, "doesn't exist"),
                             )?;
 
@@ -119,7 +191,13 @@ impl<R: CommandDrivenPort> CommandSchedulerService<R> {
                                             temp
                                         }
                                     };
-                                    Self::build_command(data.session_id, step.position, target_temp, rate.duration)
+                                    Self::build_command(
+                                        session_id,
+                                        step.position,
+                                        target_temp,
+                                        rate.duration,
+                                        scheduled_at,
+                                    )
                                 })
                                 .collect())
                         } else {
@@ -130,10 +208,11 @@ impl<R: CommandDrivenPort> CommandSchedulerService<R> {
                         }
                     }
                     None => Ok(vec![Self::build_command(
-                        data.session_id,
+                        session_id,
                         step.position,
                         step.target_temperature,
                         step.duration,
+                        scheduled_at,
                     )]),
                 }
             })
@@ -141,30 +220,43 @@ impl<R: CommandDrivenPort> CommandSchedulerService<R> {
             .map(|vec_of_vecs| vec_of_vecs.into_iter().flatten().collect()))? // Flatten the Vec<Vec<NewCommand>>
     }
 }
-impl<R: CommandDrivenPort> CommandSchedulerService<R> {
-    pub fn new(repository: Arc<R>) -> Self {
-        CommandSchedulerService { repository }
+impl<R: CommandDrivenPort, C: ClockPort> CommandSchedulerService<R, C> {
+    pub fn new(repository: Arc<R>, clock: C, settings: ControllerSettings) -> Self {
+        CommandSchedulerService {
+            repository,
+            clock,
+            settings,
+        }
     }
 }
 #[cfg(test)]
 mod test {
-    use std::sync::Arc;
+    use std::{future::ready, sync::Arc};
 
-    use time::Duration;
+    use time::{Duration, OffsetDateTime};
 
     use crate::{
         domain::{
+            controller::ControllerSettings,
             error::CommandSchedulerServiceError,
-            message::{FermentationStep, Hardware, HardwareType, Rate, ScheduleMessageData},
+            message::{FermentationStep, Hardware, HardwareType, Rate, RescheduleMessageData, ScheduleMessageData},
+            session::{ControlMode, TemperatureLimits},
+        },
+        port::{
+            clock::FakeClock,
+            command::{CommandSchedulerDriverPort, MockCommandDrivenPort},
         },
-        port::command::MockCommandDrivenPort,
         service::command_scheduler_service::CommandSchedulerService,
     };
 
     #[test]
     fn should_not_validate_on_empty_step() {
         let repository = MockCommandDrivenPort::new();
-        let service = CommandSchedulerService::new(Arc::new(repository));
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         let err = service.validate(&[]).unwrap_err();
         assert_eq!(err, CommandSchedulerServiceError::NoFermentationStep);
     }
@@ -172,7 +264,11 @@ mod test {
     #[test]
     fn should_not_validate_on_wrong_position() {
         let repository = MockCommandDrivenPort::new();
-        let service = CommandSchedulerService::new(Arc::new(repository));
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         let step_1 = FermentationStep {
             position: 0,
             target_temperature: 20.0,
@@ -197,7 +293,11 @@ mod test {
     #[test]
     fn should_not_validate_when_rate_on_first_step() {
         let repository = MockCommandDrivenPort::new();
-        let service = CommandSchedulerService::new(Arc::new(repository));
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         let step = FermentationStep {
             position: 0,
             target_temperature: 20.0,
@@ -218,7 +318,11 @@ mod test {
     #[test]
     fn should_validate_steps() {
         let repository = MockCommandDrivenPort::new();
-        let service = CommandSchedulerService::new(Arc::new(repository));
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
         let step_1 = FermentationStep {
             position: 0,
             target_temperature: 20.0,
@@ -242,7 +346,7 @@ mod test {
         let previous_target_temp = 20.4;
         let next_target_temp = 3.2;
         let rate = 2.4;
-        let amount = CommandSchedulerService::<MockCommandDrivenPort>::calculate_required_amount_of_command(
+        let amount = CommandSchedulerService::<MockCommandDrivenPort, FakeClock>::calculate_required_amount_of_command(
             previous_target_temp,
             next_target_temp,
             rate,
@@ -273,8 +377,14 @@ mod test {
                 },
             ],
             steps: vec![step_1],
+            ..Default::default()
         };
-        let err = CommandSchedulerService::<MockCommandDrivenPort>::build_commands(&data).unwrap_err();
+        let err = CommandSchedulerService::<MockCommandDrivenPort, FakeClock>::build_commands(
+            data.session_id,
+            &data.steps,
+            OffsetDateTime::now_utc(),
+        )
+        .unwrap_err();
         assert!(matches!(err, CommandSchedulerServiceError::InvalidPosition(..)))
     }
     #[test]
@@ -310,8 +420,14 @@ mod test {
                 },
             ],
             steps: vec![step_1, step_2, step_3],
+            ..Default::default()
         };
-        let new_commands = CommandSchedulerService::<MockCommandDrivenPort>::build_commands(&data).unwrap();
+        let new_commands = CommandSchedulerService::<MockCommandDrivenPort, FakeClock>::build_commands(
+            data.session_id,
+            &data.steps,
+            OffsetDateTime::now_utc(),
+        )
+        .unwrap();
         assert_eq!(new_commands.len(), 3);
         let first = new_commands.first().unwrap();
         let second = new_commands.get(1).unwrap();
@@ -363,8 +479,14 @@ mod test {
                 },
             ],
             steps: vec![step_1, step_2, step_3],
+            ..Default::default()
         };
-        let new_commands = CommandSchedulerService::<MockCommandDrivenPort>::build_commands(&data).unwrap();
+        let new_commands = CommandSchedulerService::<MockCommandDrivenPort, FakeClock>::build_commands(
+            data.session_id,
+            &data.steps,
+            OffsetDateTime::now_utc(),
+        )
+        .unwrap();
         let first = new_commands.first().unwrap();
         let second = new_commands.get(1).unwrap();
         let third = new_commands.get(2).unwrap();
@@ -394,4 +516,190 @@ mod test {
         assert_eq!(ninth.value, 2.0); //target_temperature is the limit
         assert_eq!(ninth.session_data.step_position, 2);
     }
+
+    #[test]
+    fn should_use_controller_hysteresis_when_not_overridden() {
+        let repository = MockCommandDrivenPort::new();
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            },
+        );
+        let data = ScheduleMessageData::default();
+        let settings = service.session_settings(&data).unwrap();
+        assert_eq!(settings.hysteresis, 0.5);
+        assert_eq!(settings.control_mode, ControlMode::Hysteresis);
+    }
+    #[test]
+    fn should_use_session_settings_when_overridden() {
+        let repository = MockCommandDrivenPort::new();
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings {
+                hysteresis: 0.5,
+                ..Default::default()
+            },
+        );
+        let data = ScheduleMessageData {
+            session_id: uuid::Uuid::new_v4(),
+            hysteresis: Some(1.0),
+            control_mode: Some(ControlMode::Pid),
+            ..Default::default()
+        };
+        let settings = service.session_settings(&data).unwrap();
+        assert_eq!(settings.hysteresis, 1.0);
+        assert_eq!(settings.control_mode, ControlMode::Pid);
+    }
+    #[test]
+    fn should_not_accept_negative_hysteresis() {
+        let repository = MockCommandDrivenPort::new();
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let data = ScheduleMessageData {
+            session_id: uuid::Uuid::new_v4(),
+            hysteresis: Some(-0.5),
+            ..Default::default()
+        };
+        let err = service.session_settings(&data).unwrap_err();
+        assert_eq!(err, CommandSchedulerServiceError::InvalidHysteresis(-0.5));
+    }
+    #[test]
+    fn should_only_accept_hysteresis_storable_without_rounding() {
+        let repository = MockCommandDrivenPort::new();
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let settings_of = |hysteresis: f32| {
+            service.session_settings(&ScheduleMessageData {
+                hysteresis: Some(hysteresis),
+                ..Default::default()
+            })
+        };
+        for hysteresis in [0.0, 0.3, 1.5, 99.9] {
+            assert_eq!(settings_of(hysteresis).unwrap().hysteresis, hysteresis);
+        }
+        for hysteresis in [0.25, 100.0, f32::INFINITY, f32::MAX] {
+            assert_eq!(
+                settings_of(hysteresis).unwrap_err(),
+                CommandSchedulerServiceError::InvalidHysteresis(hysteresis)
+            );
+        }
+        assert!(matches!(
+            settings_of(f32::NAN).unwrap_err(),
+            CommandSchedulerServiceError::InvalidHysteresis(h) if h.is_nan()
+        ));
+    }
+    #[test]
+    fn should_fallback_to_controller_temperature_limits() {
+        let repository = MockCommandDrivenPort::new();
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings {
+                temperature_limits: TemperatureLimits {
+                    min: Some(0.0),
+                    max: Some(35.0),
+                },
+                ..Default::default()
+            },
+        );
+        let data = ScheduleMessageData {
+            max_temperature: Some(25.0),
+            ..Default::default()
+        };
+        let settings = service.session_settings(&data).unwrap();
+        assert_eq!(
+            settings.temperature_limits,
+            TemperatureLimits {
+                min: Some(0.0),
+                max: Some(25.0)
+            }
+        );
+    }
+    #[test]
+    fn should_not_accept_min_temperature_above_max_temperature() {
+        let repository = MockCommandDrivenPort::new();
+        let service = CommandSchedulerService::new(
+            Arc::new(repository),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let data = ScheduleMessageData {
+            min_temperature: Some(20.0),
+            max_temperature: Some(10.0),
+            ..Default::default()
+        };
+        let err = service.session_settings(&data).unwrap_err();
+        assert_eq!(err, CommandSchedulerServiceError::InvalidTemperatureLimits(20.0, 10.0));
+    }
+
+    #[tokio::test]
+    async fn should_replace_remaining_commands_on_reschedule() {
+        let mut repository = MockCommandDrivenPort::new();
+        let (session_id, event_id) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let now = OffsetDateTime::now_utc();
+        repository
+            .expect_replace_commands()
+            .withf(move |id, commands, replace_running, replaced_at, replace_event_id| {
+                *id == session_id
+                    && commands.len() == 3
+                    && commands.iter().all(|c| c.session_data.id == session_id)
+                    && *replace_running
+                    && *replaced_at == now
+                    && *replace_event_id == event_id
+            })
+            .once()
+            .returning(|_, commands, _, _, _| Box::pin(ready(Ok(commands.len() as u64))));
+        let service =
+            CommandSchedulerService::new(Arc::new(repository), FakeClock::at(now), ControllerSettings::default());
+        let data = RescheduleMessageData {
+            event_id,
+            session_id,
+            steps: vec![
+                FermentationStep {
+                    position: 0,
+                    target_temperature: 18.0,
+                    duration: Duration::hours(24),
+                    rate: None,
+                },
+                FermentationStep {
+                    position: 1,
+                    target_temperature: 14.0,
+                    duration: Duration::hours(48),
+                    rate: Some(Rate {
+                        value: 2,
+                        duration: Duration::hours(12),
+                    }),
+                },
+            ],
+            replace_running: true,
+        };
+        assert_eq!(service.reschedule(data).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_not_reschedule_invalid_steps() {
+        let service = CommandSchedulerService::new(
+            Arc::new(MockCommandDrivenPort::new()),
+            FakeClock::default(),
+            ControllerSettings::default(),
+        );
+        let data = RescheduleMessageData {
+            session_id: uuid::Uuid::new_v4(),
+            steps: vec![],
+            replace_running: false,
+            ..Default::default()
+        };
+        let err = service.reschedule(data).await.unwrap_err();
+        assert_eq!(err, CommandSchedulerServiceError::NoFermentationStep);
+    }
 }